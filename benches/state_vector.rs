@@ -0,0 +1,71 @@
+// Serial against parallel state vector kernels
+//
+// cargo bench --features parallel [-- first_qubits last_qubits]
+//
+// Runs the same circuit (a layer of hadamards, a layer of y rotations and a ladder of cnots) on a
+// serial and a parallel register for 20 to 26 qubits, prints the times and checks that both end
+// in the same state. Only one register lives at a time, 26 qubits take 1 GiB.
+use learning_quantum_computing::module::gates::basic::SingleInputGate;
+use learning_quantum_computing::module::state_vector::{rotation_y, StateVector};
+use std::time::{Duration, Instant};
+
+fn run(state: &mut StateVector) -> Duration {
+    let n = state.no_qubits();
+    let start = Instant::now();
+    for qubit in 0..n {
+        state.apply_gate(SingleInputGate::Hadamard, qubit);
+    }
+    for qubit in 0..n {
+        state.apply(&rotation_y(0.1 * qubit as f64), qubit);
+    }
+    for qubit in 1..n {
+        state.cnot(qubit - 1, qubit);
+    }
+    let _ = (0..n)
+        .map(|qubit| state.probability_zero(qubit))
+        .sum::<f64>();
+    start.elapsed()
+}
+
+/// some numbers that change with every amplitude
+fn fingerprint(state: &StateVector) -> Vec<f64> {
+    (0..state.no_qubits())
+        .map(|qubit| state.probability_zero(qubit))
+        .chain(state.amplitudes().iter().step_by(997).map(|a| a.re))
+        .collect()
+}
+
+fn main() {
+    let args: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|arg| arg.parse().ok())
+        .collect();
+    let (first, last) = match args[..] {
+        [first, last] => (first, last),
+        _ => (20, 26),
+    };
+    println!(
+        "{} threads\nqubits   serial   parallel   speedup",
+        rayon::current_num_threads()
+    );
+    for n in first..=last {
+        let mut serial = StateVector::new(n).with_parallel_threshold(usize::MAX);
+        let serial_time = run(&mut serial);
+        let expected = fingerprint(&serial);
+        drop(serial);
+
+        let mut parallel = StateVector::new(n).with_parallel_threshold(0);
+        let parallel_time = run(&mut parallel);
+        let same = fingerprint(&parallel)
+            .iter()
+            .zip(&expected)
+            .all(|(a, b)| (a - b).abs() < 1e-12);
+        assert!(same, "parallel and serial results differ at {n} qubits");
+        println!(
+            "{n:>6} {:>8.0}ms {:>8.0}ms {:>8.2}x",
+            serial_time.as_secs_f64() * 1e3,
+            parallel_time.as_secs_f64() * 1e3,
+            serial_time.as_secs_f64() / parallel_time.as_secs_f64()
+        );
+    }
+}
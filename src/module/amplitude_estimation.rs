@@ -0,0 +1,314 @@
+// Amplitude estimation and quantum counting (Brassard, Hoyer, Mosca, Tapp)
+//
+// A preparation circuit A turns |0..0> into sqrt(1 - a) |bad> + sqrt(a) |good>, where the good
+// basis states are picked by a predicate. Write a = sin^2(theta). The grover operator
+//
+//     Q = A (2|0><0| - I) A^dagger S_good
+//
+// rotates by 2 theta in the plane of |good> and |bad>, so its eigenvalues are e^(+-2i theta).
+//
+// Canonical estimation reads 2 theta with phase estimation on Q: a counting register of m
+// qubits gives y with theta ~ pi y / 2^m, which is accurate to about pi / 2^m with 2^m - 1
+// applications of Q. Quantum counting is the special case of A = hadamards on n qubits, where
+// a is the fraction of marked inputs, so 2^n a is their number, the size Grover's search needs.
+//
+// Maximum likelihood estimation (Suzuki et al.) avoids the QFT and the controlled operators:
+// after Q^k A the good states are measured with probability sin^2((2k + 1) theta). Sampling
+// that for k = 0, 1, 2, 4, .. and maximizing the likelihood of all the outcomes gives nearly the
+// same accuracy from shallow circuits. The confidence interval follows from the fisher
+// information sum_k shots (2k + 1)^2 / (a (1 - a)).
+use crate::module::circuit::Circuit;
+use crate::module::phase_estimation::{phase_estimation, register_probabilities};
+use crate::module::state_vector::{StateVector, C64};
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::f64::consts::{FRAC_PI_2, PI};
+
+#[derive(Clone, Debug)]
+pub struct AmplitudeEstimate {
+    pub estimate: f64,
+    /// contains the amplitude with high probability: at least 8 / pi^2 ~ 81% for the
+    /// canonical estimate, 95% for maximum likelihood
+    pub confidence_interval: (f64, f64),
+    /// applications of the grover operator over all shots
+    pub oracle_calls: usize,
+}
+
+pub struct AmplitudeEstimation<F> {
+    preparation: Circuit,
+    inverse: Circuit,
+    good: F,
+    shots: usize,
+}
+
+impl<F: Fn(usize) -> bool> AmplitudeEstimation<F> {
+    /// `good` takes a basis state of the preparation circuit, which must not have parameters
+    pub fn new(preparation: Circuit, good: F) -> Self {
+        assert!(
+            preparation.parameters().is_empty(),
+            "the preparation has unbound parameters"
+        );
+        Self {
+            inverse: preparation.inverse(&HashMap::new()),
+            preparation,
+            good,
+            shots: 100,
+        }
+    }
+
+    /// number of samples of every circuit
+    pub fn with_shots(mut self, shots: usize) -> Self {
+        self.shots = shots;
+        self
+    }
+
+    /// the exact amplitude a, the probability to measure a good state after the preparation
+    pub fn probability(&self) -> f64 {
+        self.good_probability(&self.preparation.state(&HashMap::new()))
+    }
+
+    fn good_probability(&self, state: &StateVector) -> f64 {
+        state
+            .amplitudes()
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| (self.good)(*index))
+            .map(|(_, amplitude)| amplitude.norm_sqr())
+            .sum()
+    }
+
+    /// the grover operator on the qubits from `offset`, for the basis states where all
+    /// `controls` are |1>
+    fn grover(&self, state: &mut StateVector, controls: &[usize], offset: usize) {
+        let n = self.preparation.no_qubits();
+        let total = state.no_qubits();
+        let shift = total - offset - n;
+        let control_mask = controls
+            .iter()
+            .fold(0, |mask, control| mask | 1 << (total - 1 - control));
+        let register = |index: usize| (index >> shift) & ((1 << n) - 1);
+        let sign = |flip: bool| C64::new(if flip { -1.0 } else { 1.0 }, 0.0);
+        let values = HashMap::new();
+
+        state.apply_diagonal(|index| {
+            sign(index & control_mask == control_mask && (self.good)(register(index)))
+        });
+        self.inverse
+            .apply_controlled(state, controls, offset, &values);
+        state.apply_diagonal(|index| {
+            sign(index & control_mask == control_mask && register(index) != 0)
+        });
+        self.preparation
+            .apply_controlled(state, controls, offset, &values);
+    }
+
+    /// phase estimation of the grover operator with `precision` counting qubits, the most
+    /// frequent outcome of the shots is the estimate. The interval is the bound
+    /// |estimate - a| <= 2 pi sqrt(a (1 - a)) / M + pi^2 / M^2 for M = 2^precision, which holds
+    /// with probability at least 8 / pi^2
+    pub fn canonical(&self, precision: usize) -> AmplitudeEstimate {
+        let offset = precision;
+        let mut state = StateVector::new(precision + self.preparation.no_qubits());
+        self.preparation
+            .apply_controlled(&mut state, &[], offset, &HashMap::new());
+        let counting: Vec<usize> = (0..precision).collect();
+        phase_estimation(&mut state, &counting, &mut |state, control, power| {
+            for _ in 0..power {
+                self.grover(state, &[control], offset);
+            }
+        });
+        let probabilities = register_probabilities(&state, &counting);
+
+        // y and M - y come from the eigenvalues e^(+-2i theta) and give the same amplitude
+        let m = 1usize << precision;
+        let mut counts = vec![0; m / 2 + 1];
+        let mut rng = rand::rng();
+        for _ in 0..self.shots {
+            let y = sample(&probabilities, &mut rng);
+            counts[y.min(m - y)] += 1;
+        }
+        let y = (0..counts.len())
+            .max_by_key(|y| counts[*y])
+            .expect("at least one outcome");
+        let estimate = (PI * y as f64 / m as f64).sin().powi(2);
+        let m = m as f64;
+        let error = 2.0 * PI * (estimate * (1.0 - estimate)).sqrt() / m + PI * PI / (m * m);
+        AmplitudeEstimate {
+            estimate,
+            confidence_interval: ((estimate - error).max(0.0), (estimate + error).min(1.0)),
+            oracle_calls: self.shots * (m as usize - 1),
+        }
+    }
+
+    /// maximum likelihood estimation from the circuits Q^k A for k = 0 and k = 2^j, j < `depth`
+    pub fn maximum_likelihood(&self, depth: usize) -> AmplitudeEstimate {
+        let powers: Vec<usize> = std::iter::once(0)
+            .chain((0..depth).map(|j| 1 << j))
+            .collect();
+        let mut rng = rand::rng();
+        let hits: Vec<usize> = powers
+            .iter()
+            .map(|power| {
+                let mut state = self.preparation.state(&HashMap::new());
+                for _ in 0..*power {
+                    self.grover(&mut state, &[], 0);
+                }
+                let p = self.good_probability(&state).clamp(0.0, 1.0);
+                (0..self.shots).filter(|_| rng.random_bool(p)).count()
+            })
+            .collect();
+
+        let shots = self.shots as f64;
+        let log_likelihood = |theta: f64| -> f64 {
+            powers
+                .iter()
+                .zip(&hits)
+                .map(|(power, hits)| {
+                    let p = ((2 * power + 1) as f64 * theta).sin().powi(2);
+                    let hits = *hits as f64;
+                    hits * p.max(1e-300).ln() + (shots - hits) * (1.0 - p).max(1e-300).ln()
+                })
+                .sum()
+        };
+        // the likelihood oscillates with the largest power, so look at a fine grid first
+        let steps = 50 * (2 * powers.last().unwrap() + 1);
+        let step = FRAC_PI_2 / steps as f64;
+        let best = (0..=steps)
+            .map(|i| i as f64 * step)
+            .max_by(|a, b| log_likelihood(*a).total_cmp(&log_likelihood(*b)))
+            .unwrap();
+        let theta = golden_section_max(
+            &log_likelihood,
+            (best - step).max(0.0),
+            (best + step).min(FRAC_PI_2),
+        );
+
+        let estimate = theta.sin().powi(2);
+        let fisher = powers
+            .iter()
+            .map(|power| shots * ((2 * power + 1) as f64).powi(2))
+            .sum::<f64>()
+            / (estimate * (1.0 - estimate)).max(1e-12);
+        // 95 percent
+        let error = 1.96 / fisher.sqrt();
+        AmplitudeEstimate {
+            estimate,
+            confidence_interval: ((estimate - error).max(0.0), (estimate + error).min(1.0)),
+            oracle_calls: self.shots * powers.iter().sum::<usize>(),
+        }
+    }
+}
+
+/// index drawn from a distribution
+fn sample(probabilities: &[f64], rng: &mut impl Rng) -> usize {
+    let mut r = rng.random::<f64>() * probabilities.iter().sum::<f64>();
+    for (index, p) in probabilities.iter().enumerate() {
+        if r < *p {
+            return index;
+        }
+        r -= p;
+    }
+    probabilities.len() - 1
+}
+
+/// maximum of a function with a single peak in [low, high]
+fn golden_section_max(function: &dyn Fn(f64) -> f64, mut low: f64, mut high: f64) -> f64 {
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    for _ in 0..100 {
+        let a = high - ratio * (high - low);
+        let b = low + ratio * (high - low);
+        if function(a) < function(b) {
+            low = a;
+        } else {
+            high = b;
+        }
+    }
+    (low + high) / 2.0
+}
+
+#[derive(Clone, Debug)]
+pub struct Count {
+    pub count: usize,
+    pub estimate: f64,
+    pub confidence_interval: (f64, f64),
+}
+
+/// the number of inputs among the 2^no_qubits that `marked` accepts, from canonical amplitude
+/// estimation on the grover operator with `precision` counting qubits
+pub fn quantum_count(no_qubits: usize, precision: usize, marked: impl Fn(usize) -> bool) -> Count {
+    let mut uniform = Circuit::new(no_qubits);
+    for qubit in 0..no_qubits {
+        uniform.h(qubit);
+    }
+    let result = AmplitudeEstimation::new(uniform, marked).canonical(precision);
+    let size = (1usize << no_qubits) as f64;
+    Count {
+        count: (result.estimate * size).round() as usize,
+        estimate: result.estimate * size,
+        confidence_interval: (
+            result.confidence_interval.0 * size,
+            result.confidence_interval.1 * size,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn contains(interval: (f64, f64), value: f64) -> bool {
+        interval.0 <= value && value <= interval.1
+    }
+
+    #[test]
+    fn test_quantum_count() {
+        let count = quantum_count(4, 6, |x| [3, 7, 11].contains(&x));
+        assert_eq!(count.count, 3);
+        assert!(contains(count.confidence_interval, 3.0));
+
+        // half of the inputs is exact with two counting qubits already
+        let count = quantum_count(4, 2, |x| x % 2 == 0);
+        assert!((count.estimate - 8.0).abs() < 1e-9);
+        assert_eq!(quantum_count(3, 3, |_| false).count, 0);
+    }
+
+    #[test]
+    fn test_canonical() {
+        // a = sin^2(angle / 2) = 0.3
+        let angle = 2.0 * 0.3f64.sqrt().asin();
+        let mut preparation = Circuit::new(2);
+        preparation.ry(angle, 0).cnot(0, 1).h(1);
+        // good when qubit 0 is |1>
+        let estimation = AmplitudeEstimation::new(preparation, |x| x & 2 != 0);
+        assert!((estimation.probability() - 0.3).abs() < 1e-12);
+        let result = estimation.canonical(6);
+        assert!(contains(result.confidence_interval, 0.3));
+        assert!((result.estimate - 0.3).abs() < 0.03);
+        assert_eq!(result.oracle_calls, 100 * 63);
+    }
+
+    #[test]
+    fn test_maximum_likelihood() {
+        let mut preparation = Circuit::new(3);
+        preparation
+            .h(0)
+            .ry(0.7, 1)
+            .cnot(0, 2)
+            .ry(-1.9, 2)
+            .cnot(1, 2)
+            .rx(0.4, 0);
+        let estimation = AmplitudeEstimation::new(preparation, |x| x == 5 || x == 6);
+        let a = estimation.probability();
+        let result = estimation.maximum_likelihood(6);
+        assert!(
+            (result.estimate - a).abs() < 0.005,
+            "{} {a}",
+            result.estimate
+        );
+        let width = result.confidence_interval.1 - result.confidence_interval.0;
+        assert!(width < 0.01);
+        // 0 + 1 + 2 + .. + 32 applications of Q per shot
+        assert_eq!(result.oracle_calls, 100 * 63);
+    }
+}
@@ -1,5 +1,8 @@
+use crate::module::backend::Backend;
+use crate::module::circuit::Circuit;
 use crate::module::entangled_particle_n::EntangledParticleN;
 use crate::module::gates::basic::*;
+use crate::module::oracle::phase_oracle;
 use crate::module::{
     gates::{
         self,
@@ -8,15 +11,30 @@ use crate::module::{
     utils::round_to_n_decimal_places,
 };
 use nalgebra::SVector;
-use nalgebra::{ArrayStorage, Const};
+use std::collections::HashMap;
+
+/// tell a constant f from a balanced one with a single call of `oracle`, a bit flip oracle that
+/// flips qubit `no_inputs` by f of the inputs 0..no_inputs, for example
+/// `BooleanFunction::bit_flip_oracle`. Run as a phase oracle it only changes phases, and the
+/// inputs return to |00..0> exactly if f is constant
+pub fn is_constant<B: Backend>(no_inputs: usize, oracle: &Circuit) -> bool {
+    assert_eq!(oracle.no_qubits(), no_inputs + 1);
+    let mut state = B::allocate(no_inputs + 1);
+    for qubit in 0..no_inputs {
+        state.apply_gate(SingleInputGate::Hadamard, qubit);
+    }
+    phase_oracle(oracle, no_inputs).apply(&mut state, &HashMap::new());
+    for qubit in 0..no_inputs {
+        state.apply_gate(SingleInputGate::Hadamard, qubit);
+    }
+    // measure is true for |0>
+    (0..no_inputs).all(|qubit| state.measure(qubit))
+}
 
 mod tests {
 
-    use std::os::macos::raw::stat;
-
-    use nalgebra::{Matrix, Matrix2, Matrix3, Matrix4};
-
     use crate::module::entangled_particle_n;
+    use crate::module::oracle::BooleanFunction;
 
     use super::*;
 
@@ -29,16 +47,11 @@ mod tests {
 
         let hadamard2x2 = SingleInputGate::Hadamard.get_matrix::<2>(0);
         let hadamard8x8 = hadamard2x2.kronecker(&hadamard2x2).kronecker(&hadamard2x2);
-        let p_0 = Matrix2::new(1.0, 0.0, 0.0, 0.0);
-        let p_1 = Matrix2::new(0.0, 0.0, 0.0, 1.0);
-        let identity_2x2 = Matrix2::identity();
-        //let identity_4x4 = Matrix4::identity();
-        let x = SingleInputGate::X.get_matrix::<2>(0);
-
-        let f_balanced = p_0.kronecker(&identity_2x2).kronecker(&identity_2x2)
-            + p_1.kronecker(&identity_2x2).kronecker(&x);
-
-        let f_constant = Matrix::<f64, Const<8>, Const<8>, ArrayStorage<f64, 8, 8>>::identity();
+        // f(x) = x_0 is balanced, f(x) = 0 constant
+        let balanced = BooleanFunction::from_fn(2, |input| input >> 1 == 1);
+        let zero = BooleanFunction::from_fn(2, |_| false);
+        let f_balanced = balanced.bit_flip_oracle(3, &[0, 1], 2);
+        let f_constant = zero.bit_flip_oracle(3, &[0, 1], 2);
 
         // hadamard to input
         // puts inputs into superposition
@@ -47,9 +60,9 @@ mod tests {
         // f constant will put the superposition state into state where all phases are equal. I.e. all amplitudes are either -1 or 1
         // f balanced will put the superposition into a state where amplitudes are alternatively -1 or 1 with the same amount of each occuring
         if constant {
-            state.change_state_by_matrix(f_constant);
+            f_constant.apply(&mut state, &HashMap::new());
         } else {
-            state.change_state_by_matrix(f_balanced);
+            f_balanced.apply(&mut state, &HashMap::new());
         }
 
         // hadamard on input
@@ -60,8 +73,6 @@ mod tests {
         state.measure(0);
         state.measure(1);
         state.measure(2);
-        state.measure(3);
-        state.measure(4);
 
         // if there is a non zero probability (amplitude =!0) of measuring |00> then the function must
         // be balanced
@@ -78,4 +89,41 @@ mod tests {
         deutsch(true);
         deutsch(false);
     }
+
+    #[test]
+    fn test_is_constant_on_backends() {
+        use crate::module::stabilizer::StabilizerState;
+        use crate::module::state_vector::StateVector;
+
+        fn check_is_constant<B: Backend>(no_inputs: usize) {
+            let inputs: Vec<usize> = (0..no_inputs).collect();
+            let oracle = |f: BooleanFunction| f.bit_flip_oracle(no_inputs + 1, &inputs, no_inputs);
+            assert!(is_constant::<B>(
+                no_inputs,
+                &oracle(BooleanFunction::from_fn(no_inputs, |_| false))
+            ));
+            assert!(is_constant::<B>(
+                no_inputs,
+                &oracle(BooleanFunction::from_fn(no_inputs, |_| true))
+            ));
+            // f(x) = x_0 and the parity of all inputs
+            let first = BooleanFunction::from_fn(no_inputs, |input| input >> (no_inputs - 1) == 1);
+            assert!(!is_constant::<B>(no_inputs, &oracle(first)));
+            let parity = BooleanFunction::from_fn(no_inputs, |input| input.count_ones() % 2 == 1);
+            assert!(!is_constant::<B>(no_inputs, &oracle(parity)));
+        }
+
+        check_is_constant::<EntangledParticleN<8>>(2);
+        check_is_constant::<StateVector>(2);
+        check_is_constant::<StateVector>(8);
+
+        // a truth table of 2^200 entries is out of reach, the parity oracle is written out
+        let mut parity = Circuit::new(201);
+        for qubit in 0..200 {
+            parity.cnot(qubit, 200);
+        }
+        assert!(!is_constant::<StabilizerState>(200, &parity));
+        assert!(is_constant::<StabilizerState>(200, &Circuit::new(201)));
+        check_is_constant::<StabilizerState>(4);
+    }
 }
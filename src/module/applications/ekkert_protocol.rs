@@ -1,26 +1,20 @@
 use super::utils::round_to_n_decimal_places;
-use crate::module::entangled_particle_n::{self, EntangledParticleN};
-use nalgebra::{DVector, Matrix2, Matrix4, SVector, Vector2, Vector4};
+use crate::module::applications::superdense_coding::bell_pair;
+use crate::module::eavesdropper::{EavesdropReport, Eavesdropper};
+use crate::module::quantum_channel::{QuantumChannel, Tappable};
+use crate::module::state_vector::rotation_y;
 use rand::prelude::*;
 use std::{f64::consts::PI, vec};
 
+/// bell pairs shared by alice and bob, simulated on any backend eve can tap
 #[derive(Debug)]
-pub struct EntangledParticleStream {
-    particles: Vec<EntangledParticleN<4>>,
+pub struct EntangledParticleStream<B> {
+    particles: Vec<B>,
 }
 
-impl EntangledParticleStream {
+impl<B: Tappable> EntangledParticleStream<B> {
     fn new(len: usize) -> Self {
-        let mut particles = vec![];
-        for i in 0..len {
-            let prtcl = EntangledParticleN::new(SVector::<f64, 4>::new(
-                0.5f64.sqrt(),
-                0.0,
-                0.0,
-                0.5f64.sqrt(),
-            ));
-            particles.push(prtcl);
-        }
+        let particles = (0..len).map(|_| bell_pair()).collect();
         Self { particles }
     }
     fn rotate_to_basis(basis: &Basis) -> f64 {
@@ -31,23 +25,60 @@ impl EntangledParticleStream {
         }
     }
 
-    fn rotate_bases(&mut self, basis: &Vec<Basis>, alice: bool) {
-        for i in 0..basis.len() {
-            self.particles[i].swap_basis(basis[i].degrees(), 0);
+    // alice holds the first particle of every pair, bob the second
+    fn index(alice: bool) -> usize {
+        if alice {
+            0
+        } else {
+            1
         }
     }
 
-    fn measure_all(&mut self, alice: bool) -> Vec<bool> {
-        let mut res: Vec<bool> = vec![];
-
-        for particle in &mut self.particles {
-            res.push(particle.measure(0));
-        }
+    /// measure every particle of one party in its basis. Measuring in the basis rotated by an
+    /// angle is measuring in the standard basis between a rotation by minus the angle and back
+    fn measure_all(&mut self, bases: &[Basis], alice: bool) -> Vec<bool> {
+        let index = Self::index(alice);
+        self.particles
+            .iter_mut()
+            .zip(bases)
+            .map(|(particle, basis)| {
+                // ry(2 angle) rotates the real plane by the angle
+                let angle = basis.degrees().to_radians();
+                particle.apply(&rotation_y(-2.0 * angle), index);
+                let result = particle.measure(index);
+                particle.apply(&rotation_y(2.0 * angle), index);
+                result
+            })
+            .collect()
+    }
 
-        res
+    /// send bobs particles to him over `channel`. If eve taps the channel, alice has to have
+    /// measured already so that bobs particle is no longer entangled (the order of measurements on
+    /// different particles doesnt change the statistics). Returns which particles arrived at bob
+    fn transmit(&mut self, channel: &mut QuantumChannel) -> Vec<bool> {
+        self.particles
+            .iter_mut()
+            .map(|particle| channel.send_qubit(particle, Self::index(false)))
+            .collect()
     }
 }
 
+/// compare the keys of alice and bob against what eve can guess once the bases are public
+pub fn eavesdrop_report(alice: &Party, bob: &Party, eve: &dyn Eavesdropper) -> EavesdropReport {
+    let bases = bob.bases.as_ref().expect("no bases");
+    let positions: Vec<(usize, f64)> = bob
+        .key_indices
+        .iter()
+        .map(|i| (*i, bases[*i].degrees()))
+        .collect();
+    EavesdropReport::new(
+        alice.key.as_ref().expect("no key"),
+        bob.key.as_ref().expect("no key"),
+        &positions,
+        eve,
+    )
+}
+
 #[derive(Debug)]
 pub struct Party {
     name: String,
@@ -55,6 +86,8 @@ pub struct Party {
     measurements: Option<Vec<bool>>,
     key: Option<Vec<bool>>,
     non_agreemets: Option<Vec<bool>>,
+    detections: Option<Vec<bool>>,
+    key_indices: Vec<usize>,
 }
 
 impl Party {
@@ -65,6 +98,8 @@ impl Party {
             measurements: None,
             key: None,
             non_agreemets: None,
+            detections: None,
+            key_indices: vec![],
         };
         party.generate_random_bases_vec(no_particles);
         party
@@ -94,14 +129,22 @@ impl Party {
     /// Store measurements where bases didnt agree in non_agreements
     fn compare_basis(&mut self, other: Vec<Basis>) {
         let mut key: Vec<bool> = vec![];
+        let mut key_indices: Vec<usize> = vec![];
         let measurements = self.measurements.as_ref().expect("no measurements");
         let mut non_agreemets: Vec<bool> = vec![];
         let bases = self.bases.as_ref().expect("no bases");
 
         for (i, basis) in bases.iter().enumerate() {
+            // particles that never arrived at bob are dropped
+            if let Some(detections) = &self.detections {
+                if !detections[i] {
+                    continue;
+                }
+            }
             // if the bases agree, we store the measurement. This will be the key
             if basis == &other[i] {
                 key.push(measurements[i]);
+                key_indices.push(i);
             }
             // if the bases dont agree we store the measuremens.
             else {
@@ -109,6 +152,7 @@ impl Party {
             }
         }
         self.key = Some(key);
+        self.key_indices = key_indices;
         self.non_agreemets = Some(non_agreemets);
     }
 
@@ -129,6 +173,10 @@ impl Party {
         counter as f64 / other.len() as f64
     }
 
+    fn apply_detections(&mut self, detections: &[bool]) {
+        self.detections = Some(detections.to_vec());
+    }
+
     fn share_bases(&self) -> Vec<Basis> {
         self.bases.clone().expect("no basis found")
     }
@@ -156,24 +204,37 @@ impl Basis {
     }
 }
 
-fn generate_particle_stream(len: usize) -> Vec<EntangledParticleN<4>> {
-    let mut vec: Vec<EntangledParticleN<4>> = vec![];
-    for i in 0..len {
-        let prtcl = EntangledParticleN::new(SVector::<f64, 4>::new(
-            0.5f64.sqrt(),
-            0.0,
-            0.0,
-            0.5f64.sqrt(),
-        ));
-        vec.push(prtcl);
-    }
-    vec
-}
-
 // too make particle stream a struct
+#[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::module::eavesdropper::{InterceptResend, PhaseCovariantCloner};
+    use crate::module::entangled_particle_n::EntangledParticleN;
+    use crate::module::state_vector::StateVector;
+
+    fn run_with_eve<B: Tappable>(
+        eve: Box<dyn Eavesdropper>,
+        no_particles: usize,
+    ) -> EavesdropReport {
+        let mut alice = Party::new("alice", no_particles);
+        let mut bob = Party::new("bob", no_particles);
+        let mut prtcls = EntangledParticleStream::<B>::new(no_particles);
+
+        alice.record_measurement(&prtcls.measure_all(alice.bases.as_ref().unwrap(), true));
+
+        // eve gets hold of bobs particles before he measures
+        let mut channel = QuantumChannel::new("alice", "bob").with_tap(eve);
+        let detections = prtcls.transmit(&mut channel);
+        alice.apply_detections(&detections);
+        bob.apply_detections(&detections);
+
+        bob.record_measurement(&prtcls.measure_all(bob.bases.as_ref().unwrap(), false));
+
+        alice.compare_basis(bob.share_bases());
+        bob.compare_basis(alice.share_bases());
+        eavesdrop_report(&alice, &bob, channel.tap().unwrap())
+    }
 
     #[test]
     fn test_ekkert_no_eve() {
@@ -188,13 +249,11 @@ mod tests {
         // parties receive random basis at generations
         let mut alice = Party::new("alice", no_particles);
         let mut bob = Party::new("bob", no_particles);
-        let mut prtcls = EntangledParticleStream::new(no_particles);
+        let mut prtcls = EntangledParticleStream::<EntangledParticleN<4>>::new(no_particles);
 
-        // alice rotates her basis according to her random bases
-        prtcls.rotate_bases(alice.bases.as_ref().unwrap(), true);
-        alice.record_measurement(&prtcls.measure_all(true));
-        prtcls.rotate_bases(bob.bases.as_ref().unwrap(), false);
-        bob.record_measurement(&prtcls.measure_all(false));
+        // alice measures in her random bases
+        alice.record_measurement(&prtcls.measure_all(alice.bases.as_ref().unwrap(), true));
+        bob.record_measurement(&prtcls.measure_all(bob.bases.as_ref().unwrap(), false));
 
         alice.compare_basis(bob.share_bases());
         bob.compare_basis(alice.share_bases());
@@ -224,19 +283,16 @@ mod tests {
         let mut alice = Party::new("alice", no_particles);
         let mut bob = Party::new("bob", no_particles);
         let mut eve = Party::new("eve", no_particles);
-        let mut prtcls = EntangledParticleStream::new(no_particles);
+        let mut prtcls = EntangledParticleStream::<EntangledParticleN<4>>::new(no_particles);
 
-        // alice rotates her basis according to her random bases
-        prtcls.rotate_bases(alice.bases.as_ref().unwrap(), true);
-        alice.record_measurement(&prtcls.measure_all(true));
+        // alice measures in her random bases
+        alice.record_measurement(&prtcls.measure_all(alice.bases.as_ref().unwrap(), true));
 
         // eve measures
-        prtcls.rotate_bases(eve.bases.as_ref().unwrap(), false);
-        eve.record_measurement(&prtcls.measure_all(false));
+        eve.record_measurement(&prtcls.measure_all(eve.bases.as_ref().unwrap(), false));
 
         // bob measures
-        prtcls.rotate_bases(bob.bases.as_ref().unwrap(), false);
-        bob.record_measurement(&prtcls.measure_all(false));
+        bob.record_measurement(&prtcls.measure_all(bob.bases.as_ref().unwrap(), false));
 
         alice.compare_basis(bob.share_bases());
         bob.compare_basis(alice.share_bases());
@@ -251,4 +307,33 @@ mod tests {
             alice.key.unwrap().len()
         );
     }
+
+    #[test]
+    fn test_ekkert_eavesdroppers() {
+        let bases = vec![
+            Basis::Standard.degrees(),
+            Basis::Degree90.degrees(),
+            Basis::Degree45.degrees(),
+        ];
+
+        // eve measuring in one of the three bases: no errors if she picked the right one,
+        // 3/8 errors otherwise. In the wrong basis she still guesses right 3/4 of the time
+        let report = run_with_eve::<EntangledParticleN<4>>(
+            Box::new(InterceptResend::new(bases.clone())),
+            3000,
+        );
+        assert!((report.qber - 0.25).abs() < 0.06);
+        assert!((report.eve_information - 0.46).abs() < 0.1);
+
+        let report =
+            run_with_eve::<EntangledParticleN<4>>(Box::new(PhaseCovariantCloner::optimal()), 3000);
+        assert!((report.qber - 0.146).abs() < 0.05);
+        assert!((report.eve_agreement - 0.854).abs() < 0.06);
+
+        // the same on a state vector
+        let report = run_with_eve::<StateVector>(Box::new(InterceptResend::new(bases)), 3000);
+        assert!((report.qber - 0.25).abs() < 0.06);
+        let report = run_with_eve::<StateVector>(Box::new(PhaseCovariantCloner::optimal()), 3000);
+        assert!((report.qber - 0.146).abs() < 0.05);
+    }
 }
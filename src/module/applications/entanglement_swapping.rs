@@ -0,0 +1,266 @@
+// Entanglement swapping and the bookkeeping for noisy bell pairs
+//
+// Two bell pairs (0,1) and (2,3) share no entanglement between 0 and 3. A bell measurement on the
+// middle particles 1 and 2 teleports the state of 1 onto 3, so after bob's correction 0 and 3
+// are a bell pair although they never met.
+//
+// Noisy pairs are tracked as bell diagonal states: a pauli error on the second particle of |Φ+>
+// with some probability. Swapping, memory decoherence and purification only move these
+// probabilities around, so chains of many pairs can be simulated without a state vector.
+use crate::module::applications::quantum_teleportation::{bell_measurement, correct};
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+
+/// |Φ+>_(0,1) ⊗ |Φ+>_(2,3)
+pub fn two_bell_pairs<B: Backend>() -> B {
+    let mut state = B::allocate(4);
+    for first in [0, 2] {
+        state.apply_gate(SingleInputGate::Hadamard, first);
+        state.cnot(first, first + 1);
+    }
+    state
+}
+
+/// bell measurement on particles 1 and 2 and correction on 3, leaves 0 and 3 in |Φ+>.
+/// Returns the measurement that was sent to the owner of particle 3
+pub fn swap_entanglement<B: Backend>(state: &mut B) -> [bool; 2] {
+    let measurement = bell_measurement(state, 1);
+    correct(state, 3, measurement);
+    measurement
+}
+
+/// Bell diagonal state, the probabilities of |Φ+>, |Ψ+>, |Φ->, |Ψ->. These are |Φ+> with the
+/// error I, X, Z or Y on the second particle, so the index is (x bit) | (z bit) << 1
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BellDiagonal {
+    pub coefficients: [f64; 4],
+}
+
+impl BellDiagonal {
+    pub fn perfect() -> Self {
+        Self {
+            coefficients: [1.0, 0.0, 0.0, 0.0],
+        }
+    }
+
+    /// |Φ+> with probability `fidelity`, the other three bell states share the rest
+    pub fn werner(fidelity: f64) -> Self {
+        assert!((0.0..=1.0).contains(&fidelity));
+        let rest = (1.0 - fidelity) / 3.0;
+        Self {
+            coefficients: [fidelity, rest, rest, rest],
+        }
+    }
+
+    /// overlap with |Φ+>
+    pub fn fidelity(&self) -> f64 {
+        self.coefficients[0]
+    }
+
+    /// pair between the outer particles after a bell measurement on the inner ones.
+    /// The errors of both pairs end up on the new pair, pauli errors multiply like xor on the
+    /// (x, z) bits
+    pub fn swap(&self, other: &BellDiagonal) -> BellDiagonal {
+        let mut coefficients = [0.0; 4];
+        for (a, pa) in self.coefficients.iter().enumerate() {
+            for (b, pb) in other.coefficients.iter().enumerate() {
+                coefficients[a ^ b] += pa * pb;
+            }
+        }
+        BellDiagonal { coefficients }
+    }
+
+    /// one of the particles goes through a depolarizing channel
+    pub fn depolarize(&self, p: f64) -> BellDiagonal {
+        BellDiagonal {
+            coefficients: self.coefficients.map(|c| (1.0 - p) * c + p / 4.0),
+        }
+    }
+
+    /// both particles wait `time` in a memory that depolarizes with decay time `memory_time`
+    pub fn decohere(&self, time: f64, memory_time: f64) -> BellDiagonal {
+        let p = 1.0 - (-time / memory_time).exp();
+        self.depolarize(p).depolarize(p)
+    }
+
+    /// bilateral cnot from this (source) pair onto `target`, then both sides measure the target
+    /// pair in the standard basis. The source is kept if the results agree, this is the case if
+    /// both pairs have the same x error. The z error of the target is kicked back onto the source.
+    /// Returns the kept source pair and the probability to keep it
+    pub fn bilateral_cnot(&self, target: &BellDiagonal) -> (BellDiagonal, f64) {
+        let mut coefficients = [0.0; 4];
+        for (s, ps) in self.coefficients.iter().enumerate() {
+            for (t, pt) in target.coefficients.iter().enumerate() {
+                if s & 1 == t & 1 {
+                    coefficients[s ^ (t & 2)] += ps * pt;
+                }
+            }
+        }
+        let success: f64 = coefficients.iter().sum();
+        (
+            BellDiagonal {
+                coefficients: coefficients.map(|c| c / success),
+            },
+            success,
+        )
+    }
+
+    /// DEJMPS purification of two copies: the bilateral rotation swaps |Φ-> and |Ψ->, then a
+    /// bilateral cnot. Returns the purified pair and the probability of success
+    pub fn purify(&self, other: &BellDiagonal) -> (BellDiagonal, f64) {
+        let rotate = |state: &BellDiagonal| {
+            let [a, b, c, d] = state.coefficients;
+            BellDiagonal {
+                coefficients: [a, b, d, c],
+            }
+        };
+        let (purified, success) = rotate(self).bilateral_cnot(&rotate(other));
+        (rotate(&purified), success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::entangled_particle_n::EntangledParticleN;
+    use crate::module::gates::basic::TwoInputGate;
+    use crate::module::mps::Mps;
+    use crate::module::stabilizer::StabilizerState;
+    use crate::module::state_vector::StateVector;
+    use crate::module::utils::round_to_n_decimal_places;
+    use nalgebra::SVector;
+
+    fn pauli(error: usize) -> Option<SingleInputGate> {
+        match error {
+            1 => Some(SingleInputGate::X),
+            2 => Some(SingleInputGate::Z),
+            3 => Some(SingleInputGate::Y),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_swap_entanglement() {
+        for _i in 0..20 {
+            let mut prtcl: EntangledParticleN<16> = two_bell_pairs();
+            swap_entanglement(&mut prtcl);
+
+            // only |0xx0> and |1xx1> are left, with the same weight
+            let params = prtcl.get_params();
+            let zeros: f64 = (0..16).filter(|i| i & 0b1001 == 0).map(|i| params[i]).sum();
+            let ones: f64 = (0..16)
+                .filter(|i| i & 0b1001 == 0b1001)
+                .map(|i| params[i])
+                .sum();
+            assert_eq!(
+                round_to_n_decimal_places(zeros.abs(), 5),
+                round_to_n_decimal_places(0.5f64.sqrt(), 5)
+            );
+            assert_eq!(
+                round_to_n_decimal_places(zeros, 5),
+                round_to_n_decimal_places(ones, 5)
+            );
+            assert_eq!(prtcl.measure(0), prtcl.measure(3));
+        }
+    }
+
+    /// the outer particles agree in the standard basis and in the hadamard basis, as |Φ+> does
+    fn check_swapped_pair<B: Backend>() {
+        for hadamard_basis in [false, true] {
+            let mut state = two_bell_pairs::<B>();
+            swap_entanglement(&mut state);
+            if hadamard_basis {
+                state.apply_gate(SingleInputGate::Hadamard, 0);
+                state.apply_gate(SingleInputGate::Hadamard, 3);
+            }
+            assert_eq!(state.measure(0), state.measure(3));
+        }
+    }
+
+    #[test]
+    fn test_swap_entanglement_on_backends() {
+        for _i in 0..20 {
+            check_swapped_pair::<EntangledParticleN<16>>();
+            check_swapped_pair::<StateVector>();
+            check_swapped_pair::<StabilizerState>();
+            check_swapped_pair::<Mps>();
+        }
+    }
+
+    #[test]
+    fn test_swap_werner_pairs() {
+        // werner pairs swap like their visibility (4F - 1) / 3 multiplies
+        let swapped = BellDiagonal::werner(0.9).swap(&BellDiagonal::werner(0.8));
+        let visibility = (4.0 * 0.9 - 1.0) / 3.0 * (4.0 * 0.8 - 1.0) / 3.0;
+        assert!((swapped.fidelity() - (3.0 * visibility + 1.0) / 4.0).abs() < 1e-9);
+        assert!((swapped.coefficients.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_bilateral_cnot() {
+        // particles are (alice source, alice target, bob source, bob target), the errors sit on
+        // bob's particles
+        for source_error in 0..4 {
+            for target_error in 0..4 {
+                let mut state = SVector::<f64, 16>::zeros();
+                for a in 0..2 {
+                    for b in 0..2 {
+                        state[a * 8 + b * 4 + a * 2 + b] = 0.5;
+                    }
+                }
+                let mut prtcl = EntangledParticleN::new(state);
+                if let Some(gate) = pauli(source_error) {
+                    prtcl.change_state_by_matrix(gate.get_matrix::<16>(2));
+                }
+                if let Some(gate) = pauli(target_error) {
+                    prtcl.change_state_by_matrix(gate.get_matrix::<16>(3));
+                }
+
+                prtcl.change_state_by_matrix(TwoInputGate::CNot.get_matrix::<16>(0, 1));
+                prtcl.change_state_by_matrix(TwoInputGate::CNot.get_matrix::<16>(2, 3));
+                let alice = prtcl.measure(1);
+                let bob = prtcl.measure(3);
+
+                let mut source = BellDiagonal {
+                    coefficients: [0.0; 4],
+                };
+                source.coefficients[source_error] = 1.0;
+                let mut target = BellDiagonal {
+                    coefficients: [0.0; 4],
+                };
+                target.coefficients[target_error] = 1.0;
+                let (kept, success) = source.bilateral_cnot(&target);
+                assert_eq!(alice == bob, success == 1.0);
+                if alice != bob {
+                    continue;
+                }
+
+                // the source pair has to be |Φ+> with the predicted error
+                let error = kept.coefficients.iter().position(|c| *c == 1.0).unwrap();
+                let mut expected = SVector::<f64, 16>::zeros();
+                for a in 0..2 {
+                    expected[a * 8 + usize::from(!alice) * 4 + a * 2 + usize::from(!bob)] =
+                        0.5f64.sqrt();
+                }
+                let mut expected = EntangledParticleN::new(expected);
+                if let Some(gate) = pauli(error) {
+                    expected.change_state_by_matrix(gate.get_matrix::<16>(2));
+                }
+                let overlap = expected.get_params().dot(prtcl.get_params());
+                assert_eq!(round_to_n_decimal_places(overlap.abs(), 5), 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_purify() {
+        let (purified, success) = BellDiagonal::werner(0.8).purify(&BellDiagonal::werner(0.8));
+        assert_eq!(round_to_n_decimal_places(purified.fidelity(), 3), 0.838);
+        assert_eq!(round_to_n_decimal_places(success, 3), 0.769);
+
+        // decoherence pulls everything towards the completely mixed state
+        let old = BellDiagonal::perfect().decohere(1000.0, 1.0);
+        assert_eq!(round_to_n_decimal_places(old.fidelity(), 5), 0.25);
+    }
+}
@@ -0,0 +1,150 @@
+// GHZ and W states and the mermin inequality
+//
+// GHZ: (|00..0> + |11..1>) / sqrt(2), a hadamard and a ladder of cnots.
+// W:   (|10..0> + |01..0> + .. + |00..1>) / sqrt(n), a single excitation that is passed down the
+//      line, every qubit keeps its share 1 / sqrt(n) of it.
+//
+// Mermin: for n qubits each measured in the x or y basis, the real part of the product
+// (X1 + iY1)(X2 + iY2)..(Xn + iYn) is a sum of 2^(n-1) products with an even number of Ys.
+// Local hidden variables reach at most 2^(n/2) (rounded down), the GHZ state reaches 2^(n-1).
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::state_vector::{phase, rotation_y, StateVector};
+
+/// only cliffords are needed, so any backend works
+pub fn ghz_state<B: Backend>(no_qubits: usize) -> B {
+    assert!(no_qubits >= 2);
+    let mut state = B::allocate(no_qubits);
+    state.apply_gate(SingleInputGate::Hadamard, 0);
+    for i in 1..no_qubits {
+        state.cnot(i - 1, i);
+    }
+    state
+}
+
+pub fn w_state(no_qubits: usize) -> StateVector {
+    assert!(no_qubits >= 2);
+    let mut state = StateVector::new(no_qubits);
+    state.apply_gate(SingleInputGate::X, 0);
+    for i in 0..no_qubits - 1 {
+        // keep 1 / sqrt(n - i) of the excitation at i, move the rest on to i + 1
+        let angle = 2.0 * (1.0 / ((no_qubits - i) as f64).sqrt()).acos();
+        state.apply_controlled(&rotation_y(angle), &[i], i + 1);
+        state.cnot(i + 1, i);
+    }
+    state
+}
+
+/// measure qubit `index` in the x basis or the y basis, true for +1
+pub fn measure_x_or_y<B: Backend + ?Sized>(state: &mut B, index: usize, y_basis: bool) -> bool {
+    if y_basis {
+        // s^dagger maps the y eigenstates onto the x eigenstates
+        state.apply(&phase(-std::f64::consts::FRAC_PI_2), index);
+    }
+    state.apply_gate(SingleInputGate::Hadamard, index);
+    state.measure(index)
+}
+
+/// measure every qubit in the x basis (false) or y basis (true) and return the product of the
+/// results, +1 or -1
+pub fn measure_xy<B: Backend>(state: &mut B, y_basis: &[bool]) -> f64 {
+    assert_eq!(state.no_qubits(), y_basis.len());
+    let mut product = 1.0;
+    for (index, y) in y_basis.iter().enumerate() {
+        if !measure_x_or_y(state, index, *y) {
+            product = -product;
+        }
+    }
+    product
+}
+
+/// the terms of the mermin operator, sign and which qubits are measured in the y basis
+pub fn mermin_terms(no_qubits: usize) -> Vec<(f64, Vec<bool>)> {
+    (0..1usize << no_qubits)
+        .filter(|term| term.count_ones() % 2 == 0)
+        .map(|term| {
+            let sign = if term.count_ones() % 4 == 0 {
+                1.0
+            } else {
+                -1.0
+            };
+            let y_basis = (0..no_qubits)
+                .map(|i| (term >> (no_qubits - 1 - i)) & 1 == 1)
+                .collect();
+            (sign, y_basis)
+        })
+        .collect()
+}
+
+/// largest mermin value local hidden variables can reach
+pub fn mermin_classical_bound(no_qubits: usize) -> f64 {
+    2f64.powi((no_qubits / 2) as i32)
+}
+
+/// estimate the mermin value of `state` from `shots` measurements per term
+pub fn mermin_value<B: Backend + Clone>(state: &B, shots: usize) -> f64 {
+    mermin_terms(state.no_qubits())
+        .iter()
+        .map(|(sign, y_basis)| {
+            let sum: f64 = (0..shots)
+                .map(|_| measure_xy(&mut state.clone(), y_basis))
+                .sum();
+            sign * sum / shots as f64
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::stabilizer::StabilizerState;
+
+    #[test]
+    fn test_ghz_and_w_states() {
+        let ghz: StateVector = ghz_state(4);
+        let amplitudes = ghz.amplitudes();
+        assert!((amplitudes[0].re - 0.5f64.sqrt()).abs() < 1e-9);
+        assert!((amplitudes[15].re - 0.5f64.sqrt()).abs() < 1e-9);
+
+        let w = w_state(5);
+        for (index, amplitude) in w.amplitudes().iter().enumerate() {
+            let expected = if index.count_ones() == 1 {
+                0.2f64.sqrt()
+            } else {
+                0.0
+            };
+            assert!((amplitude.re - expected).abs() < 1e-9);
+            assert!(amplitude.im.abs() < 1e-9);
+        }
+
+        // losing one qubit of a GHZ state destroys the entanglement of the rest, for W the
+        // others stay entangled
+        let mut ghz: StateVector = ghz_state(3);
+        let mut w = w_state(3);
+        ghz.measure(0);
+        assert!(ghz.qubit_state(1).is_some());
+        if w.measure(0) {
+            assert!(w.qubit_state(1).is_none());
+        }
+    }
+
+    #[test]
+    fn test_mermin() {
+        assert_eq!(mermin_terms(3).len(), 4);
+
+        let ghz = mermin_value(&ghz_state::<StateVector>(3), 200);
+        assert!((ghz - 4.0).abs() < 1e-9);
+        assert!(ghz > mermin_classical_bound(3));
+
+        // every term is deterministic for GHZ, so few shots are enough
+        assert!((mermin_value(&ghz_state::<StateVector>(5), 5) - 16.0).abs() < 1e-9);
+
+        // the same on the stabilizer backend, which is not limited by the number of qubits
+        let ghz = mermin_value(&ghz_state::<StabilizerState>(9), 2);
+        assert!((ghz - 256.0).abs() < 1e-9);
+
+        // W states do not violate the inequality this way
+        assert!(mermin_value(&w_state(3), 500) < mermin_classical_bound(3) + 0.3);
+    }
+}
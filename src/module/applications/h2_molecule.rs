@@ -0,0 +1,89 @@
+// Ground state energy of the hydrogen molecule with VQE
+//
+// In the minimal sto-3g basis H2 has four spin orbitals. At the equilibrium distance of 0.735
+// angstrom the fermionic hamiltonian is mapped to qubits with the parity mapping and the two
+// qubits fixed by particle number and spin symmetry are removed, which leaves two qubits:
+//
+//   H = g0 + g1 Z0 - g1 Z1 + g2 Z0Z1 + g3 X0X1
+//
+// The ground state lies in the span of |01> and |10>, so a single parameter ansatz reaches it.
+// The energies are electronic, the repulsion of the nuclei is added for the total energy.
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::optimizers::Optimizer;
+use crate::module::pauli::{Pauli, PauliString, PauliSum};
+use crate::module::state_vector::rotation_y;
+use crate::module::vqe::{Ansatz, CustomAnsatz, Vqe};
+
+/// in hartree
+pub const NUCLEAR_REPULSION: f64 = 0.7199689944489797;
+
+/// exact electronic ground energy of the hamiltonian below
+pub const ELECTRONIC_GROUND_ENERGY: f64 = -1.857275030202378;
+
+pub fn h2_hamiltonian() -> PauliSum {
+    let z0z1 = PauliString::new(&[(0, Pauli::Z), (1, Pauli::Z)]);
+    let x0x1 = PauliString::new(&[(0, Pauli::X), (1, Pauli::X)]);
+    PauliSum::new()
+        .with_term(-1.052373245772859, PauliString::identity())
+        .with_term(0.39793742484318045, PauliString::z(0))
+        .with_term(-0.39793742484318045, PauliString::z(1))
+        .with_term(-0.01128010425623538, z0z1)
+        .with_term(0.18093119978423156, x0x1)
+}
+
+/// cos(theta / 2) |01> + sin(theta / 2) |10>, the two configurations with one electron pair
+pub fn h2_ansatz() -> impl Ansatz {
+    CustomAnsatz::new(2, 1, |state: &mut dyn Backend, parameters: &[f64]| {
+        state.apply(&rotation_y(parameters[0]), 0);
+        state.apply_gate(SingleInputGate::X, 1);
+        state.cnot(0, 1);
+    })
+}
+
+/// total ground energy of H2 found by VQE, electronic plus nuclear repulsion
+pub fn h2_ground_energy(optimizer: &dyn Optimizer, shots: Option<usize>) -> f64 {
+    let mut vqe = Vqe::new(h2_hamiltonian(), h2_ansatz());
+    if let Some(shots) = shots {
+        vqe = vqe.with_shots(shots);
+    }
+    vqe.run(optimizer, &[0.0]).value + NUCLEAR_REPULSION
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::optimizers::{GradientDescent, NelderMead, Spsa};
+    use crate::module::vqe::HardwareEfficient;
+
+    /// 1.6 mhartree, the accuracy needed for chemistry
+    const CHEMICAL_ACCURACY: f64 = 1.6e-3;
+
+    #[test]
+    fn test_hamiltonian() {
+        let exact = h2_hamiltonian().ground_energy(2);
+        assert!((exact - ELECTRONIC_GROUND_ENERGY).abs() < 1e-9);
+        // the known total energy of H2 at this distance
+        assert!((exact + NUCLEAR_REPULSION + 1.137306).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_h2_ground_energy() {
+        let total = ELECTRONIC_GROUND_ENERGY + NUCLEAR_REPULSION;
+        let nelder_mead = h2_ground_energy(&NelderMead::new(), None);
+        assert!((nelder_mead - total).abs() < 1e-8);
+        let gradient = h2_ground_energy(&GradientDescent::new(0.5, 300), None);
+        assert!((gradient - total).abs() < 1e-8);
+
+        // from samples the optimum is only found up to the shot noise
+        let spsa = h2_ground_energy(&Spsa::new(150).with_step(0.5), Some(2000));
+        assert!((spsa - total).abs() < 0.03);
+
+        // the general ansatz gets there as well
+        let vqe = Vqe::new(h2_hamiltonian(), HardwareEfficient::new(2, 1));
+        let initial = vec![0.2; vqe.ansatz().no_parameters()];
+        let result = vqe.run(&NelderMead::new().with_max_iterations(5000), &initial);
+        assert!((result.value - ELECTRONIC_GROUND_ENERGY).abs() < CHEMICAL_ACCURACY);
+    }
+}
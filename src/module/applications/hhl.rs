@@ -0,0 +1,241 @@
+// HHL algorithm for linear systems (Harrow, Hassidim, Lloyd)
+//
+// For a hermitian A = sum_j lambda_j |u_j><u_j| and |b> = sum_j beta_j |u_j> the solution of
+// A x = b is |x> ~ sum_j beta_j / lambda_j |u_j>. The register is an ancilla (qubit 0), a clock
+// of m qubits and the system qubits holding |b>:
+//
+// 1. phase estimation on U = e^(iAt) writes every eigenvalue into the clock,
+//    sum_j beta_j |lambda_j t M / 2 pi>|u_j>
+// 2. for every clock value a controlled y rotation turns the ancilla into
+//    sqrt(1 - C^2 / lambda^2) |0> + C / lambda |1>
+// 3. the inverse phase estimation returns the clock to |0..0>
+// 4. post-selecting the ancilla on |1> leaves sum_j beta_j C / lambda_j |u_j> on the system
+//
+// Clock values from M / 2 on stand for negative eigenvalues. The time t is chosen from the
+// largest eigenvalue so that the spectrum fits into the clock, the result is exact when all the
+// lambda_j t M / 2 pi are integers. Here |b> is written into the amplitudes directly and the
+// solution is read from them instead of being measured.
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::phase_estimation::{inverse_phase_estimation, phase_estimation};
+use crate::module::state_vector::{evolution_operator, rotation_y, StateVector, C64};
+use nalgebra::{DMatrix, DVector};
+use std::f64::consts::PI;
+
+#[derive(Clone, Debug)]
+pub struct HhlResult {
+    /// normalized solution read from the system qubits
+    pub solution: DVector<C64>,
+    /// normalized solution of nalgebra's lu decomposition
+    pub classical: DVector<C64>,
+    /// |<classical|solution>|^2
+    pub fidelity: f64,
+    /// probability to find the ancilla in |1>
+    pub success_probability: f64,
+}
+
+pub struct Hhl {
+    matrix: DMatrix<C64>,
+    b: DVector<C64>,
+    clock_qubits: usize,
+    time: Option<f64>,
+}
+
+impl Hhl {
+    pub fn new(matrix: DMatrix<C64>, b: DVector<C64>) -> Self {
+        assert!(matrix.is_square() && matrix.nrows().is_power_of_two());
+        assert!(
+            (&matrix - matrix.adjoint()).norm() < 1e-9,
+            "the matrix is not hermitian"
+        );
+        assert_eq!(b.len(), matrix.nrows());
+        Self {
+            matrix,
+            b,
+            clock_qubits: 4,
+            time: None,
+        }
+    }
+
+    pub fn with_clock_qubits(mut self, clock_qubits: usize) -> Self {
+        assert!(clock_qubits > 1);
+        self.clock_qubits = clock_qubits;
+        self
+    }
+
+    /// the evolution time t of e^(iAt)
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// by default the largest eigenvalue goes to the clock value M / 2 - 1
+    fn time(&self) -> f64 {
+        self.time.unwrap_or_else(|| {
+            let largest = self.matrix.clone().symmetric_eigenvalues().amax();
+            let m = (1 << self.clock_qubits) as f64;
+            2.0 * PI * (m / 2.0 - 1.0) / (m * largest)
+        })
+    }
+
+    /// the eigenvalue for a clock value
+    fn eigenvalue(&self, clock: usize, time: f64) -> f64 {
+        let m = 1usize << self.clock_qubits;
+        let signed = if clock < m / 2 {
+            clock as f64
+        } else {
+            clock as f64 - m as f64
+        };
+        2.0 * PI * signed / (time * m as f64)
+    }
+
+    pub fn run(&self) -> HhlResult {
+        let n = self.b.len().trailing_zeros() as usize;
+        let m = self.clock_qubits;
+        let total = 1 + m + n;
+        let clock: Vec<usize> = (1..=m).collect();
+        let system: Vec<usize> = (m + 1..total).collect();
+        let time = self.time();
+
+        // ancilla and clock in |0>, so |b> fills the first amplitudes
+        let b = self.b.normalize();
+        let mut amplitudes = vec![C64::new(0.0, 0.0); 1 << total];
+        amplitudes[..b.len()].copy_from_slice(b.as_slice());
+        let mut state = StateVector::from_amplitudes(amplitudes);
+
+        phase_estimation(&mut state, &clock, &mut |state, control, power| {
+            let u = evolution_operator(&self.matrix, -time * power as f64);
+            state.apply_controlled_unitary(&u, &[control], &system);
+        });
+
+        // C is the smallest eigenvalue the clock can hold
+        let c = self.eigenvalue(1, time);
+        for value in 1..1 << m {
+            let angle = 2.0 * (c / self.eigenvalue(value, time)).asin();
+            controlled_on_value(&mut state, &clock, value, |state| {
+                state.apply_controlled(&rotation_y(angle), &clock, 0)
+            });
+        }
+
+        inverse_phase_estimation(&mut state, &clock, &mut |state, control, power| {
+            let u = evolution_operator(&self.matrix, time * power as f64);
+            state.apply_controlled_unitary(&u, &[control], &system);
+        });
+
+        let success_probability = 1.0 - state.probability_zero(0);
+        state.collapse(0, false);
+        // ancilla |1> and clock |0..0>
+        let start = 1 << (total - 1);
+        let solution =
+            DVector::from_column_slice(&state.amplitudes().as_slice()[start..start + (1 << n)])
+                .normalize();
+
+        let classical = self
+            .matrix
+            .clone()
+            .lu()
+            .solve(&self.b)
+            .expect("the matrix is singular")
+            .normalize();
+        HhlResult {
+            fidelity: classical.dotc(&solution).norm_sqr(),
+            solution,
+            classical,
+            success_probability,
+        }
+    }
+}
+
+/// run `gate` with x gates around it so that it acts where the `qubits` hold `value`
+fn controlled_on_value(
+    state: &mut StateVector,
+    qubits: &[usize],
+    value: usize,
+    gate: impl FnOnce(&mut StateVector),
+) {
+    let zeros: Vec<usize> = (0..qubits.len())
+        .filter(|bit| (value >> (qubits.len() - 1 - bit)) & 1 == 0)
+        .map(|bit| qubits[bit])
+        .collect();
+    for qubit in &zeros {
+        state.apply_gate(SingleInputGate::X, *qubit);
+    }
+    gate(state);
+    for qubit in &zeros {
+        state.apply_gate(SingleInputGate::X, *qubit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn real(rows: usize, values: &[f64]) -> DMatrix<C64> {
+        DMatrix::from_row_slice(rows, rows, values).map(|value| C64::new(value, 0.0))
+    }
+
+    #[test]
+    fn test_exact_eigenvalues() {
+        // eigenvalues 1 and 2 land on the clock values 2 and 4 of 16 for t = pi / 4
+        let matrix = real(2, &[1.5, 0.5, 0.5, 1.5]);
+        let b = DVector::from_vec(vec![C64::new(1.0, 0.0), C64::new(0.0, 0.0)]);
+        let result = Hhl::new(matrix, b)
+            .with_clock_qubits(4)
+            .with_time(PI / 4.0)
+            .run();
+        assert!((result.fidelity - 1.0).abs() < 1e-9);
+        // C = 1 / 2, so the ancilla is |1> with probability (1/4 + 1/16) / 2
+        assert!((result.success_probability - 0.15625).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_four_by_four() {
+        let matrix = DMatrix::from_row_slice(
+            4,
+            4,
+            &[
+                C64::new(2.0, 0.0),
+                C64::new(0.5, 0.5),
+                C64::new(0.0, 0.0),
+                C64::new(0.25, 0.0),
+                C64::new(0.5, -0.5),
+                C64::new(3.0, 0.0),
+                C64::new(0.0, -0.3),
+                C64::new(0.0, 0.0),
+                C64::new(0.0, 0.0),
+                C64::new(0.0, 0.3),
+                C64::new(1.5, 0.0),
+                C64::new(0.2, 0.0),
+                C64::new(0.25, 0.0),
+                C64::new(0.0, 0.0),
+                C64::new(0.2, 0.0),
+                C64::new(2.5, 0.0),
+            ],
+        );
+        let b = DVector::from_vec(vec![
+            C64::new(1.0, 0.0),
+            C64::new(0.0, 1.0),
+            C64::new(-0.5, 0.0),
+            C64::new(0.3, 0.0),
+        ]);
+        // more clock qubits resolve the eigenvalues better
+        let coarse = Hhl::new(matrix.clone(), b.clone())
+            .with_clock_qubits(3)
+            .run();
+        let fine = Hhl::new(matrix, b).with_clock_qubits(7).run();
+        assert!(fine.fidelity > 0.99, "{}", fine.fidelity);
+        assert!(fine.fidelity > coarse.fidelity);
+    }
+
+    #[test]
+    fn test_negative_eigenvalue() {
+        // eigenvalues -1 and 2
+        let matrix = real(2, &[0.5, 1.5, 1.5, 0.5]);
+        let b = DVector::from_vec(vec![C64::new(0.6, 0.0), C64::new(0.8, 0.0)]);
+        let result = Hhl::new(matrix, b)
+            .with_clock_qubits(4)
+            .with_time(PI / 4.0)
+            .run();
+        assert!((result.fidelity - 1.0).abs() < 1e-9, "{}", result.fidelity);
+    }
+}
@@ -0,0 +1,167 @@
+// Swap test and hadamard test
+//
+// Both put an ancilla into |+>, let it control an operation on the registers and read the
+// interference back with a second hadamard, so P(ancilla = 0) = (1 + x) / 2 for the quantity x:
+//
+// swap test:     controlled swap of two registers, x = <SWAP>, which is |<psi|phi>|^2 for
+//                registers in the product state |psi>|phi>
+// hadamard test: controlled U on |psi>, x = Re <psi|U|psi>. An S^dagger on the ancilla before
+//                the controlled U gives Im <psi|U|psi> instead
+//
+// Every estimate comes from `shots` measurements of the ancilla, with a standard error of
+// 2 sqrt(p (1 - p) / shots).
+use crate::module::circuit::Circuit;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::state_vector::{complex_matrix, phase, StateVector, C64};
+use rand::prelude::*;
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_2;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Estimate {
+    pub value: f64,
+    pub standard_error: f64,
+    pub shots: usize,
+}
+
+impl Estimate {
+    /// estimate of x = 2 P(0) - 1 from `shots` measurements of the ancilla
+    fn from_ancilla(state: &StateVector, shots: usize) -> Self {
+        assert!(shots > 0);
+        let p = state.probability_zero(0).clamp(0.0, 1.0);
+        let mut rng = rand::rng();
+        let zeros = (0..shots).filter(|_| rng.random_bool(p)).count();
+        let frequency = zeros as f64 / shots as f64;
+        Estimate {
+            value: 2.0 * frequency - 1.0,
+            standard_error: 2.0 * (frequency * (1.0 - frequency) / shots as f64).sqrt(),
+            shots,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Part {
+    Real,
+    Imaginary,
+}
+
+/// |0> in front of the qubits of `state`
+fn with_ancilla(state: &StateVector) -> StateVector {
+    let mut amplitudes = vec![C64::new(0.0, 0.0); 2 * state.amplitudes().len()];
+    amplitudes[..state.amplitudes().len()].copy_from_slice(state.amplitudes().as_slice());
+    StateVector::from_amplitudes(amplitudes)
+}
+
+/// swap test of the registers `first` and `second` of `state`, the result estimates <SWAP>
+pub fn swap_test_registers(
+    state: &StateVector,
+    first: &[usize],
+    second: &[usize],
+    shots: usize,
+) -> Estimate {
+    assert_eq!(first.len(), second.len());
+    // qubit q of `state` is qubit q + 1 behind the ancilla
+    let mut state = with_ancilla(state);
+    let x = complex_matrix(SingleInputGate::X);
+    state.apply_gate(SingleInputGate::Hadamard, 0);
+    for (a, b) in first.iter().zip(second) {
+        let (a, b) = (a + 1, b + 1);
+        // fredkin as cnot, toffoli, cnot
+        state.cnot(b, a);
+        state.apply_controlled(&x, &[0, a], b);
+        state.cnot(b, a);
+    }
+    state.apply_gate(SingleInputGate::Hadamard, 0);
+    Estimate::from_ancilla(&state, shots)
+}
+
+/// |<psi|phi>|^2 from the swap test
+pub fn swap_test(psi: &StateVector, phi: &StateVector, shots: usize) -> Estimate {
+    let (n, m) = (psi.no_qubits(), phi.no_qubits());
+    assert_eq!(n, m);
+    let product = psi.amplitudes().kronecker(phi.amplitudes());
+    let first: Vec<usize> = (0..n).collect();
+    let second: Vec<usize> = (n..2 * n).collect();
+    swap_test_registers(
+        &StateVector::from_amplitudes(product.as_slice().to_vec()),
+        &first,
+        &second,
+        shots,
+    )
+}
+
+/// the real or imaginary part of <psi|U|psi> for the circuit U bound to `values`
+pub fn hadamard_test(
+    psi: &StateVector,
+    unitary: &Circuit,
+    values: &HashMap<String, f64>,
+    part: Part,
+    shots: usize,
+) -> Estimate {
+    assert_eq!(psi.no_qubits(), unitary.no_qubits());
+    let mut state = with_ancilla(psi);
+    state.apply_gate(SingleInputGate::Hadamard, 0);
+    if part == Part::Imaginary {
+        state.apply(&phase(-FRAC_PI_2), 0);
+    }
+    unitary.apply_controlled(&mut state, &[0], 1, values);
+    state.apply_gate(SingleInputGate::Hadamard, 0);
+    Estimate::from_ancilla(&state, shots)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::state_vector::random_qubit;
+
+    fn random_state() -> StateVector {
+        let mut state = StateVector::product(&[random_qubit(), random_qubit(), random_qubit()]);
+        state.cnot(0, 2);
+        state
+    }
+
+    /// within five standard errors of `exact`
+    fn close(estimate: Estimate, exact: f64) -> bool {
+        let p = (1.0 + exact) / 2.0;
+        let error = 2.0 * (p * (1.0 - p) / estimate.shots as f64).sqrt();
+        (estimate.value - exact).abs() <= 5.0 * error + 1e-12
+    }
+
+    #[test]
+    fn test_swap_test() {
+        let (psi, phi) = (random_state(), random_state());
+        let overlap = psi.fidelity(&phi);
+        assert!(close(swap_test(&psi, &phi, 20000), overlap));
+        assert_eq!(swap_test(&psi, &psi, 100).value, 1.0);
+
+        // the halves of a bell pair are symmetric under the swap
+        let mut bell = StateVector::new(2);
+        bell.apply_gate(SingleInputGate::Hadamard, 0);
+        bell.cnot(0, 1);
+        assert_eq!(swap_test_registers(&bell, &[0], &[1], 100).value, 1.0);
+    }
+
+    #[test]
+    fn test_hadamard_test() {
+        let psi = random_state();
+        let mut unitary = Circuit::new(3);
+        unitary
+            .ry("theta", 0)
+            .cnot(0, 1)
+            .phase(0.8, 1)
+            .rx(-0.4, 2)
+            .h(2);
+        let values = unitary.bind(&[1.3]);
+        let mut applied = psi.clone();
+        unitary.apply(&mut applied, &values);
+        let exact = psi.amplitudes().dotc(applied.amplitudes());
+
+        let real = hadamard_test(&psi, &unitary, &values, Part::Real, 20000);
+        let imaginary = hadamard_test(&psi, &unitary, &values, Part::Imaginary, 20000);
+        assert!(close(real, exact.re), "{real:?} {exact}");
+        assert!(close(imaginary, exact.im), "{imaginary:?} {exact}");
+        assert!(real.standard_error < 0.01);
+    }
+}
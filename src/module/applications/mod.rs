@@ -2,6 +2,14 @@ use crate::module::utils;
 
 pub mod deutsch_josza;
 pub mod ekkert_protocol;
+pub mod entanglement_swapping;
+pub mod ghz_w_states;
 pub mod grover;
+pub mod h2_molecule;
+pub mod hhl;
+pub mod interference_tests;
+pub mod quantum_repeater;
 pub mod quantum_teleportation;
+pub mod secret_sharing;
 pub mod superdense_coding;
+pub mod transverse_field_ising;
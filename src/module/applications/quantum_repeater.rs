@@ -0,0 +1,229 @@
+// Linear quantum repeater chain
+//
+// The end nodes are connected by `links` elementary links. In every time step each link that is
+// not up yet tries to make a bell pair with the nodes at its ends. Pairs wait in memories that
+// depolarize, optionally get purified with a second pair of the same link, and neighbouring pairs
+// are swapped as soon as both are there, until one pair connects the end nodes.
+use crate::module::applications::entanglement_swapping::BellDiagonal;
+use rand::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct RepeaterChain {
+    links: usize,
+    link_success: f64,
+    link_fidelity: f64,
+    memory_time: f64,
+    purification_rounds: usize,
+}
+
+/// a pair between the nodes `start` and `end`
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start: usize,
+    end: usize,
+    state: BellDiagonal,
+}
+
+/// what a run of the chain delivered
+#[derive(Clone, Debug)]
+pub struct RepeaterReport {
+    pub pairs: usize,
+    /// mean fidelity of the end to end pairs
+    pub fidelity: f64,
+    /// end to end pairs per time step
+    pub rate: f64,
+    /// mean number of time steps for one pair
+    pub mean_time: f64,
+}
+
+impl RepeaterChain {
+    /// perfect links that always succeed and memories that never decohere
+    pub fn new(links: usize) -> Self {
+        assert!(links > 0);
+        Self {
+            links,
+            link_success: 1.0,
+            link_fidelity: 1.0,
+            memory_time: f64::INFINITY,
+            purification_rounds: 0,
+        }
+    }
+
+    /// probability that an elementary link is made in one time step
+    pub fn with_link_success(mut self, p: f64) -> Self {
+        assert!(p > 0.0 && p <= 1.0);
+        self.link_success = p;
+        self
+    }
+
+    /// fidelity of the werner pairs an elementary link produces
+    pub fn with_link_fidelity(mut self, fidelity: f64) -> Self {
+        self.link_fidelity = fidelity;
+        self
+    }
+
+    /// decay time of the memories in time steps
+    pub fn with_memory_time(mut self, memory_time: f64) -> Self {
+        assert!(memory_time > 0.0);
+        self.memory_time = memory_time;
+        self
+    }
+
+    /// every elementary link is purified `rounds` times before it is swapped,
+    /// this needs 2^rounds raw pairs when nothing fails
+    pub fn with_purification(mut self, rounds: usize) -> Self {
+        self.purification_rounds = rounds;
+        self
+    }
+
+    /// purify a new pair of the link with the pairs that are already stored, returns the pair
+    /// once it has been through all rounds
+    fn store(
+        &self,
+        stored: &mut [Option<BellDiagonal>],
+        pair: BellDiagonal,
+    ) -> Option<BellDiagonal> {
+        let mut pair = pair;
+        for slot in stored.iter_mut() {
+            let Some(other) = slot.take() else {
+                *slot = Some(pair);
+                return None;
+            };
+            let (purified, success) = pair.purify(&other);
+            if !rand::rng().random_bool(success.min(1.0)) {
+                return None;
+            }
+            pair = purified;
+        }
+        Some(pair)
+    }
+
+    /// run until the end nodes share a pair, returns it and the number of time steps it took
+    pub fn distribute(&self) -> (BellDiagonal, usize) {
+        let mut rng = rand::rng();
+        let mut segments: Vec<Segment> = vec![];
+        let mut stored: Vec<Vec<Option<BellDiagonal>>> =
+            vec![vec![None; self.purification_rounds]; self.links];
+        let mut time = 0;
+
+        loop {
+            time += 1;
+
+            // everything that waited in memory during the last step decoheres
+            if self.memory_time.is_finite() {
+                for segment in segments.iter_mut() {
+                    segment.state = segment.state.decohere(1.0, self.memory_time);
+                }
+                for pair in stored.iter_mut().flatten().flatten() {
+                    *pair = pair.decohere(1.0, self.memory_time);
+                }
+            }
+
+            for (link, stored) in stored.iter_mut().enumerate() {
+                let up = segments
+                    .iter()
+                    .any(|segment| segment.start <= link && link < segment.end);
+                if up || !rng.random_bool(self.link_success) {
+                    continue;
+                }
+                let pair = BellDiagonal::werner(self.link_fidelity);
+                if let Some(state) = self.store(stored, pair) {
+                    segments.push(Segment {
+                        start: link,
+                        end: link + 1,
+                        state,
+                    });
+                }
+            }
+
+            // swap neighbouring pairs
+            segments.sort_by_key(|segment| segment.start);
+            let mut swapped: Vec<Segment> = vec![];
+            for segment in segments {
+                match swapped.last_mut() {
+                    Some(last) if last.end == segment.start => {
+                        last.end = segment.end;
+                        last.state = last.state.swap(&segment.state);
+                    }
+                    _ => swapped.push(segment),
+                }
+            }
+            segments = swapped;
+
+            if let [segment] = segments[..] {
+                if segment.start == 0 && segment.end == self.links {
+                    return (segment.state, time);
+                }
+            }
+        }
+    }
+
+    pub fn simulate(&self, pairs: usize) -> RepeaterReport {
+        assert!(pairs > 0);
+        let mut fidelity = 0.0;
+        let mut time = 0;
+        for _i in 0..pairs {
+            let (state, steps) = self.distribute();
+            fidelity += state.fidelity();
+            time += steps;
+        }
+        RepeaterReport {
+            pairs,
+            fidelity: fidelity / pairs as f64,
+            rate: pairs as f64 / time as f64,
+            mean_time: time as f64 / pairs as f64,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::utils::round_to_n_decimal_places;
+
+    #[test]
+    fn test_perfect_chain() {
+        let report = RepeaterChain::new(5).simulate(10);
+        assert_eq!(report.fidelity, 1.0);
+        assert_eq!(report.rate, 1.0);
+    }
+
+    #[test]
+    fn test_noisy_links() {
+        // without decoherence only the link fidelity matters, not when the links came up
+        let report = RepeaterChain::new(4)
+            .with_link_success(0.5)
+            .with_link_fidelity(0.95)
+            .simulate(200);
+        let visibility = ((4.0 * 0.95 - 1.0) / 3.0f64).powi(4);
+        assert_eq!(
+            round_to_n_decimal_places(report.fidelity, 5),
+            round_to_n_decimal_places((3.0 * visibility + 1.0) / 4.0, 5)
+        );
+        // waiting for the slowest of 4 links takes longer than for one
+        assert!(report.mean_time > 2.5);
+        assert!(report.rate < 0.4);
+    }
+
+    #[test]
+    fn test_memory_and_purification() {
+        let chain = RepeaterChain::new(3).with_link_success(0.2);
+        let perfect_memory = chain.clone().with_link_fidelity(0.9).simulate(300);
+        let bad_memory = chain
+            .clone()
+            .with_link_fidelity(0.9)
+            .with_memory_time(20.0)
+            .simulate(300);
+        assert!(bad_memory.fidelity < perfect_memory.fidelity - 0.05);
+
+        // purification trades rate for fidelity
+        let purified = chain
+            .clone()
+            .with_link_fidelity(0.9)
+            .with_purification(1)
+            .simulate(300);
+        assert!(purified.fidelity > perfect_memory.fidelity + 0.03);
+        assert!(purified.rate < perfect_memory.rate);
+    }
+}
@@ -1,17 +1,111 @@
-use crate::module::entangled_particle_n::EntangledParticleN;
+use crate::module::backend::Backend;
 use crate::module::gates::basic::*;
-use crate::module::{
-    gates::{
-        self,
-        basic::{self, SingleInputGate},
-    },
-    utils::round_to_n_decimal_places,
-};
-use nalgebra::SVector;
+use crate::module::state_vector::{StateVector, C64};
+use nalgebra::Vector2;
+
+/// alice's half of teleportation: reverse bell circuit on the adjacent particles `first` and
+/// `first + 1` and measure both, the result is what she sends to bob
+pub fn bell_measurement<B: Backend>(state: &mut B, first: usize) -> [bool; 2] {
+    state.cnot(first, first + 1);
+    state.apply_gate(SingleInputGate::Hadamard, first);
+    [state.measure(first), state.measure(first + 1)]
+}
+
+/// the pauli gate bob applies for alice's measurement. Measurements are true for |0>, so
+/// [true, true] is the 00 outcome: 01 needs an x, 10 a z and 11 both (the y gate)
+pub fn correction(measurement: [bool; 2]) -> Option<SingleInputGate> {
+    match measurement {
+        [true, true] => None,
+        [true, false] => Some(SingleInputGate::X),
+        [false, true] => Some(SingleInputGate::Z),
+        [false, false] => Some(SingleInputGate::Y),
+    }
+}
+
+/// bob's half of teleportation: the pauli correction for alice's measurement on `target`
+pub fn correct<B: Backend>(state: &mut B, target: usize, measurement: [bool; 2]) {
+    if let Some(gate) = correction(measurement) {
+        state.apply_gate(gate, target);
+    }
+}
+
+/// teleport the state of qubit `input` onto qubit `bob` on any backend, `alice` and `bob` start
+/// in |0> and become the shared bell pair. Returns alice's measurement
+pub fn teleport_qubit<B: Backend>(
+    state: &mut B,
+    input: usize,
+    alice: usize,
+    bob: usize,
+) -> [bool; 2] {
+    state.apply_gate(SingleInputGate::Hadamard, alice);
+    state.cnot(alice, bob);
+
+    state.cnot(input, alice);
+    state.apply_gate(SingleInputGate::Hadamard, input);
+    let measurement = [state.measure(input), state.measure(alice)];
+
+    if let Some(gate) = correction(measurement) {
+        state.apply_gate(gate, bob);
+    }
+    measurement
+}
+
+/// teleport any single qubit state from alice to bob. Returns alice's measurement (true for |0>,
+/// what she sends to bob) and the state bob ends up with after his correction
+pub fn teleport(input: Vector2<C64>) -> ([bool; 2], Vector2<C64>) {
+    let zero = Vector2::new(C64::new(1.0, 0.0), C64::new(0.0, 0.0));
+    // particle 0 is the input, 1 and 2 become the bell pair of alice and bob
+    let mut state = StateVector::product(&[input, zero, zero]);
+    let measurement = teleport_qubit(&mut state, 0, 1, 2);
+    let bob = state
+        .qubit_state(2)
+        .expect("bob's particle is not entangled after the measurement");
+    (measurement, bob)
+}
 
+#[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::module::entangled_particle_n::EntangledParticleN;
+    use crate::module::quantum_channel::QuantumChannel;
+    use crate::module::state_vector::{qubit_fidelity, random_qubit};
+    use crate::module::utils::round_to_n_decimal_places;
+    use nalgebra::SVector;
+
+    #[test]
+    fn test_teleport_random_states() {
+        let mut outcomes = [0; 4];
+        for _i in 0..500 {
+            let input = random_qubit();
+            let (measurement, bob) = teleport(input);
+            assert!((qubit_fidelity(&input, &bob) - 1.0).abs() < 1e-9);
+            outcomes[usize::from(measurement[0]) * 2 + usize::from(measurement[1])] += 1;
+        }
+        // all four corrections were needed
+        assert!(outcomes.iter().all(|count| *count > 50));
+    }
+
+    /// teleport |-> from the first to the last qubit, far apart
+    fn teleport_minus<B: Backend>(no_qubits: usize) {
+        let mut state = B::allocate(no_qubits);
+        state.apply_gate(SingleInputGate::X, 0);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        teleport_qubit(&mut state, 0, no_qubits / 2, no_qubits - 1);
+        state.apply_gate(SingleInputGate::Hadamard, no_qubits - 1);
+        assert!(!state.measure(no_qubits - 1));
+    }
+
+    #[test]
+    fn test_teleport_on_backends() {
+        use crate::module::mps::Mps;
+        use crate::module::stabilizer::StabilizerState;
+        for _i in 0..20 {
+            teleport_minus::<StateVector>(3);
+            teleport_minus::<StabilizerState>(500);
+            teleport_minus::<Mps>(40);
+        }
+    }
 
     #[test]
     fn test_teleport_pure_0() {
@@ -123,4 +217,39 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_teleport_over_channel() {
+        // the bell pair is made at alice, bob's half (particle 2) travels to him over the quantum
+        // channel and the measurement results follow over the classical channel
+        let mut channel = QuantumChannel::new("Alice", "Bob");
+        let initial_state: SVector<f64, 8> = SVector::from_vec(vec![
+            0.5f64.sqrt(),
+            0.0,
+            0.0,
+            0.5f64.sqrt(),
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+        ]);
+        let mut prtcl = EntangledParticleN::new(initial_state);
+        assert!(channel.send_qubit(&mut prtcl, 2));
+
+        prtcl.change_state_by_matrix(TwoInputGate::CNot.get_matrix::<8>(0, 1));
+        prtcl.change_state_by_matrix(SingleInputGate::Hadamard.get_matrix::<8>(0));
+        let alice_measurement = [prtcl.measure(0), prtcl.measure(1)];
+        channel.classical.send_bits("Alice", &alice_measurement);
+
+        let received = channel.classical.receive_bits("Bob").unwrap();
+        match received[..] {
+            [true, true] => {}
+            [true, false] => prtcl.change_state_by_matrix(SingleInputGate::X.get_matrix::<8>(2)),
+            [false, true] => prtcl.change_state_by_matrix(SingleInputGate::Z.get_matrix::<8>(2)),
+            _ => prtcl.change_state_by_matrix(SingleInputGate::Y.get_matrix::<8>(2)),
+        }
+
+        // bob ends up with |0>
+        assert!(prtcl.measure(2));
+    }
 }
@@ -0,0 +1,136 @@
+// Quantum secret sharing (Hillery, Bužek, Berthiaume)
+//
+// The dealer and n - 1 players share a GHZ state per round and everyone measures in the x or y
+// basis at random. After announcing the bases the rounds with an even number of y measurements
+// are kept: there the product of all results is fixed (minus for 2 mod 4 ys), so the players can
+// work out the dealer's bit, but only if all of them put their results together. Any smaller
+// group of players has no information about it.
+use crate::module::applications::ghz_w_states::{ghz_state, measure_x_or_y};
+use crate::module::state_vector::StateVector;
+use rand::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct Party {
+    pub name: String,
+    pub bases: Vec<bool>,
+    /// results of all rounds, true for +1
+    pub results: Vec<bool>,
+}
+
+impl Party {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            bases: vec![],
+            results: vec![],
+        }
+    }
+}
+
+pub struct SecretSharing {
+    /// the dealer is the first party, the players follow
+    pub parties: Vec<Party>,
+    /// rounds that were kept after the bases were announced
+    pub kept_rounds: Vec<usize>,
+}
+
+impl SecretSharing {
+    pub fn new(no_players: usize) -> Self {
+        assert!(
+            no_players >= 2,
+            "sharing with a single player is just key distribution"
+        );
+        let mut parties = vec![Party::new("Dealer")];
+        parties.extend((1..=no_players).map(|i| Party::new(&format!("Player {i}"))));
+        Self {
+            parties,
+            kept_rounds: vec![],
+        }
+    }
+
+    /// share `rounds` GHZ states, everyone measures in a random basis (true for y)
+    pub fn distribute(&mut self, rounds: usize) {
+        let mut rng = rand::rng();
+        for _round in 0..rounds {
+            let mut state: StateVector = ghz_state(self.parties.len());
+            for (index, party) in self.parties.iter_mut().enumerate() {
+                let y_basis = rng.random_bool(0.5);
+                party.bases.push(y_basis);
+                party
+                    .results
+                    .push(measure_x_or_y(&mut state, index, y_basis));
+            }
+        }
+    }
+
+    /// everyone announces their bases, keep the rounds with an even number of y measurements
+    pub fn sift(&mut self) {
+        let rounds = self.parties[0].bases.len();
+        self.kept_rounds = (0..rounds)
+            .filter(|round| self.no_y(*round).is_multiple_of(2))
+            .collect();
+    }
+
+    fn no_y(&self, round: usize) -> usize {
+        self.parties
+            .iter()
+            .filter(|party| party.bases[round])
+            .count()
+    }
+
+    pub fn dealer_key(&self) -> Vec<bool> {
+        self.kept_rounds
+            .iter()
+            .map(|round| self.parties[0].results[*round])
+            .collect()
+    }
+
+    /// the key the players `players` (indices into `parties`) guess for the dealer by combining
+    /// their results. Only with all players this is the dealer's key
+    pub fn combined_key(&self, players: &[usize]) -> Vec<bool> {
+        self.kept_rounds
+            .iter()
+            .map(|round| {
+                // product of all results is +1 for 0 mod 4 ys, written as xor of the -1 results
+                let mut bit = self.no_y(*round).is_multiple_of(4);
+                for player in players {
+                    assert_ne!(*player, 0, "the dealer is not a player");
+                    bit ^= !self.parties[*player].results[*round];
+                }
+                bit
+            })
+            .collect()
+    }
+}
+
+/// fraction of equal bits
+pub fn agreement(a: &[bool], b: &[bool]) -> f64 {
+    assert_eq!(a.len(), b.len());
+    a.iter().zip(b).filter(|(x, y)| x == y).count() as f64 / a.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_secret_sharing() {
+        for no_players in [2, 3] {
+            let mut sharing = SecretSharing::new(no_players);
+            sharing.distribute(800);
+            sharing.sift();
+
+            // half of the rounds are kept
+            let kept = sharing.kept_rounds.len() as f64 / 800.0;
+            assert!((kept - 0.5).abs() < 0.08);
+
+            let players: Vec<usize> = (1..=no_players).collect();
+            assert_eq!(sharing.combined_key(&players), sharing.dealer_key());
+
+            // without the last player the others only guess
+            let guess = sharing.combined_key(&players[..no_players - 1]);
+            assert!((agreement(&guess, &sharing.dealer_key()) - 0.5).abs() < 0.1);
+        }
+    }
+}
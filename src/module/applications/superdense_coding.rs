@@ -1,6 +1,84 @@
-use crate::module::entangled_particle_n::EntangledParticleN;
+use crate::module::backend::Backend;
 use crate::module::gates::basic::*;
-use nalgebra::{ArrayStorage, Const, Matrix, Matrix2, Matrix4, SVector, Vector2, Vector4};
+use crate::module::quantum_channel::{QuantumChannel, Tappable};
+
+// Alice holds particle 0 of the bell pair, bob particle 1. The bits are message bits (true is a
+// 1), not measurement results
+
+/// (|00> + |11>) / sqrt(2)
+pub fn bell_pair<B: Backend>() -> B {
+    let mut pair = B::allocate(2);
+    pair.apply_gate(SingleInputGate::Hadamard, 0);
+    pair.cnot(0, 1);
+    pair
+}
+
+/// alice writes two bits into her half of the pair: 00 nothing, 01 x, 10 z, 11 y
+pub fn encode<B: Backend>(bits: [bool; 2], pair: &mut B) {
+    let gate = match bits {
+        [false, false] => return,
+        [false, true] => SingleInputGate::X,
+        [true, false] => SingleInputGate::Z,
+        [true, true] => SingleInputGate::Y,
+    };
+    pair.apply_gate(gate, 0);
+}
+
+/// bob applies the reverse bell circuit to both halves and measures
+pub fn decode<B: Backend>(pair: &mut B) -> [bool; 2] {
+    pair.cnot(0, 1);
+    pair.apply_gate(SingleInputGate::Hadamard, 0);
+    // measure is true for |0>
+    [!pair.measure(0), !pair.measure(1)]
+}
+
+/// send a message two bits per bell pair, most significant bits first. Bob already holds his
+/// halves, alice's halves go over the channel. Bits of lost particles are read as 0
+pub fn send_message<B: Tappable>(message: &[u8], channel: &mut QuantumChannel) -> Vec<u8> {
+    message
+        .iter()
+        .map(|byte| {
+            (0..4).rev().fold(0, |received, pair_index| {
+                let bits = [
+                    (byte >> (2 * pair_index + 1)) & 1 == 1,
+                    (byte >> (2 * pair_index)) & 1 == 1,
+                ];
+                let mut pair: B = bell_pair();
+                encode(bits, &mut pair);
+                let decoded = if channel.send_qubit(&mut pair, 0) {
+                    decode(&mut pair)
+                } else {
+                    [false, false]
+                };
+                (received << 2) | (u8::from(decoded[0]) << 1) | u8::from(decoded[1])
+            })
+        })
+        .collect()
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitErrorStats {
+    pub bits: usize,
+    pub errors: usize,
+}
+
+impl BitErrorStats {
+    pub fn new(sent: &[u8], received: &[u8]) -> Self {
+        assert_eq!(sent.len(), received.len());
+        Self {
+            bits: sent.len() * 8,
+            errors: sent
+                .iter()
+                .zip(received)
+                .map(|(a, b)| (a ^ b).count_ones() as usize)
+                .sum(),
+        }
+    }
+
+    pub fn bit_error_rate(&self) -> f64 {
+        self.errors as f64 / self.bits as f64
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -14,6 +92,45 @@ mod tests {
     };
 
     use super::*;
+    use crate::module::entangled_particle_n::EntangledParticleN;
+    use crate::module::stabilizer::StabilizerState;
+    use crate::module::state_vector::StateVector;
+    use nalgebra::SVector;
+
+    fn check_encode_decode<B: Backend>() {
+        for bits in [[false, false], [false, true], [true, false], [true, true]] {
+            let mut pair: B = bell_pair();
+            encode(bits, &mut pair);
+            assert_eq!(decode(&mut pair), bits);
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        check_encode_decode::<EntangledParticleN<4>>();
+        check_encode_decode::<StateVector>();
+        check_encode_decode::<StabilizerState>();
+    }
+
+    fn check_send_message<B: Tappable>() {
+        let message = "hello bob".as_bytes();
+        let mut channel = QuantumChannel::new("Alice", "Bob");
+        assert_eq!(send_message::<B>(message, &mut channel), message);
+
+        // a depolarizing channel flips each bit with probability p / 2
+        let message: Vec<u8> = (0..=255).collect();
+        let mut channel = QuantumChannel::new("Alice", "Bob").with_depolarizing(0.2);
+        let received = send_message::<B>(&message, &mut channel);
+        let stats = BitErrorStats::new(&message, &received);
+        assert_eq!(stats.bits, 2048);
+        assert!((stats.bit_error_rate() - 0.1).abs() < 0.03);
+    }
+
+    #[test]
+    fn test_send_message() {
+        check_send_message::<EntangledParticleN<4>>();
+        check_send_message::<StateVector>();
+    }
 
     #[test]
     fn test_superdense_coding() {
@@ -106,4 +223,55 @@ mod tests {
         assert_eq!([res_0, res_1], [false, false]);
         dbg!("worked");
     }
+
+    #[test]
+    fn test_superdense_coding_over_channel() {
+        // alice keeps her half of the bell pair until she knows the message, then sends it to bob
+        let encodings = [
+            (None, [true, true]),
+            (Some(SingleInputGate::X), [true, false]),
+            (Some(SingleInputGate::Z), [false, true]),
+            (Some(SingleInputGate::Y), [false, false]),
+        ];
+        let hadamard = SingleInputGate::Hadamard.get_matrix::<4>(0);
+        let cnot = TwoInputGate::CNot.get_matrix::<4>(0, 1);
+
+        let mut channel = QuantumChannel::new("Alice", "Bob");
+        for (gate, expected) in encodings {
+            let mut prtcl = EntangledParticleN::new(SVector::<f64, 4>::new(
+                0.5f64.sqrt(),
+                0.0,
+                0.0,
+                0.5f64.sqrt(),
+            ));
+            if let Some(gate) = gate {
+                prtcl.change_state_by_matrix(gate.get_matrix::<4>(0));
+            }
+            assert!(channel.send_qubit(&mut prtcl, 0));
+
+            prtcl.change_state_by_matrix(cnot);
+            prtcl.change_state_by_matrix(hadamard);
+            assert_eq!([prtcl.measure(0), prtcl.measure(1)], expected);
+        }
+
+        // with a completely depolarizing channel bob only gets the right pair a quarter of the
+        // time
+        let mut channel = QuantumChannel::new("Alice", "Bob").with_depolarizing(1.0);
+        let mut right = 0;
+        for _i in 0..400 {
+            let mut prtcl = EntangledParticleN::new(SVector::<f64, 4>::new(
+                0.5f64.sqrt(),
+                0.0,
+                0.0,
+                0.5f64.sqrt(),
+            ));
+            channel.send_qubit(&mut prtcl, 0);
+            prtcl.change_state_by_matrix(cnot);
+            prtcl.change_state_by_matrix(hadamard);
+            if [prtcl.measure(0), prtcl.measure(1)] == [true, true] {
+                right += 1;
+            }
+        }
+        assert!((right as f64 / 400.0 - 0.25).abs() < 0.08);
+    }
 }
@@ -0,0 +1,100 @@
+// Magnetization of the transverse field Ising chain after a quench
+//
+//   H = -J sum_i Z_i Z_(i+1) - h sum_i X_i
+//
+// on an open chain of n spins. All spins start up, |00..0>, the ground state for h = 0. Switching
+// on the field h makes the magnetization m = sum_i <Z_i> / n oscillate and decay. The state is
+// evolved with a Trotter circuit of one step per time point and with the exact matrix
+// exponential, so the two magnetizations can be compared over time.
+use crate::module::hamiltonian_simulation::{exact_evolution, trotter_circuit, TrotterOrder};
+use crate::module::pauli::{Pauli, PauliString, PauliSum};
+use crate::module::state_vector::StateVector;
+use std::collections::HashMap;
+
+pub fn ising_hamiltonian(no_spins: usize, coupling: f64, field: f64) -> PauliSum {
+    let mut hamiltonian = PauliSum::new();
+    for i in 1..no_spins {
+        hamiltonian.add_term(
+            -coupling,
+            PauliString::new(&[(i - 1, Pauli::Z), (i, Pauli::Z)]),
+        );
+    }
+    for i in 0..no_spins {
+        hamiltonian.add_term(-field, PauliString::x(i));
+    }
+    hamiltonian
+}
+
+/// sum_i <Z_i> / n
+pub fn magnetization(state: &StateVector) -> f64 {
+    let n = state.no_qubits();
+    (0..n)
+        .map(|qubit| 2.0 * state.probability_zero(qubit) - 1.0)
+        .sum::<f64>()
+        / n as f64
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct MagnetizationPoint {
+    pub time: f64,
+    pub trotter: f64,
+    pub exact: f64,
+}
+
+/// the magnetization at the times dt, 2 dt, .. up to `steps` dt after the quench
+pub fn magnetization_over_time(
+    no_spins: usize,
+    coupling: f64,
+    field: f64,
+    dt: f64,
+    steps: usize,
+    order: TrotterOrder,
+) -> Vec<MagnetizationPoint> {
+    let hamiltonian = ising_hamiltonian(no_spins, coupling, field);
+    let step = trotter_circuit(&hamiltonian, no_spins, dt, 1, order);
+    let mut trotter = StateVector::new(no_spins);
+    let start = StateVector::new(no_spins);
+    (1..=steps)
+        .map(|k| {
+            step.apply(&mut trotter, &HashMap::new());
+            let time = k as f64 * dt;
+            MagnetizationPoint {
+                time,
+                trotter: magnetization(&trotter),
+                exact: magnetization(&exact_evolution(&hamiltonian, &start, time)),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_single_spin() {
+        // without coupling every spin precesses on its own, m = cos(2 h t)
+        let points = magnetization_over_time(3, 0.0, 0.8, 0.1, 20, TrotterOrder::First);
+        for point in points {
+            assert!((point.exact - (1.6 * point.time).cos()).abs() < 1e-9);
+            assert!((point.trotter - point.exact).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_quench() {
+        let first = magnetization_over_time(5, 1.0, 0.7, 0.05, 60, TrotterOrder::First);
+        let second = magnetization_over_time(5, 1.0, 0.7, 0.05, 60, TrotterOrder::Second);
+        let max_error = |points: &[MagnetizationPoint]| {
+            points
+                .iter()
+                .map(|point| (point.trotter - point.exact).abs())
+                .fold(0.0, f64::max)
+        };
+        assert!(max_error(&first) < 2e-2, "{}", max_error(&first));
+        assert!(max_error(&second) < 1e-3, "{}", max_error(&second));
+        // the field tips the spins over, m drops below 1
+        assert!(second.iter().any(|point| point.exact < 0.5));
+    }
+}
@@ -0,0 +1,489 @@
+// Reversible arithmetic circuits
+//
+// Numbers live in named registers of qubits, the i-th qubit of a register is bit i of its
+// number, so the least significant bit comes first. Every circuit is reversible and returns its
+// ancillas to |0>, so it also acts on superpositions of numbers, as Shor's algorithm and grover
+// oracles need.
+//
+// Ripple-carry adder (Cuccaro, Draper, Kutin, Moulton): MAJ gates carry the sum bit by bit up the
+// a register with toffolis, the carry out is copied and UMA gates walk back down, writing a + b
+// into b and restoring a and the single carry ancilla. The same MAJ chain on the complement of
+// a computes a < b.
+//
+// QFT adder (Draper): after a fourier transform without swaps qubit b_j holds the phase
+// 2 pi b / 2^(j + 1), so adding a is a controlled phase 2 pi 2^k / 2^(j + 1) from every a_k.
+// A classical constant is added with plain phases, no ancillas at all.
+//
+// Modular arithmetic (Beauregard) works on an n + 1 qubit register b < N in fourier space: add
+// c, subtract N, and if that went negative, visible in the top bit, add N back. An ancilla
+// remembers the top bit and is cleaned up by comparing again after subtracting c. Controlled
+// modular additions of c 2^i mod N for every bit x_i multiply, and multiplying by c^(2^j) for
+// every bit of an exponent is modular exponentiation, the heart of Shor's algorithm.
+use crate::module::circuit::{Circuit, Gate};
+use crate::module::state_vector::phase;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+
+/// named registers on consecutive qubits
+#[derive(Clone, Debug, Default)]
+pub struct Registers {
+    registers: Vec<(String, Vec<usize>)>,
+    no_qubits: usize,
+}
+
+impl Registers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the next `size` qubits, least significant bit first
+    pub fn add(&mut self, name: &str, size: usize) -> Vec<usize> {
+        assert!(
+            self.registers.iter().all(|(known, _)| known != name),
+            "register {name} exists"
+        );
+        let qubits: Vec<usize> = (self.no_qubits..self.no_qubits + size).collect();
+        self.no_qubits += size;
+        self.registers.push((name.to_string(), qubits.clone()));
+        qubits
+    }
+
+    pub fn get(&self, name: &str) -> &[usize] {
+        self.registers
+            .iter()
+            .find(|(known, _)| known == name)
+            .map(|(_, qubits)| qubits.as_slice())
+            .unwrap_or_else(|| panic!("no register {name}"))
+    }
+
+    pub fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    /// the basis state with the registers holding `values` and everything else 0
+    pub fn basis_index(&self, values: &[(&str, usize)]) -> usize {
+        let mut index = 0;
+        for (name, value) in values {
+            let qubits = self.get(name);
+            assert!(
+                *value < 1 << qubits.len(),
+                "{value} does not fit into {name}"
+            );
+            for (bit, qubit) in qubits.iter().enumerate() {
+                if (value >> bit) & 1 == 1 {
+                    index |= 1 << (self.no_qubits - 1 - qubit);
+                }
+            }
+        }
+        index
+    }
+
+    /// the number in register `name` of basis state `index`
+    pub fn value(&self, name: &str, index: usize) -> usize {
+        self.get(name)
+            .iter()
+            .enumerate()
+            .map(|(bit, qubit)| ((index >> (self.no_qubits - 1 - qubit)) & 1) << bit)
+            .sum()
+    }
+}
+
+fn majority(circuit: &mut Circuit, carry: usize, b: usize, a: usize) {
+    circuit.cnot(a, b).cnot(a, carry).toffoli(carry, b, a);
+}
+
+fn unmajority(circuit: &mut Circuit, carry: usize, b: usize, a: usize) {
+    circuit.toffoli(carry, b, a).cnot(a, carry).cnot(carry, b);
+}
+
+/// b <- a + b mod 2^n with the `carry` ancilla in |0>, `overflow` is flipped by the carry out
+pub fn cuccaro_add(
+    circuit: &mut Circuit,
+    a: &[usize],
+    b: &[usize],
+    carry: usize,
+    overflow: Option<usize>,
+) {
+    let n = a.len();
+    assert!(n > 0 && b.len() == n);
+    // the carry into bit i sits on a_(i - 1), the first one on the ancilla
+    let carries: Vec<usize> = std::iter::once(carry)
+        .chain(a[..n - 1].iter().copied())
+        .collect();
+    for i in 0..n {
+        majority(circuit, carries[i], b[i], a[i]);
+    }
+    if let Some(overflow) = overflow {
+        circuit.cnot(a[n - 1], overflow);
+    }
+    for i in (0..n).rev() {
+        unmajority(circuit, carries[i], b[i], a[i]);
+    }
+}
+
+/// b <- b - a mod 2^n, `borrow` is flipped if a > b
+pub fn cuccaro_subtract(
+    circuit: &mut Circuit,
+    a: &[usize],
+    b: &[usize],
+    carry: usize,
+    borrow: Option<usize>,
+) {
+    let mut add = Circuit::new(circuit.no_qubits());
+    cuccaro_add(&mut add, a, b, carry, borrow);
+    circuit.append(&add.inverse(&HashMap::new()));
+}
+
+/// `result` is flipped if a < b, a and b are unchanged
+pub fn less_than(circuit: &mut Circuit, a: &[usize], b: &[usize], carry: usize, result: usize) {
+    let n = a.len();
+    assert!(n > 0 && b.len() == n);
+    // the carry out of (2^n - 1 - a) + b is set exactly for b > a
+    let mut chain = Circuit::new(circuit.no_qubits());
+    for qubit in a {
+        chain.x(*qubit);
+    }
+    let carries: Vec<usize> = std::iter::once(carry)
+        .chain(a[..n - 1].iter().copied())
+        .collect();
+    for i in 0..n {
+        majority(&mut chain, carries[i], b[i], a[i]);
+    }
+    circuit.append(&chain);
+    circuit.cnot(a[n - 1], result);
+    circuit.append(&chain.inverse(&HashMap::new()));
+}
+
+/// fourier transform without the final swaps, b_j ends up with the phase 2 pi b / 2^(j + 1)
+fn phase_qft(no_qubits: usize, b: &[usize]) -> Circuit {
+    let mut circuit = Circuit::new(no_qubits);
+    for j in (0..b.len()).rev() {
+        circuit.h(b[j]);
+        for k in (0..j).rev() {
+            circuit.controlled_phase(2.0 * PI / (1u64 << (j - k + 1)) as f64, b[k], b[j]);
+        }
+    }
+    circuit
+}
+
+fn inverse_phase_qft(no_qubits: usize, b: &[usize]) -> Circuit {
+    phase_qft(no_qubits, b).inverse(&HashMap::new())
+}
+
+/// b <- a + b mod 2^n with a fourier transform of b and controlled phases
+pub fn draper_add(circuit: &mut Circuit, a: &[usize], b: &[usize]) {
+    assert!(a.len() <= b.len());
+    circuit.append(&phase_qft(circuit.no_qubits(), b));
+    for (j, target) in b.iter().enumerate() {
+        for (k, qubit) in a.iter().enumerate().take(j + 1) {
+            let angle = 2.0 * PI * (1u64 << k) as f64 / (1u64 << (j + 1)) as f64;
+            circuit.controlled_phase(angle, *qubit, *target);
+        }
+    }
+    circuit.append(&inverse_phase_qft(circuit.no_qubits(), b));
+}
+
+/// add `constant` to b in fourier space where all `controls` are |1>
+fn phase_add_constant(circuit: &mut Circuit, constant: i64, b: &[usize], controls: &[usize]) {
+    for (j, qubit) in b.iter().enumerate() {
+        let angle = 2.0 * PI * constant as f64 / (1u64 << (j + 1)) as f64;
+        circuit.push(Gate::Controlled(controls.to_vec(), phase(angle), *qubit));
+    }
+}
+
+/// b <- b + constant mod 2^n
+pub fn add_constant(circuit: &mut Circuit, constant: u64, b: &[usize]) {
+    circuit.append(&phase_qft(circuit.no_qubits(), b));
+    phase_add_constant(circuit, constant as i64, b, &[]);
+    circuit.append(&inverse_phase_qft(circuit.no_qubits(), b));
+}
+
+/// b <- b + constant mod `modulus` in fourier space where all `controls` are |1>. b has one
+/// qubit more than the modulus needs, which is |0> like the ancilla before and after
+fn phase_modular_add(
+    circuit: &mut Circuit,
+    constant: u64,
+    modulus: u64,
+    b: &[usize],
+    ancilla: usize,
+    controls: &[usize],
+) {
+    let top = b[b.len() - 1];
+    let (constant, modulus) = (constant as i64, modulus as i64);
+    let no_qubits = circuit.no_qubits();
+    phase_add_constant(circuit, constant, b, controls);
+    phase_add_constant(circuit, -modulus, b, &[]);
+    // negative, so add the modulus back
+    circuit.append(&inverse_phase_qft(no_qubits, b));
+    circuit.cnot(top, ancilla);
+    circuit.append(&phase_qft(no_qubits, b));
+    phase_add_constant(circuit, modulus, b, &[ancilla]);
+    // b + constant mod N - constant is negative exactly if the modulus was not added
+    phase_add_constant(circuit, -constant, b, controls);
+    circuit.append(&inverse_phase_qft(no_qubits, b));
+    circuit.x(top).cnot(top, ancilla).x(top);
+    circuit.append(&phase_qft(no_qubits, b));
+    phase_add_constant(circuit, constant, b, controls);
+}
+
+/// b <- b + constant mod `modulus` where all `controls` are |1>, for b and constant below the
+/// modulus. b needs a spare top qubit and the ancilla starts in |0>, both end in |0>
+pub fn modular_add_constant(
+    circuit: &mut Circuit,
+    constant: u64,
+    modulus: u64,
+    b: &[usize],
+    ancilla: usize,
+    controls: &[usize],
+) {
+    assert!(constant < modulus && modulus < 1 << (b.len() - 1));
+    circuit.append(&phase_qft(circuit.no_qubits(), b));
+    phase_modular_add(circuit, constant, modulus, b, ancilla, controls);
+    circuit.append(&inverse_phase_qft(circuit.no_qubits(), b));
+}
+
+/// b <- b + constant x mod `modulus` if `control` is |1>, b has one qubit more than x
+pub fn modular_multiply_add(
+    circuit: &mut Circuit,
+    constant: u64,
+    modulus: u64,
+    control: usize,
+    x: &[usize],
+    b: &[usize],
+    ancilla: usize,
+) {
+    assert_eq!(b.len(), x.len() + 1);
+    assert!(modulus < 1 << x.len());
+    circuit.append(&phase_qft(circuit.no_qubits(), b));
+    let mut addend = constant % modulus;
+    for qubit in x {
+        phase_modular_add(circuit, addend, modulus, b, ancilla, &[control, *qubit]);
+        addend = 2 * addend % modulus;
+    }
+    circuit.append(&inverse_phase_qft(circuit.no_qubits(), b));
+}
+
+/// the inverse of `value` mod `modulus`
+fn modular_inverse(value: u64, modulus: u64) -> u64 {
+    let (mut r, mut new_r) = (modulus as i64, (value % modulus) as i64);
+    let (mut t, mut new_t) = (0i64, 1i64);
+    while new_r != 0 {
+        let quotient = r / new_r;
+        (r, new_r) = (new_r, r - quotient * new_r);
+        (t, new_t) = (new_t, t - quotient * new_t);
+    }
+    assert_eq!(r, 1, "{value} has no inverse mod {modulus}");
+    t.rem_euclid(modulus as i64) as u64
+}
+
+/// x <- constant x mod `modulus` if `control` is |1>, for x below the modulus and a constant
+/// coprime to it. b is a scratch register of one qubit more than x, in |0> before and after
+pub fn modular_multiply(
+    circuit: &mut Circuit,
+    constant: u64,
+    modulus: u64,
+    control: usize,
+    x: &[usize],
+    b: &[usize],
+    ancilla: usize,
+) {
+    // b = c x, swap, then b - c^-1 c x = 0
+    modular_multiply_add(circuit, constant, modulus, control, x, b, ancilla);
+    for (x, b) in x.iter().zip(b) {
+        circuit.cnot(*b, *x).toffoli(control, *x, *b).cnot(*b, *x);
+    }
+    let mut undo = Circuit::new(circuit.no_qubits());
+    let inverse = modular_inverse(constant, modulus);
+    modular_multiply_add(&mut undo, inverse, modulus, control, x, b, ancilla);
+    circuit.append(&undo.inverse(&HashMap::new()));
+}
+
+/// x <- x base^e mod `modulus` for the number e in the `exponent` register
+pub fn modular_exponentiation(
+    circuit: &mut Circuit,
+    base: u64,
+    modulus: u64,
+    exponent: &[usize],
+    x: &[usize],
+    b: &[usize],
+    ancilla: usize,
+) {
+    let mut power = base % modulus;
+    for qubit in exponent {
+        modular_multiply(circuit, power, modulus, *qubit, x, b, ancilla);
+        power = power * power % modulus;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::gates::basic::SingleInputGate;
+    use crate::module::mps::Mps;
+    use crate::module::sparse_state::SparseState;
+
+    /// the basis state `circuit` maps the basis state of `inputs` to
+    fn run(registers: &Registers, circuit: &Circuit, inputs: &[(&str, usize)]) -> usize {
+        let n = registers.no_qubits();
+        let index = registers.basis_index(inputs);
+        let mut state = SparseState::new(n);
+        for qubit in (0..n).filter(|qubit| (index >> (n - 1 - qubit)) & 1 == 1) {
+            state.apply_gate(SingleInputGate::X, qubit);
+        }
+        circuit.apply(&mut state, &HashMap::new());
+        let amplitudes = state.nonzero_amplitudes();
+        assert_eq!(amplitudes.len(), 1, "not a basis state");
+        assert!((amplitudes[0].1.norm() - 1.0).abs() < 1e-9);
+        amplitudes[0].0
+    }
+
+    #[test]
+    fn test_cuccaro() {
+        let mut registers = Registers::new();
+        let a = registers.add("a", 3);
+        let b = registers.add("b", 3);
+        let carry = registers.add("carry", 1)[0];
+        let flag = registers.add("flag", 1)[0];
+        let n = registers.no_qubits();
+
+        let mut add = Circuit::new(n);
+        cuccaro_add(&mut add, &a, &b, carry, Some(flag));
+        let mut subtract = Circuit::new(n);
+        cuccaro_subtract(&mut subtract, &a, &b, carry, Some(flag));
+        let mut compare = Circuit::new(n);
+        less_than(&mut compare, &a, &b, carry, flag);
+
+        for x in 0..8 {
+            for y in 0..8 {
+                let out = run(&registers, &add, &[("a", x), ("b", y)]);
+                assert_eq!(registers.value("a", out), x);
+                assert_eq!(registers.value("b", out), (x + y) % 8);
+                assert_eq!(registers.value("carry", out), 0);
+                assert_eq!(registers.value("flag", out), (x + y) / 8);
+
+                let out = run(&registers, &subtract, &[("a", x), ("b", y)]);
+                assert_eq!(registers.value("b", out), (y + 8 - x) % 8);
+                assert_eq!(registers.value("flag", out), (x > y) as usize);
+
+                let out = run(&registers, &compare, &[("a", x), ("b", y), ("flag", 1)]);
+                assert_eq!(registers.value("a", out), x);
+                assert_eq!(registers.value("b", out), y);
+                assert_eq!(registers.value("carry", out), 0);
+                assert_eq!(registers.value("flag", out), 1 ^ (x < y) as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn test_draper() {
+        let mut registers = Registers::new();
+        let a = registers.add("a", 3);
+        let b = registers.add("b", 4);
+        let mut add = Circuit::new(registers.no_qubits());
+        draper_add(&mut add, &a, &b);
+        let mut constant = Circuit::new(registers.no_qubits());
+        add_constant(&mut constant, 11, &b);
+        for x in 0..8 {
+            for y in 0..16 {
+                let out = run(&registers, &add, &[("a", x), ("b", y)]);
+                assert_eq!(registers.value("a", out), x);
+                assert_eq!(registers.value("b", out), (x + y) % 16);
+            }
+        }
+        for y in 0..16 {
+            let out = run(&registers, &constant, &[("b", y)]);
+            assert_eq!(registers.value("b", out), (y + 11) % 16);
+        }
+    }
+
+    #[test]
+    fn test_modular_addition() {
+        let modulus = 11;
+        let mut registers = Registers::new();
+        let control = registers.add("control", 1)[0];
+        let b = registers.add("b", 5);
+        let ancilla = registers.add("ancilla", 1)[0];
+        for constant in 0..modulus {
+            let mut circuit = Circuit::new(registers.no_qubits());
+            modular_add_constant(&mut circuit, constant, modulus, &b, ancilla, &[control]);
+            for y in 0..modulus as usize {
+                for on in 0..2 {
+                    let out = run(&registers, &circuit, &[("control", on), ("b", y)]);
+                    let expected = if on == 1 {
+                        (y + constant as usize) % modulus as usize
+                    } else {
+                        y
+                    };
+                    assert_eq!(registers.value("b", out), expected);
+                    assert_eq!(registers.value("ancilla", out), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_modular_multiplication() {
+        let modulus = 13;
+        let mut registers = Registers::new();
+        let control = registers.add("control", 1)[0];
+        let x = registers.add("x", 4);
+        let b = registers.add("b", 5);
+        let ancilla = registers.add("ancilla", 1)[0];
+        let mut circuit = Circuit::new(registers.no_qubits());
+        modular_multiply(&mut circuit, 5, modulus, control, &x, &b, ancilla);
+        for value in 0..modulus as usize {
+            for on in 0..2 {
+                let out = run(&registers, &circuit, &[("control", on), ("x", value)]);
+                let expected = if on == 1 { 5 * value % 13 } else { value };
+                assert_eq!(registers.value("x", out), expected);
+                assert_eq!(registers.value("b", out), 0);
+                assert_eq!(registers.value("ancilla", out), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_on_mps() {
+        // the mps has no controlled gates of its own and runs the toffolis decomposed
+        let mut registers = Registers::new();
+        let a = registers.add("a", 3);
+        let b = registers.add("b", 3);
+        let carry = registers.add("carry", 1)[0];
+        let mut add = Circuit::new(registers.no_qubits());
+        cuccaro_add(&mut add, &a, &b, carry, None);
+        for (x, y) in [(3, 4), (5, 6), (7, 7)] {
+            let mut state = Mps::new(registers.no_qubits());
+            let index = registers.basis_index(&[("a", x), ("b", y)]);
+            for qubit in 0..registers.no_qubits() {
+                if (index >> (registers.no_qubits() - 1 - qubit)) & 1 == 1 {
+                    state.apply_gate(SingleInputGate::X, qubit);
+                }
+            }
+            add.apply(&mut state, &HashMap::new());
+            let out = state
+                .sample()
+                .iter()
+                .fold(0, |index, zero| (index << 1) | usize::from(!zero));
+            assert_eq!(registers.value("b", out), (x + y) % 8);
+            assert_eq!(registers.value("a", out), x);
+        }
+    }
+
+    #[test]
+    fn test_modular_exponentiation() {
+        let mut registers = Registers::new();
+        let exponent = registers.add("exponent", 3);
+        let x = registers.add("x", 4);
+        let b = registers.add("b", 5);
+        let ancilla = registers.add("ancilla", 1)[0];
+        let mut circuit = Circuit::new(registers.no_qubits());
+        modular_exponentiation(&mut circuit, 7, 15, &exponent, &x, &b, ancilla);
+        for e in 0..8 {
+            let out = run(&registers, &circuit, &[("exponent", e), ("x", 1)]);
+            assert_eq!(registers.value("x", out), 7usize.pow(e as u32) % 15);
+            assert_eq!(registers.value("exponent", out), e);
+            assert_eq!(registers.value("b", out), 0);
+        }
+    }
+}
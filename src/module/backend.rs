@@ -0,0 +1,654 @@
+// Common interface of the simulators
+//
+// The dense state vector, the sparse state vector, the stabilizer tableau and the matrix product
+// state all simulate the same circuits with different costs. Code written against Backend runs
+// on any of them: the dense vector for anything up to ~25 qubits, the sparse vector for mostly
+// classical circuits, the tableau for clifford circuits on thousands of qubits and the mps for
+// wide circuits with little entanglement. The entangled particles keep real amplitudes and only
+// run circuits of real gates (x, z, y, h, rotations around y).
+//
+// As everywhere else qubit 0 is the most significant bit and measurements return true for |0>.
+use crate::module::entangled_particle_n::EntangledParticleN;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::mps::Mps;
+use crate::module::sparse_state::SparseState;
+use crate::module::stabilizer::StabilizerState;
+use crate::module::state_vector::{
+    complex_matrix, phase, rotation_y, rotation_z, StateVector, C64,
+};
+use nalgebra::{Matrix2, SVector, Vector2};
+use rand::prelude::*;
+
+pub trait Backend {
+    /// `no_qubits` qubits in |0>
+    fn allocate(no_qubits: usize) -> Self
+    where
+        Self: Sized;
+
+    fn no_qubits(&self) -> usize;
+
+    /// single qubit gate, the stabilizer backend only accepts clifford gates
+    fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize);
+
+    fn apply_gate(&mut self, gate: SingleInputGate, qubit: usize) {
+        self.apply(&complex_matrix(gate), qubit);
+    }
+
+    fn cnot(&mut self, control: usize, target: usize);
+
+    /// `matrix` on `target` for the basis states where all `controls` are |1>. Backends
+    /// without controlled gates of their own run the decomposition into single qubit gates and
+    /// cnots
+    fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        decompose_controlled(self, matrix, controls, target);
+    }
+
+    /// probability to measure `qubit` as |0>
+    fn probability_zero(&mut self, qubit: usize) -> f64;
+
+    /// measure `qubit` in the standard basis, true for |0>
+    fn measure(&mut self, qubit: usize) -> bool;
+
+    /// measure all qubits without changing the state, true for |0>
+    fn sample(&mut self) -> Vec<bool>;
+
+    /// <z z .. z> on `qubits`
+    fn expectation_z(&mut self, qubits: &[usize]) -> f64;
+
+    /// measure and flip back to |0>
+    fn reset(&mut self, qubit: usize) {
+        if !self.measure(qubit) {
+            self.apply_gate(SingleInputGate::X, qubit);
+        }
+    }
+}
+
+/// (alpha, beta, gamma, delta) with `matrix` = e^(i alpha) rz(beta) ry(gamma) rz(delta)
+fn zyz_angles(matrix: &Matrix2<C64>) -> (f64, f64, f64, f64) {
+    let alpha = matrix.determinant().arg() / 2.0;
+    // special unitary [[a, -b*], [b, a*]] = [[e^(-i(beta + delta)/2) cos, ..], [e^(i(beta -
+    // delta)/2) sin, e^(i(beta + delta)/2) cos]] with cos and sin of gamma / 2
+    let special = matrix * C64::from_polar(1.0, -alpha);
+    let (a, b) = (special[(1, 1)], special[(1, 0)]);
+    let gamma = 2.0 * b.norm().atan2(a.norm());
+    let sum = if a.norm() > 1e-12 { 2.0 * a.arg() } else { 0.0 };
+    let difference = if b.norm() > 1e-12 { 2.0 * b.arg() } else { 0.0 };
+    (
+        alpha,
+        (sum + difference) / 2.0,
+        gamma,
+        (sum - difference) / 2.0,
+    )
+}
+
+/// a unitary whose square is `matrix`
+fn square_root(matrix: &Matrix2<C64>) -> Matrix2<C64> {
+    // sqrt(U) = (U + s I) / sqrt(tr U + 2 s) with s^2 = det U, the sign keeps the denominator
+    // away from 0
+    let root = matrix.determinant().sqrt();
+    let trace = matrix.trace();
+    let s = if (trace + 2.0 * root).norm() >= (trace - 2.0 * root).norm() {
+        root
+    } else {
+        -root
+    };
+    (matrix + Matrix2::identity() * s) / (trace + 2.0 * s).sqrt()
+}
+
+/// true if `a` and `b` agree up to rounding, gates built from products carry small residues
+fn is_close(a: &Matrix2<C64>, b: &Matrix2<C64>) -> bool {
+    (a - b).norm() < 1e-9
+}
+
+/// `matrix` on `target` where all `controls` are |1>, built from single qubit gates and cnots.
+/// One control takes two cnots (U = e^(i alpha) A X B X C with ABC = I), k controls go down to
+/// k - 1 with V^2 = U:
+///
+///   C^k(U) = C^(k-1)(V) on the first controls, then C(V) from the last control, conjugated
+///            by cnots C^(k-1)(X) onto the last control around a C(V^dagger)
+///
+/// The gate count grows like 3^k, fine for the few controls of toffolis and oracles
+pub fn decompose_controlled<B: Backend + ?Sized>(
+    state: &mut B,
+    matrix: &Matrix2<C64>,
+    controls: &[usize],
+    target: usize,
+) {
+    let x = complex_matrix(SingleInputGate::X);
+    match controls {
+        [] => state.apply(matrix, target),
+        [control] if is_close(matrix, &x) => state.cnot(*control, target),
+        [control] => {
+            let (alpha, beta, gamma, delta) = zyz_angles(matrix);
+            state.apply(&rotation_z((delta - beta) / 2.0), target);
+            state.cnot(*control, target);
+            state.apply(
+                &(rotation_y(-gamma / 2.0) * rotation_z(-(delta + beta) / 2.0)),
+                target,
+            );
+            state.cnot(*control, target);
+            state.apply(&(rotation_z(beta) * rotation_y(gamma / 2.0)), target);
+            state.apply(&phase(alpha), *control);
+        }
+        [first @ .., last] => {
+            let root = square_root(matrix);
+            state.apply_controlled(&root, &[*last], target);
+            state.apply_controlled(&x, first, *last);
+            state.apply_controlled(&root.adjoint(), &[*last], target);
+            state.apply_controlled(&x, first, *last);
+            state.apply_controlled(&root, first, target);
+        }
+    }
+}
+
+/// mask of `qubit` in a basis index of `no_qubits` qubits
+fn mask(no_qubits: usize, qubit: usize) -> usize {
+    assert!(qubit < no_qubits);
+    1 << (no_qubits - 1 - qubit)
+}
+
+/// +1 or -1 depending on the parity of the bits of `index` in `mask`
+fn z_sign(index: usize, mask: usize) -> f64 {
+    if (index & mask).count_ones().is_multiple_of(2) {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+/// the qubits of basis state `index`, true for |0>
+fn bits_of(no_qubits: usize, index: usize) -> Vec<bool> {
+    (0..no_qubits)
+        .map(|qubit| index & mask(no_qubits, qubit) == 0)
+        .collect()
+}
+
+impl Backend for StateVector {
+    fn allocate(no_qubits: usize) -> Self {
+        StateVector::new(no_qubits)
+    }
+
+    fn no_qubits(&self) -> usize {
+        StateVector::no_qubits(self)
+    }
+
+    fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize) {
+        StateVector::apply(self, matrix, qubit);
+    }
+
+    fn cnot(&mut self, control: usize, target: usize) {
+        StateVector::cnot(self, control, target);
+    }
+
+    fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        StateVector::apply_controlled(self, matrix, controls, target);
+    }
+
+    fn probability_zero(&mut self, qubit: usize) -> f64 {
+        StateVector::probability_zero(self, qubit)
+    }
+
+    fn measure(&mut self, qubit: usize) -> bool {
+        StateVector::measure(self, qubit)
+    }
+
+    fn sample(&mut self) -> Vec<bool> {
+        let mut remaining = rand::rng().random::<f64>();
+        let amplitudes = self.amplitudes();
+        let index = (0..amplitudes.len())
+            .find(|index| {
+                remaining -= amplitudes[*index].norm_sqr();
+                remaining < 0.0
+            })
+            .unwrap_or(amplitudes.len() - 1);
+        bits_of(self.no_qubits(), index)
+    }
+
+    fn expectation_z(&mut self, qubits: &[usize]) -> f64 {
+        let n = self.no_qubits();
+        let mask = qubits.iter().fold(0, |m, qubit| m | mask(n, *qubit));
+        self.amplitudes()
+            .iter()
+            .enumerate()
+            .map(|(index, amplitude)| z_sign(index, mask) * amplitude.norm_sqr())
+            .sum()
+    }
+}
+
+impl Backend for SparseState {
+    fn allocate(no_qubits: usize) -> Self {
+        SparseState::new(no_qubits)
+    }
+
+    fn no_qubits(&self) -> usize {
+        SparseState::no_qubits(self)
+    }
+
+    fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize) {
+        SparseState::apply(self, matrix, qubit);
+    }
+
+    fn cnot(&mut self, control: usize, target: usize) {
+        SparseState::cnot(self, control, target);
+    }
+
+    fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        SparseState::apply_controlled(self, matrix, controls, target);
+    }
+
+    fn probability_zero(&mut self, qubit: usize) -> f64 {
+        SparseState::probability_zero(self, qubit)
+    }
+
+    fn measure(&mut self, qubit: usize) -> bool {
+        SparseState::measure(self, qubit)
+    }
+
+    fn sample(&mut self) -> Vec<bool> {
+        SparseState::sample(self)
+    }
+
+    fn expectation_z(&mut self, qubits: &[usize]) -> f64 {
+        let n = self.no_qubits();
+        let mask = qubits.iter().fold(0, |m, qubit| m | mask(n, *qubit));
+        self.nonzero_amplitudes()
+            .iter()
+            .map(|(index, amplitude)| z_sign(*index, mask) * amplitude.norm_sqr())
+            .sum()
+    }
+}
+
+/// true if `a` and `b` are the same gate up to a global phase
+fn same_up_to_phase(a: &Matrix2<C64>, b: &Matrix2<C64>) -> bool {
+    let (largest, _) = a
+        .iter()
+        .enumerate()
+        .max_by(|x, y| x.1.norm().total_cmp(&y.1.norm()))
+        .expect("a 2x2 matrix has entries");
+    if b[largest].norm() < 1e-9 {
+        return false;
+    }
+    let ratio = b[largest] / a[largest];
+    (a * ratio - b).norm() < 1e-9
+}
+
+impl Backend for StabilizerState {
+    fn allocate(no_qubits: usize) -> Self {
+        StabilizerState::new(no_qubits)
+    }
+
+    fn no_qubits(&self) -> usize {
+        StabilizerState::no_qubits(self)
+    }
+
+    /// the paulis, h, s and s^dagger, recognized up to a global phase
+    fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize) {
+        let s = phase(std::f64::consts::FRAC_PI_2);
+        if same_up_to_phase(matrix, &Matrix2::identity()) {
+            return;
+        }
+        if same_up_to_phase(matrix, &s) {
+            self.s(qubit);
+        } else if same_up_to_phase(matrix, &s.adjoint()) {
+            self.s_dagger(qubit);
+        } else {
+            let gate = [
+                SingleInputGate::X,
+                SingleInputGate::Y,
+                SingleInputGate::Z,
+                SingleInputGate::Hadamard,
+            ]
+            .into_iter()
+            .find(|gate| same_up_to_phase(matrix, &complex_matrix(*gate)))
+            .expect("the stabilizer backend only simulates clifford gates");
+            StabilizerState::apply_gate(self, gate, qubit);
+        }
+    }
+
+    fn apply_gate(&mut self, gate: SingleInputGate, qubit: usize) {
+        StabilizerState::apply_gate(self, gate, qubit);
+    }
+
+    fn cnot(&mut self, control: usize, target: usize) {
+        StabilizerState::cnot(self, control, target);
+    }
+
+    /// controlled paulis directly, anything else through the decomposition, which only works
+    /// out if all its gates are cliffords
+    fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        let [control] = controls else {
+            return decompose_controlled(self, matrix, controls, target);
+        };
+        let (s, z) = (
+            phase(std::f64::consts::FRAC_PI_2),
+            complex_matrix(SingleInputGate::Z),
+        );
+        // y = s x s^dagger, z = h x h
+        let x = complex_matrix(SingleInputGate::X);
+        if is_close(matrix, &x) {
+            StabilizerState::cnot(self, *control, target);
+        } else if is_close(matrix, &(s * x * s.adjoint())) {
+            self.s_dagger(target);
+            StabilizerState::cnot(self, *control, target);
+            self.s(target);
+        } else if is_close(matrix, &z) {
+            self.h(target);
+            StabilizerState::cnot(self, *control, target);
+            self.h(target);
+        } else {
+            decompose_controlled(self, matrix, controls, target);
+        }
+    }
+
+    fn probability_zero(&mut self, qubit: usize) -> f64 {
+        match self.peek(qubit) {
+            Some(true) => 1.0,
+            Some(false) => 0.0,
+            None => 0.5,
+        }
+    }
+
+    fn measure(&mut self, qubit: usize) -> bool {
+        StabilizerState::measure(self, qubit)
+    }
+
+    fn sample(&mut self) -> Vec<bool> {
+        let mut copy = self.clone();
+        (0..copy.no_qubits())
+            .map(|qubit| copy.measure(qubit))
+            .collect()
+    }
+
+    fn expectation_z(&mut self, qubits: &[usize]) -> f64 {
+        let Some((first, rest)) = qubits.split_first() else {
+            return 1.0;
+        };
+        // collect the parity on the first qubit, look at it and undo the cnots
+        for qubit in rest {
+            StabilizerState::cnot(self, *qubit, *first);
+        }
+        let expectation = self.probability_zero(*first) * 2.0 - 1.0;
+        for qubit in rest.iter().rev() {
+            StabilizerState::cnot(self, *qubit, *first);
+        }
+        expectation
+    }
+
+    fn reset(&mut self, qubit: usize) {
+        StabilizerState::reset(self, qubit);
+    }
+}
+
+impl Backend for Mps {
+    fn allocate(no_qubits: usize) -> Self {
+        Mps::new(no_qubits)
+    }
+
+    fn no_qubits(&self) -> usize {
+        Mps::no_qubits(self)
+    }
+
+    fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize) {
+        Mps::apply(self, matrix, qubit);
+    }
+
+    fn cnot(&mut self, control: usize, target: usize) {
+        Mps::cnot(self, control, target);
+    }
+
+    fn probability_zero(&mut self, qubit: usize) -> f64 {
+        Mps::probability_zero(self, qubit)
+    }
+
+    fn measure(&mut self, qubit: usize) -> bool {
+        Mps::measure(self, qubit)
+    }
+
+    fn sample(&mut self) -> Vec<bool> {
+        Mps::sample(self)
+    }
+
+    fn expectation_z(&mut self, qubits: &[usize]) -> f64 {
+        let z = complex_matrix(SingleInputGate::Z);
+        let ops: Vec<(usize, Matrix2<C64>)> = qubits.iter().map(|qubit| (*qubit, z)).collect();
+        self.expectation(&ops)
+    }
+}
+
+/// `matrix` with real entries, None if it has complex ones. With `up_to_phase` a global phase is
+/// divided out first
+fn real_matrix(matrix: &Matrix2<C64>, up_to_phase: bool) -> Option<Matrix2<f64>> {
+    let mut matrix = *matrix;
+    if up_to_phase {
+        let largest = *matrix
+            .iter()
+            .max_by(|a, b| a.norm().total_cmp(&b.norm()))
+            .expect("a 2x2 matrix has entries");
+        matrix *= C64::from_polar(1.0, -largest.arg());
+    }
+    matrix
+        .iter()
+        .all(|value| value.im.abs() < 1e-9)
+        .then(|| matrix.map(|value| value.re))
+}
+
+impl<const N: usize> Backend for EntangledParticleN<N> {
+    fn allocate(no_qubits: usize) -> Self {
+        assert_eq!(
+            N,
+            1 << no_qubits,
+            "{no_qubits} particles need N = 2^{no_qubits}"
+        );
+        let mut state = SVector::<f64, N>::zeros();
+        state[0] = 1.0;
+        EntangledParticleN::new(state)
+    }
+
+    fn no_qubits(&self) -> usize {
+        self.get_no_particles()
+    }
+
+    /// real gates up to a global phase
+    fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize) {
+        let matrix =
+            real_matrix(matrix, true).expect("the entangled particles only take real gates");
+        EntangledParticleN::apply_real_controlled(self, &matrix, &[], qubit);
+    }
+
+    fn cnot(&mut self, control: usize, target: usize) {
+        let x = SingleInputGate::X.get_matrix::<2>(0);
+        EntangledParticleN::apply_real_controlled(self, &x, &[control], target);
+    }
+
+    /// real gates only, a global phase of the gate is a relative phase once it is controlled
+    fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        let matrix =
+            real_matrix(matrix, false).expect("the entangled particles only take real gates");
+        EntangledParticleN::apply_real_controlled(self, &matrix, controls, target);
+    }
+
+    fn probability_zero(&mut self, qubit: usize) -> f64 {
+        self.standard_probability_zero(qubit)
+    }
+
+    /// in the standard basis, whatever basis `swap_basis` picked for the particle
+    fn measure(&mut self, qubit: usize) -> bool {
+        self.measure_in(qubit, &Vector2::new(1.0, 0.0), &Vector2::new(0.0, 1.0))
+    }
+
+    fn sample(&mut self) -> Vec<bool> {
+        let mut remaining = rand::rng().random::<f64>();
+        let amplitudes = self.get_params();
+        let index = (0..N)
+            .find(|index| {
+                remaining -= amplitudes[*index].powi(2);
+                remaining < 0.0
+            })
+            .unwrap_or(N - 1);
+        bits_of(self.no_qubits(), index)
+    }
+
+    fn expectation_z(&mut self, qubits: &[usize]) -> f64 {
+        let n = self.no_qubits();
+        let mask = qubits.iter().fold(0, |m, qubit| m | mask(n, *qubit));
+        self.get_params()
+            .iter()
+            .enumerate()
+            .map(|(index, amplitude)| z_sign(index, mask) * amplitude.powi(2))
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::pauli::Pauli;
+    use std::f64::consts::FRAC_PI_2;
+
+    /// a bell pair on 0 and the last qubit with an x on the middle, the same on every backend
+    fn check_backend<B: Backend>() {
+        let mut state = B::allocate(4);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        state.cnot(0, 3);
+        state.apply_gate(SingleInputGate::X, 1);
+        state.apply(&phase(std::f64::consts::FRAC_PI_2), 3);
+
+        assert!((state.probability_zero(0) - 0.5).abs() < 1e-9);
+        assert!((state.expectation_z(&[0, 3]) - 1.0).abs() < 1e-9);
+        assert!((state.expectation_z(&[1]) + 1.0).abs() < 1e-9);
+        assert!(state.expectation_z(&[0]).abs() < 1e-9);
+        assert!((state.expectation_z(&[]) - 1.0).abs() < 1e-9);
+
+        let bits = state.sample();
+        assert_eq!(bits[0], bits[3]);
+        assert!(bits[2] && !bits[1]);
+
+        let first = state.measure(3);
+        assert_eq!(state.measure(0), first);
+        state.reset(1);
+        assert!(state.measure(1));
+    }
+
+    #[test]
+    fn test_all_backends() {
+        check_backend::<StateVector>();
+        check_backend::<SparseState>();
+        check_backend::<StabilizerState>();
+        check_backend::<Mps>();
+    }
+
+    #[test]
+    fn test_decompose_controlled() {
+        let mut rng = rand::rng();
+        let n = 5;
+        for no_controls in 1..4 {
+            for _i in 0..10 {
+                let matrix = phase(rng.random::<f64>() * 6.0)
+                    * rotation_y(rng.random::<f64>() * 6.0)
+                    * rotation_z(rng.random::<f64>() * 6.0)
+                    * C64::from_polar(1.0, rng.random::<f64>() * 6.0);
+                let mut qubits: Vec<usize> = (0..n).collect();
+                qubits.shuffle(&mut rng);
+                let (target, controls) = (qubits[0], &qubits[1..=no_controls]);
+
+                let mut native = StateVector::new(n);
+                for qubit in 0..n {
+                    native.apply(&rotation_y(rng.random::<f64>() * 3.0), qubit);
+                }
+                let mut decomposed = native.clone();
+                native.apply_controlled(&matrix, controls, target);
+                decompose_controlled(&mut decomposed, &matrix, controls, target);
+                assert!((native.amplitudes() - decomposed.amplitudes()).norm() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_controlled_gates_on_all_backends() {
+        // toffoli on |110> and a controlled z kicking back onto |+>
+        fn check<B: Backend>() {
+            let mut state = B::allocate(4);
+            state.apply_gate(SingleInputGate::X, 0);
+            state.apply_gate(SingleInputGate::X, 1);
+            state.apply_controlled(&complex_matrix(SingleInputGate::X), &[0, 1], 2);
+            assert!(!state.measure(2));
+            state.apply_gate(SingleInputGate::Hadamard, 3);
+            state.apply_controlled(&complex_matrix(SingleInputGate::Z), &[0], 3);
+            state.apply_gate(SingleInputGate::Hadamard, 3);
+            assert!(!state.measure(3));
+        }
+        check::<StateVector>();
+        check::<SparseState>();
+        check::<Mps>();
+        check::<EntangledParticleN<16>>();
+
+        // the stabilizer backend has the controlled paulis, a toffoli needs t gates
+        let mut state = StabilizerState::allocate(2);
+        state.apply_gate(SingleInputGate::X, 0);
+        state.apply_controlled(&complex_matrix(SingleInputGate::Z), &[0], 1);
+        Backend::apply_controlled(
+            &mut state,
+            &(phase(FRAC_PI_2) * complex_matrix(SingleInputGate::X) * phase(-FRAC_PI_2)),
+            &[0],
+            1,
+        );
+        assert!(!state.measure(1));
+    }
+
+    #[test]
+    fn test_stabilizer_controlled_paulis() {
+        // the exact pauli matrices, not products of gates
+        for (pauli, flips) in [(Pauli::X, true), (Pauli::Y, true), (Pauli::Z, false)] {
+            let mut state = StabilizerState::allocate(2);
+            state.apply_gate(SingleInputGate::X, 0);
+            state.apply_controlled(&pauli.matrix(), &[0], 1);
+            assert_eq!(state.measure(1), !flips);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "clifford")]
+    fn test_stabilizer_rejects_toffoli() {
+        let mut state = StabilizerState::allocate(3);
+        Backend::apply_controlled(&mut state, &complex_matrix(SingleInputGate::X), &[0, 1], 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "clifford")]
+    fn test_stabilizer_rejects_t_gate() {
+        let mut state = StabilizerState::allocate(1);
+        Backend::apply(&mut state, &phase(std::f64::consts::FRAC_PI_4), 0);
+    }
+
+    #[test]
+    fn test_entangled_particles() {
+        // the backend check without the phase gate
+        let mut state = EntangledParticleN::<16>::allocate(4);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        state.cnot(0, 3);
+        state.apply_gate(SingleInputGate::X, 1);
+        state.apply(&rotation_z(std::f64::consts::PI), 3);
+
+        assert!((Backend::probability_zero(&mut state, 0) - 0.5).abs() < 1e-9);
+        assert!((state.expectation_z(&[0, 3]) - 1.0).abs() < 1e-9);
+        assert!((state.expectation_z(&[1]) + 1.0).abs() < 1e-9);
+        assert!(state.expectation_z(&[0]).abs() < 1e-9);
+
+        let bits = state.sample();
+        assert_eq!(bits[0], bits[3]);
+        assert!(bits[2] && !bits[1]);
+
+        let first = Backend::measure(&mut state, 3);
+        assert_eq!(Backend::measure(&mut state, 0), first);
+        state.reset(1);
+        assert!(Backend::measure(&mut state, 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "real gates")]
+    fn test_entangled_particles_reject_complex_gates() {
+        let mut state = EntangledParticleN::<4>::allocate(2);
+        state.apply(&phase(FRAC_PI_2), 0);
+    }
+}
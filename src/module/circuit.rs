@@ -0,0 +1,593 @@
+// Parameterized circuits and their gradients
+//
+// A circuit is a list of gates whose angles are either numbers or named parameters, optionally
+// scaled, such as 2 * theta. The names are bound to values only when the circuit runs, so one
+// circuit serves every point of an optimization.
+//
+// The gradient of an expectation value <psi(theta)|H|psi(theta)> is exact for rotation gates
+// e^(-i angle G / 2) with G^2 = 1 (rx, ry, rz and, up to a global phase, the phase gate): by the
+// parameter-shift rule
+//
+//     d<H>/d angle = (<H>(angle + pi / 2) - <H>(angle - pi / 2)) / 2
+//
+// which needs two circuit runs per occurrence of the parameter and no small step, so it also
+// works with shot estimates. For any other parameterized gate the derivative falls back to
+// central finite differences. A parameter used several times sums over its occurrences, each
+// multiplied by its scale.
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::pauli::PauliSum;
+use crate::module::state_vector::{
+    complex_matrix, phase, rotation_x, rotation_y, rotation_z, StateVector, C64,
+};
+use crate::module::vqe::Ansatz;
+use nalgebra::Matrix2;
+use std::collections::HashMap;
+use std::f64::consts::FRAC_PI_2;
+use std::fmt;
+
+/// a fixed angle or `scale` times a named parameter
+#[derive(Clone, Debug, PartialEq)]
+pub enum Angle {
+    Fixed(f64),
+    Parameter { name: String, scale: f64 },
+}
+
+impl Angle {
+    pub fn parameter(name: &str) -> Self {
+        Angle::Parameter {
+            name: name.to_string(),
+            scale: 1.0,
+        }
+    }
+
+    pub fn scaled(name: &str, scale: f64) -> Self {
+        Angle::Parameter {
+            name: name.to_string(),
+            scale,
+        }
+    }
+
+    /// the value for the given bindings, panics on an unbound parameter
+    pub fn value(&self, values: &HashMap<String, f64>) -> f64 {
+        match self {
+            Angle::Fixed(angle) => *angle,
+            Angle::Parameter { name, scale } => {
+                scale
+                    * values
+                        .get(name)
+                        .unwrap_or_else(|| panic!("unbound parameter {name}"))
+            }
+        }
+    }
+
+    fn name(&self) -> Option<&str> {
+        match self {
+            Angle::Fixed(_) => None,
+            Angle::Parameter { name, .. } => Some(name),
+        }
+    }
+}
+
+impl From<f64> for Angle {
+    fn from(angle: f64) -> Self {
+        Angle::Fixed(angle)
+    }
+}
+
+impl From<&str> for Angle {
+    fn from(name: &str) -> Self {
+        Angle::parameter(name)
+    }
+}
+
+impl fmt::Display for Angle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Angle::Fixed(angle) => write!(f, "{angle}"),
+            Angle::Parameter { name, scale } if *scale == 1.0 => write!(f, "{name}"),
+            Angle::Parameter { name, scale } => write!(f, "{scale}*{name}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum Gate {
+    Single(SingleInputGate, usize),
+    Matrix(Matrix2<C64>, usize),
+    Cnot(usize, usize),
+    Rx(Angle, usize),
+    Ry(Angle, usize),
+    Rz(Angle, usize),
+    Phase(Angle, usize),
+    /// any single qubit gate of one angle, differentiated by finite differences
+    Parameterized(Angle, fn(f64) -> Matrix2<C64>, usize),
+    /// a fixed matrix where all the controls are |1>
+    Controlled(Vec<usize>, Matrix2<C64>, usize),
+}
+
+impl Gate {
+    fn angle(&self) -> Option<&Angle> {
+        match self {
+            Gate::Rx(angle, _)
+            | Gate::Ry(angle, _)
+            | Gate::Rz(angle, _)
+            | Gate::Phase(angle, _)
+            | Gate::Parameterized(angle, _, _) => Some(angle),
+            _ => None,
+        }
+    }
+
+    /// whether the parameter-shift rule holds
+    fn is_rotation(&self) -> bool {
+        matches!(
+            self,
+            Gate::Rx(..) | Gate::Ry(..) | Gate::Rz(..) | Gate::Phase(..)
+        )
+    }
+
+    fn target(&self) -> usize {
+        match self {
+            Gate::Single(_, qubit)
+            | Gate::Matrix(_, qubit)
+            | Gate::Cnot(_, qubit)
+            | Gate::Rx(_, qubit)
+            | Gate::Ry(_, qubit)
+            | Gate::Rz(_, qubit)
+            | Gate::Phase(_, qubit)
+            | Gate::Parameterized(_, _, qubit)
+            | Gate::Controlled(_, _, qubit) => *qubit,
+        }
+    }
+
+    fn controls(&self) -> Vec<usize> {
+        match self {
+            Gate::Cnot(control, _) => vec![*control],
+            Gate::Controlled(controls, _, _) => controls.clone(),
+            _ => vec![],
+        }
+    }
+
+    /// the matrix on the target with the angle shifted by `shift`, x for a cnot
+    fn matrix(&self, values: &HashMap<String, f64>, shift: f64) -> Matrix2<C64> {
+        let angle = || self.angle().expect("a parameterized gate").value(values) + shift;
+        match self {
+            Gate::Single(gate, _) => complex_matrix(*gate),
+            Gate::Matrix(matrix, _) => *matrix,
+            Gate::Cnot(..) => complex_matrix(SingleInputGate::X),
+            Gate::Rx(..) => rotation_x(angle()),
+            Gate::Ry(..) => rotation_y(angle()),
+            Gate::Rz(..) => rotation_z(angle()),
+            Gate::Phase(..) => phase(angle()),
+            Gate::Parameterized(_, matrix, _) => matrix(angle()),
+            Gate::Controlled(_, matrix, _) => *matrix,
+        }
+    }
+
+    fn apply(&self, state: &mut dyn Backend, values: &HashMap<String, f64>, shift: f64) {
+        match self {
+            Gate::Cnot(control, target) => state.cnot(*control, *target),
+            Gate::Controlled(controls, matrix, target) => {
+                state.apply_controlled(matrix, controls, *target)
+            }
+            _ => state.apply(&self.matrix(values, shift), self.target()),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Circuit {
+    no_qubits: usize,
+    gates: Vec<Gate>,
+    epsilon: f64,
+}
+
+impl Circuit {
+    pub fn new(no_qubits: usize) -> Self {
+        Self {
+            no_qubits,
+            gates: vec![],
+            epsilon: 1e-6,
+        }
+    }
+
+    /// step of the finite differences for gates without a shift rule
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    pub fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    pub fn gates(&self) -> &[Gate] {
+        &self.gates
+    }
+
+    pub fn push(&mut self, gate: Gate) -> &mut Self {
+        assert!(gate.target() < self.no_qubits);
+        let controls = gate.controls();
+        for (i, control) in controls.iter().enumerate() {
+            assert!(*control < self.no_qubits && *control != gate.target());
+            assert!(!controls[..i].contains(control), "controls repeat");
+        }
+        self.gates.push(gate);
+        self
+    }
+
+    pub fn h(&mut self, qubit: usize) -> &mut Self {
+        self.push(Gate::Single(SingleInputGate::Hadamard, qubit))
+    }
+
+    pub fn x(&mut self, qubit: usize) -> &mut Self {
+        self.push(Gate::Single(SingleInputGate::X, qubit))
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) -> &mut Self {
+        self.push(Gate::Cnot(control, target))
+    }
+
+    pub fn toffoli(&mut self, first: usize, second: usize, target: usize) -> &mut Self {
+        self.mcx(&[first, second], target)
+    }
+
+    /// x on `target` where all `controls` are |1>
+    pub fn mcx(&mut self, controls: &[usize], target: usize) -> &mut Self {
+        self.push(Gate::Controlled(
+            controls.to_vec(),
+            complex_matrix(SingleInputGate::X),
+            target,
+        ))
+    }
+
+    pub fn controlled_phase(&mut self, angle: f64, control: usize, target: usize) -> &mut Self {
+        self.push(Gate::Controlled(vec![control], phase(angle), target))
+    }
+
+    pub fn rx(&mut self, angle: impl Into<Angle>, qubit: usize) -> &mut Self {
+        self.push(Gate::Rx(angle.into(), qubit))
+    }
+
+    pub fn ry(&mut self, angle: impl Into<Angle>, qubit: usize) -> &mut Self {
+        self.push(Gate::Ry(angle.into(), qubit))
+    }
+
+    pub fn rz(&mut self, angle: impl Into<Angle>, qubit: usize) -> &mut Self {
+        self.push(Gate::Rz(angle.into(), qubit))
+    }
+
+    pub fn phase(&mut self, angle: impl Into<Angle>, qubit: usize) -> &mut Self {
+        self.push(Gate::Phase(angle.into(), qubit))
+    }
+
+    /// the parameters in order of first use
+    pub fn parameters(&self) -> Vec<String> {
+        let mut names: Vec<String> = vec![];
+        for name in self.gates.iter().filter_map(|gate| gate.angle()?.name()) {
+            if !names.iter().any(|known| known == name) {
+                names.push(name.to_string());
+            }
+        }
+        names
+    }
+
+    /// bindings for `values` in the order of `parameters`
+    pub fn bind(&self, values: &[f64]) -> HashMap<String, f64> {
+        let names = self.parameters();
+        assert_eq!(values.len(), names.len());
+        names.into_iter().zip(values.iter().copied()).collect()
+    }
+
+    /// run on `state`, shifting the angle of the gate at `shifted` by the given amount
+    fn apply_shifted(
+        &self,
+        state: &mut dyn Backend,
+        values: &HashMap<String, f64>,
+        shifted: Option<(usize, f64)>,
+    ) {
+        assert_eq!(state.no_qubits(), self.no_qubits);
+        for (index, gate) in self.gates.iter().enumerate() {
+            let shift = match shifted {
+                Some((at, shift)) if at == index => shift,
+                _ => 0.0,
+            };
+            gate.apply(state, values, shift);
+        }
+    }
+
+    pub fn apply(&self, state: &mut dyn Backend, values: &HashMap<String, f64>) {
+        self.apply_shifted(state, values, None);
+    }
+
+    /// the gates of `other` after these
+    pub fn append(&mut self, other: &Circuit) -> &mut Self {
+        assert_eq!(self.no_qubits, other.no_qubits);
+        self.gates.extend(other.gates.iter().cloned());
+        self
+    }
+
+    /// the circuit for the given bindings with every gate also controlled by `controls`
+    pub fn controlled(&self, controls: &[usize], values: &HashMap<String, f64>) -> Circuit {
+        let mut circuit = Circuit {
+            no_qubits: self.no_qubits,
+            gates: vec![],
+            epsilon: self.epsilon,
+        };
+        for gate in &self.gates {
+            let mut all_controls = controls.to_vec();
+            all_controls.extend(gate.controls());
+            circuit.push(Gate::Controlled(
+                all_controls,
+                gate.matrix(values, 0.0),
+                gate.target(),
+            ));
+        }
+        circuit
+    }
+
+    /// the adjoint circuit for the given bindings, its gates are fixed matrices
+    pub fn inverse(&self, values: &HashMap<String, f64>) -> Circuit {
+        let gates = self
+            .gates
+            .iter()
+            .rev()
+            .map(|gate| match gate {
+                Gate::Cnot(..) => gate.clone(),
+                Gate::Controlled(controls, matrix, target) => {
+                    Gate::Controlled(controls.clone(), matrix.adjoint(), *target)
+                }
+                _ => Gate::Matrix(gate.matrix(values, 0.0).adjoint(), gate.target()),
+            })
+            .collect();
+        Circuit {
+            no_qubits: self.no_qubits,
+            gates,
+            epsilon: self.epsilon,
+        }
+    }
+
+    /// run on the qubits `offset..offset + no_qubits` of a larger register, only for the basis
+    /// states where all `controls` are |1>
+    pub fn apply_controlled(
+        &self,
+        state: &mut dyn Backend,
+        controls: &[usize],
+        offset: usize,
+        values: &HashMap<String, f64>,
+    ) {
+        assert!(offset + self.no_qubits <= state.no_qubits());
+        for gate in &self.gates {
+            let mut all_controls = controls.to_vec();
+            all_controls.extend(gate.controls().iter().map(|control| offset + control));
+            state.apply_controlled(
+                &gate.matrix(values, 0.0),
+                &all_controls,
+                offset + gate.target(),
+            );
+        }
+    }
+
+    /// the state the circuit prepares from |00..0>
+    pub fn state(&self, values: &HashMap<String, f64>) -> StateVector {
+        self.state_on(values)
+    }
+
+    /// the state the circuit prepares from |00..0> on the simulator `B`
+    pub fn state_on<B: Backend>(&self, values: &HashMap<String, f64>) -> B {
+        let mut state = B::allocate(self.no_qubits);
+        self.apply(&mut state, values);
+        state
+    }
+
+    fn shifted_expectation<B: Backend + Clone>(
+        &self,
+        observable: &PauliSum,
+        values: &HashMap<String, f64>,
+        shifted: Option<(usize, f64)>,
+    ) -> f64 {
+        let mut state = B::allocate(self.no_qubits);
+        self.apply_shifted(&mut state, values, shifted);
+        observable.expectation(&state)
+    }
+
+    pub fn expectation(&self, observable: &PauliSum, values: &HashMap<String, f64>) -> f64 {
+        self.expectation_on::<StateVector>(observable, values)
+    }
+
+    /// the expectation with the circuit run on the simulator `B`
+    pub fn expectation_on<B: Backend + Clone>(
+        &self,
+        observable: &PauliSum,
+        values: &HashMap<String, f64>,
+    ) -> f64 {
+        self.shifted_expectation::<B>(observable, values, None)
+    }
+
+    /// d<observable>/d parameter for every parameter: parameter shift for rotations and finite
+    /// differences for the other gates
+    pub fn gradient(
+        &self,
+        observable: &PauliSum,
+        values: &HashMap<String, f64>,
+    ) -> HashMap<String, f64> {
+        self.gradient_on::<StateVector>(observable, values)
+    }
+
+    /// the gradient with the circuit run on the simulator `B`
+    pub fn gradient_on<B: Backend + Clone>(
+        &self,
+        observable: &PauliSum,
+        values: &HashMap<String, f64>,
+    ) -> HashMap<String, f64> {
+        let mut gradient: HashMap<String, f64> = self
+            .parameters()
+            .into_iter()
+            .map(|name| (name, 0.0))
+            .collect();
+        for (index, gate) in self.gates.iter().enumerate() {
+            let Some(Angle::Parameter { name, scale }) = gate.angle() else {
+                continue;
+            };
+            let shift = if gate.is_rotation() {
+                FRAC_PI_2
+            } else {
+                self.epsilon
+            };
+            let plus = self.shifted_expectation::<B>(observable, values, Some((index, shift)));
+            let minus = self.shifted_expectation::<B>(observable, values, Some((index, -shift)));
+            let derivative = if gate.is_rotation() {
+                (plus - minus) / 2.0
+            } else {
+                (plus - minus) / (2.0 * shift)
+            };
+            *gradient.get_mut(name).unwrap() += scale * derivative;
+        }
+        gradient
+    }
+
+    /// the gradient by finite differences of the parameter values, for any gate
+    pub fn finite_difference_gradient(
+        &self,
+        observable: &PauliSum,
+        values: &HashMap<String, f64>,
+    ) -> HashMap<String, f64> {
+        self.parameters()
+            .into_iter()
+            .map(|name| {
+                let mut shifted = values.clone();
+                *shifted.get_mut(&name).expect("a bound parameter") += self.epsilon;
+                let plus = self.expectation(observable, &shifted);
+                *shifted.get_mut(&name).unwrap() -= 2.0 * self.epsilon;
+                let minus = self.expectation(observable, &shifted);
+                (name, (plus - minus) / (2.0 * self.epsilon))
+            })
+            .collect()
+    }
+}
+
+/// the parameters are passed in the order of `parameters`
+impl Ansatz for Circuit {
+    fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    fn no_parameters(&self) -> usize {
+        self.parameters().len()
+    }
+
+    fn apply(&self, state: &mut dyn Backend, parameters: &[f64]) {
+        Circuit::apply(self, state, &self.bind(parameters));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::mps::Mps;
+    use crate::module::pauli::PauliString;
+    use crate::module::sparse_state::SparseState;
+
+    /// an arbitrary gate with a derivative but no two term shift rule
+    fn wobble(angle: f64) -> Matrix2<C64> {
+        rotation_y(angle * angle) * rotation_z(angle.sin())
+    }
+
+    fn circuit() -> Circuit {
+        let mut circuit = Circuit::new(3);
+        circuit
+            .h(0)
+            .ry("theta", 0)
+            .rx(Angle::scaled("phi", 2.0), 1)
+            .cnot(0, 1)
+            .rz("theta", 1)
+            .phase(-0.5, 2)
+            .ry(Angle::scaled("phi", -0.7), 2)
+            .cnot(1, 2)
+            .phase("lambda", 2)
+            .push(Gate::Parameterized("mu".into(), wobble, 2))
+            .h(2);
+        circuit
+    }
+
+    #[test]
+    fn test_parameter_shift_matches_finite_differences() {
+        let circuit = circuit();
+        assert_eq!(circuit.parameters(), vec!["theta", "phi", "lambda", "mu"]);
+        let observable: PauliSum = "0.5*Z0Z1 + 0.3*X2 - Y1 + 0.2*Z2".parse().unwrap();
+        for values in [[0.3, -1.1, 0.7, 0.4], [2.0, 0.25, -0.3, 1.3]] {
+            let values = circuit.bind(&values);
+            let exact = circuit.gradient(&observable, &values);
+            let approximate = circuit
+                .clone()
+                .with_epsilon(1e-5)
+                .finite_difference_gradient(&observable, &values);
+            for name in circuit.parameters() {
+                assert!(
+                    (exact[&name] - approximate[&name]).abs() < 1e-6,
+                    "{name}: {} {}",
+                    exact[&name],
+                    approximate[&name]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_rotation() {
+        // <Z> after ry(theta) is cos(theta)
+        let mut circuit = Circuit::new(1);
+        circuit.ry("theta", 0);
+        let observable = 1.0 * PauliString::z(0);
+        for theta in [0.0, 0.4, 1.5, 3.0] {
+            let values = circuit.bind(&[theta]);
+            assert!((circuit.expectation(&observable, &values) - theta.cos()).abs() < 1e-12);
+            assert!((circuit.gradient(&observable, &values)["theta"] + theta.sin()).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_backends() {
+        let circuit = circuit();
+        let observable: PauliSum = "0.5*Z0Z1 + 0.3*X2 - Y1 + 0.2*Z2".parse().unwrap();
+        let values = circuit.bind(&[0.3, -1.1, 0.7, 0.4]);
+        let dense = circuit.expectation(&observable, &values);
+        assert!(
+            (circuit.expectation_on::<SparseState>(&observable, &values) - dense).abs() < 1e-12
+        );
+        assert!((circuit.expectation_on::<Mps>(&observable, &values) - dense).abs() < 1e-12);
+        let gradient = circuit.gradient(&observable, &values);
+        for (name, derivative) in circuit.gradient_on::<Mps>(&observable, &values) {
+            assert!((derivative - gradient[&name]).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_inverse_and_controlled() {
+        let circuit = circuit();
+        let values = circuit.bind(&[0.3, -1.1, 0.7, 0.4]);
+        let mut state = circuit.state(&values);
+        circuit.inverse(&values).apply(&mut state, &values);
+        assert!((state.amplitudes()[0].norm() - 1.0).abs() < 1e-12);
+
+        // controlled on |1> it acts on the lower qubits, on |0> not at all
+        let mut state = StateVector::new(4);
+        state.apply_gate(SingleInputGate::X, 0);
+        circuit.apply_controlled(&mut state, &[0], 1, &values);
+        let expected = circuit.state(&values);
+        assert!((state.amplitudes().rows(8, 8) - expected.amplitudes()).norm() < 1e-12);
+        let mut state = StateVector::new(4);
+        circuit.apply_controlled(&mut state, &[0], 1, &values);
+        assert_eq!(state, StateVector::new(4));
+    }
+
+    #[test]
+    #[should_panic(expected = "unbound parameter phi")]
+    fn test_unbound_parameter() {
+        let mut circuit = Circuit::new(2);
+        circuit.rx("theta", 0).rz("phi", 1);
+        let values = HashMap::from([("theta".to_string(), 0.1)]);
+        circuit.state(&values);
+    }
+}
@@ -0,0 +1,445 @@
+// Eavesdropping strategies for the key distribution protocols (BB84, Ekert)
+//
+// All strategies act on a single qubit in flight, given as a real state vector. Bases are given
+// as angles in degrees, using the same convention as `Basis::rotate_to_angle`: the first basis
+// vector (bit `true`) is (cos a, sin a) and the second one (bit `false`) is (-sin a, cos a).
+use nalgebra::Vector2;
+use rand::prelude::*;
+use std::collections::BTreeMap;
+
+/// first basis vector (bit `true`) of the basis rotated by `angle` degrees
+pub fn basis_vector(angle: f64) -> Vector2<f64> {
+    let radians = angle.to_radians();
+    Vector2::new(radians.cos(), radians.sin())
+}
+
+/// the state orthogonal to `state` in the real plane
+pub fn orthogonal(state: &Vector2<f64>) -> Vector2<f64> {
+    Vector2::new(-state[1], state[0])
+}
+
+/// projective measurement of `state` in the basis rotated by `angle` degrees
+pub fn measure_in_basis(state: &Vector2<f64>, angle: f64) -> bool {
+    let probability = basis_vector(angle).dot(state).powi(2);
+    rand::rng().random_bool(probability.clamp(0.0, 1.0))
+}
+
+/// the state that encodes `bit` in the basis rotated by `angle` degrees
+pub fn encode(bit: bool, angle: f64) -> Vector2<f64> {
+    let first = basis_vector(angle);
+    if bit {
+        first
+    } else {
+        orthogonal(&first)
+    }
+}
+
+/// binary entropy in bits
+pub fn binary_entropy(p: f64) -> f64 {
+    if p <= 0.0 || p >= 1.0 {
+        return 0.0;
+    }
+    -p * p.log2() - (1.0 - p) * (1.0 - p).log2()
+}
+
+/// Eves guess for a key bit once the basis has been announced
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Guess {
+    pub bit: bool,
+    /// probability that the guess is right as far as eve can tell
+    pub confidence: f64,
+}
+
+impl Guess {
+    /// maximum likelihood guess when eve holds `state` and the key bit was encoded in `angle`
+    pub fn from_state(state: &Vector2<f64>, angle: f64) -> Self {
+        let probability = basis_vector(angle).dot(state).powi(2);
+        if probability >= 0.5 {
+            Guess {
+                bit: true,
+                confidence: probability,
+            }
+        } else {
+            Guess {
+                bit: false,
+                confidence: 1.0 - probability,
+            }
+        }
+    }
+}
+
+pub trait Eavesdropper {
+    /// Eve gets hold of a qubit (carried by `photons` photons) on its way to the receiver.
+    /// Returns the state that is forwarded, or None if the qubit never arrives.
+    /// Every call corresponds to the next position in the stream.
+    fn intercept(&mut self, state: Vector2<f64>, photons: usize) -> Option<Vector2<f64>>;
+
+    /// An empty pulse passed, there was nothing to intercept at this position of the stream.
+    fn empty_pulse(&mut self);
+
+    /// Eves guess for the bit at stream position `index` once the basis `angle` is public.
+    /// None if eve learned nothing about that position.
+    fn guess(&self, index: usize, angle: f64) -> Option<Guess>;
+}
+
+/// Eve measures every qubit in one of `bases` picked at random and resends what she saw
+pub struct InterceptResend {
+    bases: Vec<f64>,
+    held: Vec<Option<Vector2<f64>>>,
+}
+
+impl InterceptResend {
+    pub fn new(bases: Vec<f64>) -> Self {
+        assert!(!bases.is_empty(), "eve needs at least one basis");
+        Self {
+            bases,
+            held: vec![],
+        }
+    }
+}
+
+impl Eavesdropper for InterceptResend {
+    fn intercept(&mut self, state: Vector2<f64>, _photons: usize) -> Option<Vector2<f64>> {
+        let angle = self.bases[rand::rng().random_range(0..self.bases.len())];
+        let resent = encode(measure_in_basis(&state, angle), angle);
+        self.held.push(Some(resent));
+        Some(resent)
+    }
+
+    fn empty_pulse(&mut self) {
+        self.held.push(None);
+    }
+
+    fn guess(&self, index: usize, angle: f64) -> Option<Guess> {
+        self.held[index].map(|state| Guess::from_state(&state, angle))
+    }
+}
+
+/// Eve only attacks a `fraction` of the qubits, the rest passes untouched
+pub struct PartialInterception<E: Eavesdropper> {
+    inner: E,
+    fraction: f64,
+    // position in the stream of the inner strategy for every intercepted qubit
+    positions: Vec<Option<usize>>,
+    inner_count: usize,
+}
+
+impl<E: Eavesdropper> PartialInterception<E> {
+    pub fn new(inner: E, fraction: f64) -> Self {
+        assert!(
+            (0.0..=1.0).contains(&fraction),
+            "fraction must be in [0, 1]"
+        );
+        Self {
+            inner,
+            fraction,
+            positions: vec![],
+            inner_count: 0,
+        }
+    }
+}
+
+impl<E: Eavesdropper> Eavesdropper for PartialInterception<E> {
+    fn intercept(&mut self, state: Vector2<f64>, photons: usize) -> Option<Vector2<f64>> {
+        if rand::rng().random_bool(self.fraction) {
+            self.positions.push(Some(self.inner_count));
+            self.inner_count += 1;
+            self.inner.intercept(state, photons)
+        } else {
+            self.positions.push(None);
+            Some(state)
+        }
+    }
+
+    fn empty_pulse(&mut self) {
+        self.positions.push(None);
+    }
+
+    fn guess(&self, index: usize, angle: f64) -> Option<Guess> {
+        self.positions[index].and_then(|inner_index| self.inner.guess(inner_index, angle))
+    }
+}
+
+/// Eve measures in the intermediate (Breidbart) basis halfway between the two protocol bases.
+/// She gets the bit right with probability cos^2(pi/8) in either basis
+pub struct Breidbart {
+    angle: f64,
+    held: Vec<Option<Vector2<f64>>>,
+}
+
+impl Breidbart {
+    pub fn new(angle: f64) -> Self {
+        Self {
+            angle,
+            held: vec![],
+        }
+    }
+
+    pub fn between(angle_a: f64, angle_b: f64) -> Self {
+        Self::new((angle_a + angle_b) / 2.0)
+    }
+}
+
+impl Eavesdropper for Breidbart {
+    fn intercept(&mut self, state: Vector2<f64>, _photons: usize) -> Option<Vector2<f64>> {
+        let resent = encode(measure_in_basis(&state, self.angle), self.angle);
+        self.held.push(Some(resent));
+        Some(resent)
+    }
+
+    fn empty_pulse(&mut self) {
+        self.held.push(None);
+    }
+
+    fn guess(&self, index: usize, angle: f64) -> Option<Guess> {
+        self.held[index].map(|state| Guess::from_state(&state, angle))
+    }
+}
+
+/// Phase covariant cloner for the real plane
+/// |0>|0> -> |0>|0>, |1>|0> -> cos(eta)|1>|0> + sin(eta)|0>|1> (in the frame where the real states
+/// are on the equator). The receiver gets the original with fidelity (1 + cos(eta)) / 2, eve keeps
+/// a clone with fidelity (1 + sin(eta)) / 2 and measures it once the basis is announced.
+/// eta = pi/4 is the optimal symmetric cloner.
+pub struct PhaseCovariantCloner {
+    eta: f64,
+    // eves clone of every qubit
+    held: Vec<Option<Vector2<f64>>>,
+}
+
+impl PhaseCovariantCloner {
+    pub fn new(eta: f64) -> Self {
+        Self { eta, held: vec![] }
+    }
+
+    pub fn optimal() -> Self {
+        Self::new(std::f64::consts::FRAC_PI_4)
+    }
+
+    pub fn receiver_fidelity(&self) -> f64 {
+        (1.0 + self.eta.cos()) / 2.0
+    }
+
+    pub fn eve_fidelity(&self) -> f64 {
+        (1.0 + self.eta.sin()) / 2.0
+    }
+
+    /// joint probabilities of (receiver correct, eve correct) when both measure their clone in
+    /// the basis the state was prepared in
+    fn joint_probabilities(&self) -> [(bool, bool, f64); 4] {
+        let (s, c) = self.eta.sin_cos();
+        [
+            (true, true, (1.0 + s + c).powi(2) / 8.0),
+            (true, false, (1.0 + c - s).powi(2) / 8.0),
+            (false, true, (1.0 - c + s).powi(2) / 8.0),
+            (false, false, (1.0 - s - c).powi(2) / 8.0),
+        ]
+    }
+}
+
+impl Eavesdropper for PhaseCovariantCloner {
+    fn intercept(&mut self, state: Vector2<f64>, _photons: usize) -> Option<Vector2<f64>> {
+        // the clones are entangled, we sample both outcomes jointly and keep the orthogonal
+        // state where a clone would have given the wrong answer
+        let mut sample = rand::rng().random::<f64>();
+        let mut outcome = (true, true);
+        for (receiver, eve, probability) in self.joint_probabilities() {
+            outcome = (receiver, eve);
+            if sample < probability {
+                break;
+            }
+            sample -= probability;
+        }
+
+        let flip = |correct: bool| if correct { state } else { orthogonal(&state) };
+        self.held.push(Some(flip(outcome.1)));
+        Some(flip(outcome.0))
+    }
+
+    fn empty_pulse(&mut self) {
+        self.held.push(None);
+    }
+
+    fn guess(&self, index: usize, angle: f64) -> Option<Guess> {
+        self.held[index].map(|state| Guess {
+            bit: Guess::from_state(&state, angle).bit,
+            confidence: self.eve_fidelity(),
+        })
+    }
+}
+
+/// Weak coherent pulse source. The number of photons per pulse is poisson distributed
+pub struct PhotonSource {
+    pub mean_photon_number: f64,
+}
+
+impl PhotonSource {
+    pub fn new(mean_photon_number: f64) -> Self {
+        assert!(mean_photon_number >= 0.0);
+        Self { mean_photon_number }
+    }
+
+    pub fn sample_photon_number(&self) -> usize {
+        // Knuth's algorithm, fine for the small mean photon numbers used in qkd
+        let limit = (-self.mean_photon_number).exp();
+        let mut product = rand::rng().random::<f64>();
+        let mut photons = 0;
+        while product > limit {
+            product *= rand::rng().random::<f64>();
+            photons += 1;
+        }
+        photons
+    }
+}
+
+/// Photon number splitting attack on a multi photon source.
+/// Eve keeps one photon of every multi photon pulse and measures it once the basis is announced.
+/// Single photon pulses are blocked with probability `block_single` to hide the attack behind
+/// the expected channel loss, empty pulses never arrive at all.
+pub struct PhotonNumberSplitting {
+    block_single: f64,
+    held: Vec<Option<Vector2<f64>>>,
+}
+
+impl PhotonNumberSplitting {
+    pub fn new(block_single: f64) -> Self {
+        Self {
+            block_single,
+            held: vec![],
+        }
+    }
+}
+
+impl Eavesdropper for PhotonNumberSplitting {
+    fn intercept(&mut self, state: Vector2<f64>, photons: usize) -> Option<Vector2<f64>> {
+        match photons {
+            0 => {
+                self.empty_pulse();
+                None
+            }
+            1 => {
+                self.held.push(None);
+                if rand::rng().random_bool(self.block_single) {
+                    None
+                } else {
+                    Some(state)
+                }
+            }
+            _ => {
+                self.held.push(Some(state));
+                Some(state)
+            }
+        }
+    }
+
+    fn empty_pulse(&mut self) {
+        self.held.push(None);
+    }
+
+    fn guess(&self, index: usize, angle: f64) -> Option<Guess> {
+        self.held[index].map(|state| Guess::from_state(&state, angle))
+    }
+}
+
+/// Summary of an eavesdropped key exchange
+#[derive(Debug, Clone)]
+pub struct EavesdropReport {
+    pub sifted_bits: usize,
+    /// fraction of sifted bits where sender and receiver disagree
+    pub qber: f64,
+    /// fraction of sifted bits eve guessed right (random guess where she has none)
+    pub eve_agreement: f64,
+    /// eves information on the sifted key in bits per bit
+    pub eve_information: f64,
+}
+
+impl EavesdropReport {
+    /// `sifted` holds for every sifted bit its stream position and the basis angle used
+    pub fn new(
+        sender_key: &[bool],
+        receiver_key: &[bool],
+        sifted: &[(usize, f64)],
+        eve: &dyn Eavesdropper,
+    ) -> Self {
+        assert_eq!(sender_key.len(), receiver_key.len());
+        assert_eq!(sender_key.len(), sifted.len());
+        let n = sender_key.len();
+
+        let errors = sender_key
+            .iter()
+            .zip(receiver_key)
+            .filter(|(a, b)| a != b)
+            .count();
+
+        // group eves guesses by how sure she is and count how often she was right in each group
+        let mut agreements = 0.0;
+        let mut groups: BTreeMap<i64, (usize, usize)> = BTreeMap::new();
+        for (i, (index, angle)) in sifted.iter().enumerate() {
+            match eve.guess(*index, *angle) {
+                Some(guess) => {
+                    let group = groups
+                        .entry((guess.confidence * 1000.0).round() as i64)
+                        .or_insert((0, 0));
+                    group.0 += 1;
+                    if guess.bit == sender_key[i] {
+                        group.1 += 1;
+                        agreements += 1.0;
+                    }
+                }
+                None => agreements += 0.5,
+            }
+        }
+
+        let mut information = 0.0;
+        for (count, right) in groups.values() {
+            information += *count as f64 * (1.0 - binary_entropy(*right as f64 / *count as f64));
+        }
+
+        let n_f = n.max(1) as f64;
+        EavesdropReport {
+            sifted_bits: n,
+            qber: errors as f64 / n_f,
+            eve_agreement: agreements / n_f,
+            eve_information: information / n_f,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_breidbart_guess() {
+        // the breidbart basis sits between 0 and 45 degree so the same guess works for both bases
+        let mut eve = Breidbart::between(0.0, 45.0);
+        for _i in 0..100 {
+            eve.intercept(basis_vector(0.0), 1);
+        }
+        for i in 0..100 {
+            let guess_0 = eve.guess(i, 0.0).unwrap();
+            let guess_45 = eve.guess(i, 45.0).unwrap();
+            assert_eq!(guess_0.bit, guess_45.bit);
+            assert!((guess_0.confidence - (std::f64::consts::PI / 8.0).cos().powi(2)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_photon_source() {
+        let source = PhotonSource::new(0.5);
+        let reps = 10000;
+        let mut total = 0;
+        let mut empty = 0;
+        for _i in 0..reps {
+            let photons = source.sample_photon_number();
+            total += photons;
+            if photons == 0 {
+                empty += 1;
+            }
+        }
+        // mean 0.5, P(0) = e^-0.5
+        assert!((total as f64 / reps as f64 - 0.5).abs() < 0.05);
+        assert!((empty as f64 / reps as f64 - (-0.5f64).exp()).abs() < 0.03);
+    }
+}
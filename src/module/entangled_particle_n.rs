@@ -1,12 +1,16 @@
 use std::{result, usize};
 
 use super::utils::round_to_n_decimal_places;
+#[cfg(feature = "parallel")]
+use crate::module::state_vector::PARALLEL_THRESHOLD;
 use nalgebra::{storage::Storage, Dim, Scalar};
 use nalgebra::{
     ArrayStorage, Const, DMatrix, DimName, Matrix, Matrix2, SMatrix, SVector, Vector, Vector2,
     Vector4,
 };
 use rand::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
 use std::fmt;
 
 pub fn s2d<const N: usize>(matrix: SMatrix<f64, N, N>) -> DMatrix<f64> {
@@ -56,6 +60,8 @@ pub struct EntangledParticleN<const N: usize> {
     // v*w = r a_0*b_0 + s a_0*b_1 + t a_1*b_0 + u a_1*b_1
     pub basis: Basis,
     state: SVector<f64, N>,
+    #[cfg(feature = "parallel")]
+    parallel_threshold: usize,
 }
 
 impl<const N: usize> fmt::Debug for EntangledParticleN<N> {
@@ -75,11 +81,33 @@ impl<const N: usize> fmt::Debug for EntangledParticleN<N> {
 impl<const N: usize> EntangledParticleN<N> {
     pub fn change_state_by_matrix(&mut self, matrix: SMatrix<f64, N, N>) {
         assert_eq!(N, self.state.len());
+        #[cfg(feature = "parallel")]
+        if self.is_parallel() {
+            let rows: Vec<f64> = (0..N)
+                .into_par_iter()
+                .map(|row| matrix.row(row).transpose().dot(&self.state))
+                .collect();
+            self.state = SVector::from_column_slice(&rows);
+            self.check_params();
+            return;
+        }
         let res = matrix * self.state;
         self.state = res;
         self.check_params();
     }
 
+    /// use the parallel kernels from `particles` particles on, usize::MAX keeps everything serial
+    #[cfg(feature = "parallel")]
+    pub fn with_parallel_threshold(mut self, particles: usize) -> Self {
+        self.parallel_threshold = particles;
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    fn is_parallel(&self) -> bool {
+        self.get_no_particles() >= self.parallel_threshold
+    }
+
     pub fn get_no_particles(&self) -> usize {
         self.basis.bases_0.len()
     }
@@ -96,6 +124,8 @@ impl<const N: usize> EntangledParticleN<N> {
         let res = Self {
             basis: Basis::new((usize::BITS - 1 - N.leading_zeros()) as usize),
             state,
+            #[cfg(feature = "parallel")]
+            parallel_threshold: PARALLEL_THRESHOLD,
         };
         res.check_params();
         res
@@ -113,6 +143,92 @@ impl<const N: usize> EntangledParticleN<N> {
         true
     }
 
+    /// panic for particles the state does not have
+    fn check_index(&self, index: usize) {
+        let no_particles = self.get_no_particles();
+        assert!(
+            index < no_particles,
+            "particle {index} out of range, the state has {no_particles} particles"
+        );
+    }
+
+    /// amplitude pairs (particle `index` in 0, in 1) for every state of the other particles
+    fn particle_amplitudes(&self, index: usize) -> Vec<(usize, Vector2<f64>)> {
+        self.check_index(index);
+        let shift = self.get_no_particles() - 1 - index;
+        (0..N)
+            .filter(|i| (i >> shift) & 1 == 0)
+            .map(|i| (i, Vector2::new(self.state[i], self.state[i | (1 << shift)])))
+            .collect()
+    }
+
+    /// state of the particle at `index`, None if it is entangled with the other particles
+    pub fn particle_state(&self, index: usize) -> Option<Vector2<f64>> {
+        let amplitudes = self.particle_amplitudes(index);
+        let (_, largest) = amplitudes
+            .iter()
+            .max_by(|a, b| a.1.norm().total_cmp(&b.1.norm()))?;
+        let state = largest.normalize();
+
+        // every amplitude pair has to point in the same direction
+        for (_, pair) in &amplitudes {
+            let rest = pair - state * state.dot(pair);
+            if round_to_n_decimal_places(rest.norm(), 6) != 0.0 {
+                return None;
+            }
+        }
+        Some(state)
+    }
+
+    /// replace the state of a particle that is not entangled with the others
+    pub fn set_particle_state(&mut self, index: usize, new_state: Vector2<f64>) {
+        let old_state = self
+            .particle_state(index)
+            .expect("particle is entangled with the others");
+        let shift = self.get_no_particles() - 1 - index;
+        for (i, pair) in self.particle_amplitudes(index) {
+            let rest = old_state.dot(&pair);
+            self.state[i] = new_state[0] * rest;
+            self.state[i | (1 << shift)] = new_state[1] * rest;
+        }
+        self.check_params();
+    }
+
+    /// the real `matrix` on particle `index` for the basis states where all `controls` are |1>
+    pub fn apply_real_controlled(
+        &mut self,
+        matrix: &Matrix2<f64>,
+        controls: &[usize],
+        index: usize,
+    ) {
+        let no_particles = self.get_no_particles();
+        let control_mask = controls.iter().fold(0, |mask, control| {
+            mask | (1 << (no_particles - 1 - control))
+        });
+        let shift = no_particles - 1 - index;
+        assert_eq!(
+            control_mask & (1 << shift),
+            0,
+            "target can not be a control"
+        );
+        for (i, pair) in self.particle_amplitudes(index) {
+            if i & control_mask == control_mask {
+                let new_pair = matrix * pair;
+                self.state[i] = new_pair[0];
+                self.state[i | (1 << shift)] = new_pair[1];
+            }
+        }
+    }
+
+    /// probability to find particle `index` in |0> of the standard basis, without the mutable
+    /// borrow `Backend::probability_zero` takes
+    pub fn standard_probability_zero(&self, index: usize) -> f64 {
+        self.particle_amplitudes(index)
+            .iter()
+            .map(|(_, pair)| pair[0] * pair[0])
+            .sum()
+    }
+
     pub fn swap_basis(&mut self, angle: f64, index: usize) {
         let rotated_basis = self.get_basis().rotate_to_angle(angle, index);
 
@@ -122,53 +238,60 @@ impl<const N: usize> EntangledParticleN<N> {
         self.basis.bases_1[index] = alice_basis.1;
     }
 
-    pub fn measure(&mut self, index: usize) -> bool {
-        let n: usize = self.get_no_particles();
-
-        let mut p_matrices: Vec<(Matrix2<f64>, Matrix2<f64>)> = vec![];
-        // go through all particles
-        for i in 0..n {
-            // case when the particle is the one we wabt to measure
-            if i == index {
-                let prtcl_to_measure_0 = self.basis.bases_0[i] * self.basis.bases_0[i].transpose();
-                let prtcl_to_measure_1 = self.basis.bases_1[i] * self.basis.bases_1[i].transpose();
-                p_matrices.push((prtcl_to_measure_0, prtcl_to_measure_1));
-            }
-            // if its not the particle we want to measure we use identiy matrix
-            else {
-                p_matrices.push((Matrix2::identity(), Matrix2::identity()));
-            }
-        }
-
-        let mut kronecker = s2d(p_matrices[0].0);
+    /// measure particle `index` in the basis `swap_basis` picked for it, true for its first
+    /// vector. `Backend::measure` always measures in the standard basis
+    pub fn measure_in_basis(&mut self, index: usize) -> bool {
+        self.check_index(index);
+        let (basis_0, basis_1) = self.basis.get_basis(index);
+        self.measure_in(index, &basis_0, &basis_1)
+    }
 
-        for i in 1..self.get_no_particles() {
-            kronecker = kronecker.kronecker(&p_matrices[i].0);
-        }
-        let mut new_state = kronecker * self.state;
-        let probability = new_state.norm_squared();
+    /// measure particle `index` in the basis (`basis_0`, `basis_1`), true for `basis_0`
+    pub(crate) fn measure_in(
+        &mut self,
+        index: usize,
+        basis_0: &Vector2<f64>,
+        basis_1: &Vector2<f64>,
+    ) -> bool {
+        let new_state = self.project(index, basis_0);
+        let probability = self.squared_norm(&new_state);
 
         //sample
         let obs = rand::rng().random_bool(round_to_n_decimal_places(probability, 5));
-        let mut norm = probability.sqrt();
-
-        if !obs {
-            norm = (1.0 - probability).sqrt();
+        let (new_state, probability) = if obs {
+            (new_state, probability)
+        } else {
+            (self.project(index, basis_1), 1.0 - probability)
+        };
 
-            let mut kronecker_1 = s2d(p_matrices[0].1);
+        self.state = new_state / probability.sqrt();
 
-            for i in 1..self.get_no_particles() {
-                kronecker_1 = kronecker_1.kronecker(&p_matrices[i].1);
-            }
+        obs
+    }
 
-            new_state = kronecker_1 * self.state;
+    /// the state with particle `index` projected onto `direction`, not normalized
+    fn project(&self, index: usize, direction: &Vector2<f64>) -> SVector<f64, N> {
+        let shift = self.get_no_particles() - 1 - index;
+        let mut projected = SVector::<f64, N>::zeros();
+        for (i, pair) in self.particle_amplitudes(index) {
+            let overlap = direction.dot(&pair);
+            projected[i] = direction[0] * overlap;
+            projected[i | (1 << shift)] = direction[1] * overlap;
         }
+        projected
+    }
 
-        let post_state = new_state / norm;
-
-        self.state = SMatrix::from_column_slice(post_state.as_slice());
-
-        obs
+    /// squared norm of a state, split over threads for large states
+    fn squared_norm(&self, state: &SVector<f64, N>) -> f64 {
+        #[cfg(feature = "parallel")]
+        if self.is_parallel() {
+            return state
+                .as_slice()
+                .par_iter()
+                .map(|amplitude| amplitude * amplitude)
+                .sum();
+        }
+        state.norm_squared()
     }
 }
 
@@ -202,8 +325,8 @@ mod tests {
             prtcl.swap_basis(0.0, 0);
             prtcl.swap_basis(0.0, 1);
 
-            let measurement_1 = prtcl.measure(0);
-            let measurement_2 = prtcl.measure(1);
+            let measurement_1 = prtcl.measure_in_basis(0);
+            let measurement_2 = prtcl.measure_in_basis(1);
             assert_eq!(measurement_1, measurement_2);
         }
 
@@ -217,8 +340,8 @@ mod tests {
             prtcl.swap_basis(0.0, 0);
             prtcl.swap_basis(0.0, 1);
 
-            let measurement_1 = prtcl.measure(0);
-            let measurement_2 = prtcl.measure(1);
+            let measurement_1 = prtcl.measure_in_basis(0);
+            let measurement_2 = prtcl.measure_in_basis(1);
             assert_eq!(measurement_1, measurement_2);
         }
     }
@@ -239,8 +362,8 @@ mod tests {
             prtcl.swap_basis(0.0, 0);
             prtcl.swap_basis(90.0 * (2.0 / 3.0), 1);
 
-            let alice: bool = prtcl.measure(0);
-            let bob: bool = prtcl.measure(1);
+            let alice: bool = prtcl.measure_in_basis(0);
+            let bob: bool = prtcl.measure_in_basis(1);
 
             if alice {
                 alice_count += 1;
@@ -260,4 +383,30 @@ mod tests {
         dbg!("agreements % {}", agreements as f64 / reps as f64);
         dbg!("expected 1/4");
     }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        use crate::module::gates::basic::SingleInputGate;
+
+        let hadamard = SingleInputGate::Hadamard.get_matrix::<16>(0);
+        let x = SingleInputGate::X.get_matrix::<16>(2);
+        let mut state = SVector::<f64, 16>::zeros();
+        state[0] = 1.0;
+        let mut parallel = EntangledParticleN::new(state).with_parallel_threshold(0);
+        let mut serial = EntangledParticleN::new(state).with_parallel_threshold(usize::MAX);
+        for prtcl in [&mut parallel, &mut serial] {
+            prtcl.change_state_by_matrix(hadamard);
+            prtcl.change_state_by_matrix(x);
+        }
+        assert_eq!(parallel.get_params(), serial.get_params());
+        assert!(parallel.particle_state(0).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "particle 3 out of range")]
+    fn test_measure_out_of_range() {
+        let mut prtcl = EntangledParticleN::new(SVector::<f64, 4>::new(1.0, 0.0, 0.0, 0.0));
+        prtcl.measure_in_basis(3);
+    }
 }
@@ -0,0 +1,364 @@
+// Quantum error correction with the 3 qubit bit-flip, 3 qubit phase-flip, 9 qubit shor and 7 qubit
+// steane codes
+//
+// The logical qubit starts on data qubit 0 and is spread over the data qubits by the encoding
+// circuit. Each stabilizer is measured with an ancilla behind the data qubits: z type checks
+// copy the parity of their qubits onto the ancilla with cnots, x type checks do the same in the
+// hadamard basis. The ancilla is reset after every check, so one is enough. The syndrome is
+// looked up in a table of single qubit errors, the correction applied and the encoding undone.
+//
+// The circuits run on any Backend. The stabilizer tableau needs a clifford logical state such as
+// |0> or |1>, the entangled particles a real one, and the shor code with its ancilla needs
+// EntangledParticleN<1024>, which is about as large as that fixed size vector gets.
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::noise::NoiseChannel;
+use crate::module::state_vector::{qubit_fidelity, StateVector, C64};
+use nalgebra::{Matrix2, Vector2};
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Code {
+    BitFlip,
+    PhaseFlip,
+    Shor,
+    /// [[7,1,3]] css code built from the classical [7,4] hamming code
+    Steane,
+}
+
+/// supports of the parity checks of the hamming code, qubit i is in check j if bit j of i + 1 is set
+const HAMMING_CHECKS: [[usize; 4]; 3] = [[0, 2, 4, 6], [1, 2, 5, 6], [3, 4, 5, 6]];
+
+/// a product of x or z operators on some data qubits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Stabilizer {
+    Z(Vec<usize>),
+    X(Vec<usize>),
+}
+
+impl Code {
+    pub fn no_data_qubits(&self) -> usize {
+        match self {
+            Code::BitFlip | Code::PhaseFlip => 3,
+            Code::Shor => 9,
+            Code::Steane => 7,
+        }
+    }
+
+    /// index of the ancilla used for the syndrome extraction
+    pub fn ancilla(&self) -> usize {
+        self.no_data_qubits()
+    }
+
+    pub fn stabilizers(&self) -> Vec<Stabilizer> {
+        match self {
+            Code::BitFlip => vec![Stabilizer::Z(vec![0, 1]), Stabilizer::Z(vec![1, 2])],
+            Code::PhaseFlip => vec![Stabilizer::X(vec![0, 1]), Stabilizer::X(vec![1, 2])],
+            Code::Shor => {
+                let mut stabilizers = vec![];
+                for block in [0, 3, 6] {
+                    stabilizers.push(Stabilizer::Z(vec![block, block + 1]));
+                    stabilizers.push(Stabilizer::Z(vec![block + 1, block + 2]));
+                }
+                stabilizers.push(Stabilizer::X((0..6).collect()));
+                stabilizers.push(Stabilizer::X((3..9).collect()));
+                stabilizers
+            }
+            Code::Steane => HAMMING_CHECKS
+                .iter()
+                .map(|check| Stabilizer::Z(check.to_vec()))
+                .chain(
+                    HAMMING_CHECKS
+                        .iter()
+                        .map(|check| Stabilizer::X(check.to_vec())),
+                )
+                .collect(),
+        }
+    }
+
+    /// the encoding circuit, hadamards are (None, qubit) and cnots (Some(control), target)
+    fn encoding_circuit(&self) -> Vec<(Option<usize>, usize)> {
+        match self {
+            Code::BitFlip => vec![(Some(0), 1), (Some(0), 2)],
+            Code::PhaseFlip => vec![(Some(0), 1), (Some(0), 2), (None, 0), (None, 1), (None, 2)],
+            Code::Shor => {
+                let mut circuit = vec![(Some(0), 3), (Some(0), 6), (None, 0), (None, 3), (None, 6)];
+                for block in [0, 3, 6] {
+                    circuit.push((Some(block), block + 1));
+                    circuit.push((Some(block), block + 2));
+                }
+                circuit
+            }
+            Code::Steane => {
+                // logical x is x on the hamming codeword {0, 1, 2}, copy the input there. Then
+                // every x stabilizer is added in superposition: a hadamard on a pivot qubit
+                // (4, 5, 3) and cnots from it onto the rest of the check
+                let mut circuit = vec![(Some(0), 1), (Some(0), 2), (None, 4), (None, 5), (None, 3)];
+                for (check, pivot) in HAMMING_CHECKS.iter().zip([4, 5, 3]) {
+                    for qubit in check {
+                        if *qubit != pivot {
+                            circuit.push((Some(pivot), *qubit));
+                        }
+                    }
+                }
+                circuit
+            }
+        }
+    }
+
+    fn run<B: Backend>(state: &mut B, circuit: impl Iterator<Item = (Option<usize>, usize)>) {
+        for (control, target) in circuit {
+            match control {
+                Some(control) => state.cnot(control, target),
+                None => state.apply_gate(SingleInputGate::Hadamard, target),
+            }
+        }
+    }
+
+    /// data qubits and the ancilla, all |0> but data qubit 0 which holds `logical`
+    pub fn prepare(&self, logical: Vector2<C64>) -> StateVector {
+        let zero = Vector2::new(C64::new(1.0, 0.0), C64::new(0.0, 0.0));
+        let mut qubits = vec![zero; self.no_data_qubits() + 1];
+        qubits[0] = logical;
+        StateVector::product(&qubits)
+    }
+
+    /// `prepare` on any backend, data qubit 0 is rotated from |0> to `logical`
+    pub fn prepare_on<B: Backend>(&self, logical: Vector2<C64>) -> B {
+        let mut state = B::allocate(self.no_data_qubits() + 1);
+        state.apply(&preparation(logical), 0);
+        state
+    }
+
+    pub fn encode<B: Backend>(&self, state: &mut B) {
+        Self::run(state, self.encoding_circuit().into_iter());
+    }
+
+    /// undo the encoding, the logical qubit is back on qubit 0
+    pub fn decode<B: Backend>(&self, state: &mut B) {
+        // all gates of the encoding are their own inverse
+        Self::run(state, self.encoding_circuit().into_iter().rev());
+    }
+
+    /// measure every stabilizer with the ancilla, true where the stabilizer is -1
+    pub fn extract_syndrome<B: Backend>(&self, state: &mut B) -> Vec<bool> {
+        let ancilla = self.ancilla();
+        self.stabilizers()
+            .iter()
+            .map(|stabilizer| {
+                let minus = match stabilizer {
+                    Stabilizer::Z(qubits) => {
+                        for qubit in qubits {
+                            state.cnot(*qubit, ancilla);
+                        }
+                        !state.measure(ancilla)
+                    }
+                    Stabilizer::X(qubits) => {
+                        state.apply_gate(SingleInputGate::Hadamard, ancilla);
+                        for qubit in qubits {
+                            state.cnot(ancilla, *qubit);
+                        }
+                        state.apply_gate(SingleInputGate::Hadamard, ancilla);
+                        !state.measure(ancilla)
+                    }
+                };
+                // reset the ancilla for the next check
+                if minus {
+                    state.apply_gate(SingleInputGate::X, ancilla);
+                }
+                minus
+            })
+            .collect()
+    }
+
+    /// syndrome of a pauli error on one data qubit
+    fn syndrome_of(&self, error: SingleInputGate, qubit: usize) -> Vec<bool> {
+        let (flips_bit, flips_phase) = match error {
+            SingleInputGate::X => (true, false),
+            SingleInputGate::Z => (false, true),
+            SingleInputGate::Y => (true, true),
+            SingleInputGate::Hadamard => panic!("not a pauli error"),
+        };
+        self.stabilizers()
+            .iter()
+            .map(|stabilizer| match stabilizer {
+                // z checks see bit flips, x checks see phase flips
+                Stabilizer::Z(qubits) => flips_bit && qubits.contains(&qubit),
+                Stabilizer::X(qubits) => flips_phase && qubits.contains(&qubit),
+            })
+            .collect()
+    }
+
+    /// lookup table from syndrome to the single qubit error that causes it
+    pub fn decoding_table(&self) -> HashMap<Vec<bool>, (SingleInputGate, usize)> {
+        let mut table = HashMap::new();
+        for error in [SingleInputGate::X, SingleInputGate::Z, SingleInputGate::Y] {
+            for qubit in 0..self.no_data_qubits() {
+                let syndrome = self.syndrome_of(error, qubit);
+                if syndrome.contains(&true) {
+                    // for shor several z errors are the same, the first one is as good as any
+                    table.entry(syndrome).or_insert((error, qubit));
+                }
+            }
+        }
+        table
+    }
+
+    pub fn correct<B: Backend>(&self, state: &mut B, syndrome: &[bool]) {
+        if let Some((error, qubit)) = self.decoding_table().get(syndrome) {
+            state.apply_gate(*error, *qubit);
+        }
+    }
+
+    /// encode `logical`, let `error` hit the data qubits, correct and decode. Returns the fidelity
+    /// of the decoded qubit with `logical`
+    pub fn protect(&self, logical: Vector2<C64>, error: impl FnOnce(&mut StateVector)) -> f64 {
+        self.protect_on(logical, error)
+    }
+
+    /// `protect` on any backend. The fidelity is the probability of |0> after the preparation
+    /// of `logical` is undone, which needs no access to the amplitudes
+    pub fn protect_on<B: Backend>(&self, logical: Vector2<C64>, error: impl FnOnce(&mut B)) -> f64 {
+        let mut state: B = self.prepare_on(logical);
+        self.encode(&mut state);
+        error(&mut state);
+        let syndrome = self.extract_syndrome(&mut state);
+        self.correct(&mut state, &syndrome);
+        self.decode(&mut state);
+        state.apply(&preparation(logical).adjoint(), 0);
+        state.probability_zero(0)
+    }
+
+    /// fraction of runs where `noise` on every data qubit leads to a wrong logical qubit
+    pub fn logical_error_rate(&self, noise: NoiseChannel, runs: usize) -> f64 {
+        self.logical_error_rate_on::<StateVector>(noise, runs)
+    }
+
+    /// `logical_error_rate` on any backend that holds the real test qubit
+    pub fn logical_error_rate_on<B: Backend>(&self, noise: NoiseChannel, runs: usize) -> f64 {
+        let failures = (0..runs)
+            .filter(|_| {
+                let fidelity = self.protect_on(test_qubit(), |state: &mut B| {
+                    for qubit in 0..self.no_data_qubits() {
+                        noise.apply(state, qubit);
+                    }
+                });
+                fidelity < 1.0 - 1e-6
+            })
+            .count();
+        failures as f64 / runs as f64
+    }
+}
+
+/// fraction of runs where `noise` changes an unprotected qubit
+pub fn physical_error_rate(noise: NoiseChannel, runs: usize) -> f64 {
+    let failures = (0..runs)
+        .filter(|_| {
+            let mut state = StateVector::product(&[test_qubit()]);
+            noise.apply(&mut state, 0);
+            qubit_fidelity(&test_qubit(), &state.qubit_state(0).unwrap()) < 1.0 - 1e-6
+        })
+        .count();
+    failures as f64 / runs as f64
+}
+
+/// unitary that takes |0> to `logical`
+fn preparation(logical: Vector2<C64>) -> Matrix2<C64> {
+    Matrix2::new(
+        logical[0],
+        -logical[1].conj(),
+        logical[1],
+        logical[0].conj(),
+    )
+}
+
+/// a state that is changed by every pauli error, so all errors count. It is real so the
+/// entangled particles can hold it too
+fn test_qubit() -> Vector2<C64> {
+    Vector2::new(C64::new(0.8f64.sqrt(), 0.0), C64::new(0.2f64.sqrt(), 0.0))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::entangled_particle_n::EntangledParticleN;
+    use crate::module::mps::Mps;
+    use crate::module::sparse_state::SparseState;
+    use crate::module::stabilizer::StabilizerState;
+    use crate::module::state_vector::random_qubit;
+
+    fn check_single_errors<B: Backend>(code: Code, logical: Vector2<C64>) {
+        for error in [SingleInputGate::X, SingleInputGate::Y, SingleInputGate::Z] {
+            for qubit in 0..code.no_data_qubits() {
+                let fidelity =
+                    code.protect_on(logical, |state: &mut B| state.apply_gate(error, qubit));
+                assert!((fidelity - 1.0).abs() < 1e-9, "{code:?} {error:?} {qubit}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_errors_are_corrected() {
+        for code in [Code::BitFlip, Code::PhaseFlip, Code::Shor, Code::Steane] {
+            let correctable = match code {
+                Code::BitFlip => vec![SingleInputGate::X],
+                Code::PhaseFlip => vec![SingleInputGate::Z],
+                Code::Shor | Code::Steane => {
+                    vec![SingleInputGate::X, SingleInputGate::Y, SingleInputGate::Z]
+                }
+            };
+            for error in correctable {
+                for qubit in 0..code.no_data_qubits() {
+                    let logical = random_qubit();
+                    let fidelity = code.protect(logical, |state| state.apply_gate(error, qubit));
+                    assert!((fidelity - 1.0).abs() < 1e-9, "{code:?} {error:?} {qubit}");
+                }
+            }
+        }
+
+        // the bit flip code does not see phase flips
+        let fidelity = Code::BitFlip.protect(test_qubit(), |state| {
+            state.apply_gate(SingleInputGate::Z, 1)
+        });
+        assert!(fidelity < 0.9);
+    }
+
+    #[test]
+    fn test_logical_error_rate() {
+        let p: f64 = 0.1;
+        let physical = physical_error_rate(NoiseChannel::BitFlip(p), 2000);
+        assert!((physical - p).abs() < 0.03);
+
+        // two or three flips out of three break the code
+        let expected = 3.0 * p.powi(2) - 2.0 * p.powi(3);
+        let logical = Code::BitFlip.logical_error_rate(NoiseChannel::BitFlip(p), 2000);
+        assert!((logical - expected).abs() < 0.015);
+        let logical = Code::PhaseFlip.logical_error_rate(NoiseChannel::PhaseFlip(p), 2000);
+        assert!((logical - expected).abs() < 0.015);
+
+        let noise = NoiseChannel::Depolarizing(0.02);
+        let physical = physical_error_rate(noise, 4000);
+        assert!(Code::Shor.logical_error_rate(noise, 400) < physical);
+        assert!(Code::Steane.logical_error_rate(noise, 400) < physical);
+    }
+
+    #[test]
+    fn test_other_backends() {
+        let one = Vector2::new(C64::new(0.0, 0.0), C64::new(1.0, 0.0));
+        for code in [Code::Shor, Code::Steane] {
+            check_single_errors::<SparseState>(code, test_qubit());
+            check_single_errors::<Mps>(code, test_qubit());
+            // the tableau only holds clifford states
+            check_single_errors::<StabilizerState>(code, one);
+        }
+        check_single_errors::<EntangledParticleN<256>>(Code::Steane, test_qubit());
+        check_single_errors::<EntangledParticleN<1024>>(Code::Shor, test_qubit());
+
+        let p: f64 = 0.1;
+        let expected = 3.0 * p.powi(2) - 2.0 * p.powi(3);
+        let logical =
+            Code::BitFlip.logical_error_rate_on::<SparseState>(NoiseChannel::BitFlip(p), 2000);
+        assert!((logical - expected).abs() < 0.015);
+    }
+}
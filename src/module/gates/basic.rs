@@ -13,6 +13,7 @@ pub fn log(N: usize) -> usize {
     assert!(N.is_power_of_two(), "Input must be a power of 2");
     N.trailing_zeros() as usize
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum SingleInputGate {
     X,
     Z,
@@ -20,6 +21,7 @@ pub enum SingleInputGate {
     Hadamard,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum TwoInputGate {
     CNot,
 }
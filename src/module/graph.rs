@@ -0,0 +1,100 @@
+// Undirected weighted graphs
+//
+// Shared by the algorithms that work on graphs: QAOA cuts them, quantum walks move along their
+// edges. Vertices are numbered 0..n, edges keep the order they were added in.
+use nalgebra::DMatrix;
+
+/// undirected weighted graph
+#[derive(Clone, Debug, PartialEq)]
+pub struct Graph {
+    no_vertices: usize,
+    edges: Vec<(usize, usize, f64)>,
+}
+
+impl Graph {
+    pub fn new(no_vertices: usize) -> Self {
+        Self {
+            no_vertices,
+            edges: vec![],
+        }
+    }
+
+    /// cycle with unit weights
+    pub fn ring(no_vertices: usize) -> Self {
+        let mut graph = Self::new(no_vertices);
+        for i in 0..no_vertices {
+            graph.add_edge(i, (i + 1) % no_vertices, 1.0);
+        }
+        graph
+    }
+
+    /// path with unit weights
+    pub fn line(no_vertices: usize) -> Self {
+        let mut graph = Self::new(no_vertices);
+        for i in 1..no_vertices {
+            graph.add_edge(i - 1, i, 1.0);
+        }
+        graph
+    }
+
+    pub fn add_edge(&mut self, a: usize, b: usize, weight: f64) {
+        assert!(a < self.no_vertices && b < self.no_vertices && a != b);
+        self.edges.push((a, b, weight));
+    }
+
+    pub fn no_vertices(&self) -> usize {
+        self.no_vertices
+    }
+
+    pub fn edges(&self) -> &[(usize, usize, f64)] {
+        &self.edges
+    }
+
+    /// the vertices joined to `vertex`, in the order the edges were added
+    pub fn neighbours(&self, vertex: usize) -> Vec<usize> {
+        self.edges
+            .iter()
+            .filter_map(|(a, b, _)| match (*a == vertex, *b == vertex) {
+                (true, _) => Some(*b),
+                (_, true) => Some(*a),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// symmetric matrix of the edge weights
+    pub fn adjacency_matrix(&self) -> DMatrix<f64> {
+        let mut matrix = DMatrix::zeros(self.no_vertices, self.no_vertices);
+        for (a, b, weight) in &self.edges {
+            matrix[(*a, *b)] += weight;
+            matrix[(*b, *a)] += weight;
+        }
+        matrix
+    }
+
+    /// total weight of the edges between the two sides
+    pub fn cut_value(&self, side: &[bool]) -> f64 {
+        self.edges
+            .iter()
+            .filter(|(a, b, _)| side[*a] != side[*b])
+            .map(|(_, _, weight)| weight)
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_graph() {
+        let graph = Graph::line(4);
+        assert_eq!(graph.neighbours(1), [0, 2]);
+        assert_eq!(graph.neighbours(3), [2]);
+        let adjacency = graph.adjacency_matrix();
+        assert_eq!(adjacency, adjacency.transpose());
+        assert_eq!(adjacency.sum(), 6.0);
+        assert_eq!(Graph::ring(4).cut_value(&[true, false, true, false]), 4.0);
+    }
+}
@@ -0,0 +1,160 @@
+// Time evolution under pauli sum hamiltonians with Trotter-Suzuki product formulas
+//
+// For H = sum_k c_k P_k the terms do not commute in general, but for a short step dt
+//
+//     first order:  e^(-iH dt) ~ prod_k e^(-i c_k P_k dt)                           error O(dt^2)
+//     second order: e^(-iH dt) ~ prod_k e^(-i c_k P_k dt / 2) prod_k' e^(..) reversed  O(dt^3)
+//
+// so t / dt steps approximate e^(-iHt) with a total error of O(t dt) and O(t dt^2). Each factor is
+// a circuit: the qubits of P are rotated so that P becomes a product of zs, cnots collect the
+// parity on the last qubit, rz(2 c dt) rotates it and everything is undone again.
+//
+// The exact evolution exponentiates the matrix of H for comparison.
+use crate::module::circuit::Circuit;
+use crate::module::pauli::{Pauli, PauliString, PauliSum};
+use crate::module::state_vector::{evolution_operator, StateVector};
+use std::f64::consts::FRAC_PI_2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrotterOrder {
+    First,
+    Second,
+}
+
+/// append e^(-i angle P) to `circuit`, the identity is only a global phase
+pub fn pauli_exponential(circuit: &mut Circuit, string: &PauliString, angle: f64) {
+    let ops = string.ops();
+    let Some((last, _)) = ops.last() else {
+        return;
+    };
+    for (qubit, pauli) in ops {
+        match pauli {
+            Pauli::X => {
+                circuit.h(*qubit);
+            }
+            Pauli::Y => {
+                circuit.phase(-FRAC_PI_2, *qubit).h(*qubit);
+            }
+            Pauli::I | Pauli::Z => {}
+        }
+    }
+    for pair in ops.windows(2) {
+        circuit.cnot(pair[0].0, pair[1].0);
+    }
+    circuit.rz(2.0 * angle, *last);
+    for pair in ops.windows(2).rev() {
+        circuit.cnot(pair[0].0, pair[1].0);
+    }
+    for (qubit, pauli) in ops {
+        match pauli {
+            Pauli::X => {
+                circuit.h(*qubit);
+            }
+            Pauli::Y => {
+                circuit.h(*qubit).phase(FRAC_PI_2, *qubit);
+            }
+            Pauli::I | Pauli::Z => {}
+        }
+    }
+}
+
+/// `steps` steps of the product formula for e^(-i hamiltonian time)
+pub fn trotter_circuit(
+    hamiltonian: &PauliSum,
+    no_qubits: usize,
+    time: f64,
+    steps: usize,
+    order: TrotterOrder,
+) -> Circuit {
+    assert!(hamiltonian.min_qubits() <= no_qubits);
+    assert!(steps > 0);
+    let dt = time / steps as f64;
+    let mut circuit = Circuit::new(no_qubits);
+    for _ in 0..steps {
+        match order {
+            TrotterOrder::First => {
+                for (coefficient, string) in hamiltonian.terms() {
+                    pauli_exponential(&mut circuit, string, coefficient * dt);
+                }
+            }
+            TrotterOrder::Second => {
+                for (coefficient, string) in hamiltonian.terms() {
+                    pauli_exponential(&mut circuit, string, coefficient * dt / 2.0);
+                }
+                for (coefficient, string) in hamiltonian.terms().iter().rev() {
+                    pauli_exponential(&mut circuit, string, coefficient * dt / 2.0);
+                }
+            }
+        }
+    }
+    circuit
+}
+
+/// e^(-i hamiltonian time) |state> from the matrix exponential
+pub fn exact_evolution(hamiltonian: &PauliSum, state: &StateVector, time: f64) -> StateVector {
+    let evolution = evolution_operator(&hamiltonian.to_matrix(state.no_qubits()), time);
+    StateVector::from_amplitudes((evolution * state.amplitudes()).as_slice().to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use std::collections::HashMap;
+
+    fn random_state(no_qubits: usize) -> StateVector {
+        let mut circuit = Circuit::new(no_qubits);
+        for qubit in 0..no_qubits {
+            circuit
+                .ry(0.3 + qubit as f64, qubit)
+                .rz(1.1 * qubit as f64, qubit);
+        }
+        for qubit in 1..no_qubits {
+            circuit.cnot(qubit - 1, qubit);
+        }
+        circuit.state(&HashMap::new())
+    }
+
+    fn evolve(circuit: &Circuit, state: &StateVector) -> StateVector {
+        let mut state = state.clone();
+        circuit.apply(&mut state, &HashMap::new());
+        state
+    }
+
+    #[test]
+    fn test_pauli_exponential() {
+        let state = random_state(4);
+        for string in ["X0Y1Z3", "Y2", "Z0Z1", "X1Y2Y3"] {
+            let string: PauliString = string.parse().unwrap();
+            let mut circuit = Circuit::new(4);
+            pauli_exponential(&mut circuit, &string, 0.37);
+            let expected = exact_evolution(&(0.37 * string), &state, 1.0);
+            assert!((evolve(&circuit, &state).fidelity(&expected) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_trotter_error() {
+        let hamiltonian: PauliSum = "Z0Z1 + 0.5*X0 + 0.7*Y1 - 0.4*X1X2 + 0.3*Z2"
+            .parse()
+            .unwrap();
+        let state = random_state(3);
+        let exact = exact_evolution(&hamiltonian, &state, 1.0);
+        let error = |steps, order| {
+            let circuit = trotter_circuit(&hamiltonian, 3, 1.0, steps, order);
+            1.0 - evolve(&circuit, &state).fidelity(&exact)
+        };
+        // the infidelity goes with the square of the error
+        let ratio = error(10, TrotterOrder::First) / error(20, TrotterOrder::First);
+        assert!((ratio - 4.0).abs() < 0.5, "{ratio}");
+        let ratio = error(10, TrotterOrder::Second) / error(20, TrotterOrder::Second);
+        assert!((ratio - 16.0).abs() < 2.0, "{ratio}");
+        assert!(error(10, TrotterOrder::Second) < error(10, TrotterOrder::First) / 10.0);
+
+        // commuting terms are exact in one step
+        let commuting: PauliSum = "Z0Z1 + 0.5*Z1 - 0.3*Z0Z2".parse().unwrap();
+        let circuit = trotter_circuit(&commuting, 3, 2.0, 1, TrotterOrder::First);
+        let exact = exact_evolution(&commuting, &state, 2.0);
+        assert!((evolve(&circuit, &state).fidelity(&exact) - 1.0).abs() < 1e-12);
+    }
+}
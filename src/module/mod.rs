@@ -1,5 +1,28 @@
+pub mod amplitude_estimation;
 pub mod applications;
+pub mod arithmetic;
+pub mod backend;
+pub mod circuit;
+pub mod eavesdropper;
 pub mod entangled_particle_n;
+pub mod error_correction;
 pub mod gates;
+pub mod graph;
+pub mod hamiltonian_simulation;
+pub mod mps;
+pub mod noise;
+pub mod optimizers;
+pub mod oracle;
+pub mod pauli;
+pub mod phase_estimation;
+pub mod qaoa;
+pub mod quantum_channel;
+pub mod quantum_walk;
 pub mod simple_example_bb_84;
+pub mod sparse_state;
+pub mod stabilizer;
+pub mod state_vector;
+pub mod surface_code;
+pub mod union_find_decoder;
 pub mod utils;
+pub mod vqe;
@@ -0,0 +1,458 @@
+// Matrix product state simulator
+//
+// The amplitude of |s0 s1 .. s(n-1)> is the matrix product A0[s0] A1[s1] .. A(n-1)[s(n-1)], the
+// first matrix is a row and the last a column. The sizes of the matrices in between, the bond
+// dimensions, grow with the entanglement between the left and the right part of the chain, so
+// circuits with little entanglement can be simulated for many qubits.
+//
+// A two qubit gate on neighbours contracts their matrices, applies the gate and splits them
+// again with a singular value decomposition. Small singular values are dropped (at most
+// `max_bond` are kept, and the dropped weight stays below `cutoff`), the dropped weight is added
+// up as truncation error. The state is kept in canonical form around one site, the center, so
+// the singular values are the real schmidt coefficients of the cut.
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::state_vector::{complex_matrix, C64};
+use nalgebra::{DMatrix, Matrix2, Matrix4};
+use rand::prelude::*;
+
+/// two qubit gate with `matrix` on the second qubit if the first one is |1>
+pub fn controlled(matrix: &Matrix2<C64>) -> Matrix4<C64> {
+    let mut gate = Matrix4::identity();
+    gate.fixed_view_mut::<2, 2>(2, 2).copy_from(matrix);
+    gate
+}
+
+pub fn swap_matrix() -> Matrix4<C64> {
+    let one = C64::new(1.0, 0.0);
+    let mut gate = Matrix4::zeros();
+    gate[(0, 0)] = one;
+    gate[(1, 2)] = one;
+    gate[(2, 1)] = one;
+    gate[(3, 3)] = one;
+    gate
+}
+
+/// relative weight below which a singular value is rounding noise
+const ZERO_WEIGHT: f64 = 1e-24;
+
+/// singular value decomposition a = u diag(s) v^dagger with the singular values sorted from large
+/// to small, returns (u, s, v^dagger). One sided jacobi: the columns are rotated pairwise until
+/// they are orthogonal, then their norms are the singular values. nalgebra's svd is not used as it
+/// can return wrong factors for rank deficient matrices, which are the normal case here
+fn svd(matrix: &DMatrix<C64>) -> (DMatrix<C64>, Vec<f64>, DMatrix<C64>) {
+    if matrix.nrows() < matrix.ncols() {
+        let (u, singular, v_t) = svd(&matrix.adjoint());
+        return (v_t.adjoint(), singular, u.adjoint());
+    }
+    let mut a = matrix.clone();
+    let n = a.ncols();
+    let mut v = DMatrix::<C64>::identity(n, n);
+    for _sweep in 0..60 {
+        let mut rotated = false;
+        for p in 0..n {
+            for q in p + 1..n {
+                let alpha = a.column(p).norm_squared();
+                let beta = a.column(q).norm_squared();
+                let gamma = a.column(p).dotc(&a.column(q));
+                if gamma.norm() <= f64::EPSILON * (alpha * beta).sqrt() || gamma.norm() == 0.0 {
+                    continue;
+                }
+                rotated = true;
+                let zeta = (beta - alpha) / (2.0 * gamma.norm());
+                let t = zeta.signum() / (zeta.abs() + (1.0 + zeta * zeta).sqrt());
+                let c = 1.0 / (1.0 + t * t).sqrt();
+                let s = C64::new(c * t, 0.0);
+                let c = C64::new(c, 0.0);
+                // rotate the phase of column q away first, then a real rotation
+                let phase = (gamma / gamma.norm()).conj();
+                for m in [&mut a, &mut v] {
+                    let (column_p, column_q) = (m.column(p).into_owned(), m.column(q) * phase);
+                    m.set_column(p, &(&column_p * c - &column_q * s));
+                    m.set_column(q, &(column_p * s + column_q * c));
+                }
+            }
+        }
+        if !rotated {
+            break;
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    let norms: Vec<f64> = (0..n).map(|column| a.column(column).norm()).collect();
+    order.sort_by(|x, y| norms[*y].total_cmp(&norms[*x]));
+    let mut u = DMatrix::zeros(a.nrows(), n);
+    let mut v_sorted = DMatrix::zeros(n, n);
+    for (column, index) in order.iter().enumerate() {
+        if norms[*index] > 0.0 {
+            u.set_column(column, &(a.column(*index) / C64::new(norms[*index], 0.0)));
+        }
+        v_sorted.set_column(column, &v.column(*index));
+    }
+    let singular = order.iter().map(|index| norms[*index]).collect();
+    (u, singular, v_sorted.adjoint())
+}
+
+#[derive(Clone, Debug)]
+pub struct Mps {
+    // one matrix per value of the qubit
+    tensors: Vec<[DMatrix<C64>; 2]>,
+    center: usize,
+    max_bond: usize,
+    cutoff: f64,
+    truncation_error: f64,
+}
+
+impl Mps {
+    /// all qubits in |0>, no truncation
+    pub fn new(no_qubits: usize) -> Self {
+        assert!(no_qubits > 0);
+        let zero = || {
+            [
+                DMatrix::from_element(1, 1, C64::new(1.0, 0.0)),
+                DMatrix::zeros(1, 1),
+            ]
+        };
+        Self {
+            tensors: (0..no_qubits).map(|_| zero()).collect(),
+            center: 0,
+            max_bond: usize::MAX,
+            cutoff: 0.0,
+            truncation_error: 0.0,
+        }
+    }
+
+    pub fn with_max_bond(mut self, max_bond: usize) -> Self {
+        assert!(max_bond > 0);
+        self.max_bond = max_bond;
+        self
+    }
+
+    /// largest weight of singular values that may be dropped at every split
+    pub fn with_cutoff(mut self, cutoff: f64) -> Self {
+        self.cutoff = cutoff;
+        self
+    }
+
+    pub fn no_qubits(&self) -> usize {
+        self.tensors.len()
+    }
+
+    /// sum of the weights dropped at all truncations
+    pub fn truncation_error(&self) -> f64 {
+        self.truncation_error
+    }
+
+    /// bond dimension between qubit i and i + 1
+    pub fn bond_dimensions(&self) -> Vec<usize> {
+        self.tensors[..self.no_qubits() - 1]
+            .iter()
+            .map(|tensor| tensor[0].ncols())
+            .collect()
+    }
+
+    /// amplitude of a basis state, qubit 0 first and true for |0> like `sample`
+    pub fn amplitude(&self, bits: &[bool]) -> C64 {
+        assert_eq!(bits.len(), self.no_qubits());
+        let mut product = DMatrix::from_element(1, 1, C64::new(1.0, 0.0));
+        for (tensor, bit) in self.tensors.iter().zip(bits) {
+            product *= &tensor[usize::from(!bit)];
+        }
+        product[(0, 0)]
+    }
+
+    pub fn apply(&mut self, matrix: &Matrix2<C64>, qubit: usize) {
+        let [a0, a1] = &self.tensors[qubit];
+        let new = [
+            a0 * matrix[(0, 0)] + a1 * matrix[(0, 1)],
+            a0 * matrix[(1, 0)] + a1 * matrix[(1, 1)],
+        ];
+        self.tensors[qubit] = new;
+    }
+
+    pub fn apply_gate(&mut self, gate: SingleInputGate, qubit: usize) {
+        self.apply(&complex_matrix(gate), qubit);
+    }
+
+    /// move the canonical center one site to the right
+    fn shift_right(&mut self) {
+        let k = self.center;
+        let [a0, a1] = &self.tensors[k];
+        let (left, right) = (a0.nrows(), a0.ncols());
+        let mut stacked = DMatrix::zeros(2 * left, right);
+        stacked.rows_mut(0, left).copy_from(a0);
+        stacked.rows_mut(left, left).copy_from(a1);
+        let qr = stacked.qr();
+        let (q, r) = (qr.q(), qr.r());
+        let bond = q.ncols();
+        self.tensors[k] = [
+            q.rows(0, left).into_owned(),
+            q.rows(left, left).into_owned(),
+        ];
+        let [b0, b1] = &self.tensors[k + 1];
+        self.tensors[k + 1] = [&r * b0, &r * b1];
+        debug_assert_eq!(self.tensors[k + 1][0].nrows(), bond);
+        self.center += 1;
+    }
+
+    /// move the canonical center one site to the left
+    fn shift_left(&mut self) {
+        let k = self.center;
+        let [a0, a1] = &self.tensors[k];
+        let (left, right) = (a0.nrows(), a0.ncols());
+        let mut joined = DMatrix::zeros(left, 2 * right);
+        joined.columns_mut(0, right).copy_from(a0);
+        joined.columns_mut(right, right).copy_from(a1);
+        // joined = r^dagger q^dagger
+        let qr = joined.adjoint().qr();
+        let (q, r) = (qr.q().adjoint(), qr.r().adjoint());
+        self.tensors[k] = [
+            q.columns(0, right).into_owned(),
+            q.columns(right, right).into_owned(),
+        ];
+        let [b0, b1] = &self.tensors[k - 1];
+        self.tensors[k - 1] = [b0 * &r, b1 * &r];
+        self.center -= 1;
+    }
+
+    fn move_center(&mut self, site: usize) {
+        while self.center < site {
+            self.shift_right();
+        }
+        while self.center > site {
+            self.shift_left();
+        }
+    }
+
+    /// two qubit gate on qubit `site` and `site + 1`, the gate is in the basis |s(site) s(site+1)>
+    fn apply_neighbours(&mut self, gate: &Matrix4<C64>, site: usize) {
+        self.move_center(site);
+        let (left, right) = (
+            self.tensors[site][0].nrows(),
+            self.tensors[site + 1][0].ncols(),
+        );
+
+        // blocks[s1][s2] = A_site[s1] A_site+1[s2], mixed by the gate
+        let products: Vec<DMatrix<C64>> = (0..4)
+            .map(|pair| &self.tensors[site][pair / 2] * &self.tensors[site + 1][pair % 2])
+            .collect();
+        let mut theta = DMatrix::zeros(2 * left, 2 * right);
+        for pair in 0..4 {
+            let mut block = DMatrix::zeros(left, right);
+            for (other, product) in products.iter().enumerate() {
+                block += product * gate[(pair, other)];
+            }
+            theta
+                .view_mut(((pair / 2) * left, (pair % 2) * right), (left, right))
+                .copy_from(&block);
+        }
+
+        let (u, singular, v_t) = svd(&theta);
+        let weights: Vec<f64> = singular.iter().map(|value| value.powi(2)).collect();
+        let total: f64 = weights.iter().sum();
+
+        // keep the largest singular values, drop the rest as long as it stays under the cutoff
+        let mut keep = weights
+            .iter()
+            .filter(|w| **w > ZERO_WEIGHT * total)
+            .count()
+            .max(1);
+        keep = keep.min(self.max_bond);
+        let mut dropped: f64 = weights[keep..].iter().sum();
+        while keep > 1 && dropped + weights[keep - 1] <= self.cutoff * total {
+            keep -= 1;
+            dropped += weights[keep];
+        }
+        self.truncation_error += dropped / total;
+
+        let norm = (total - dropped).sqrt();
+        let mut new_left = [DMatrix::zeros(left, keep), DMatrix::zeros(left, keep)];
+        let mut new_right = [DMatrix::zeros(keep, right), DMatrix::zeros(keep, right)];
+        for (column, value) in singular[..keep].iter().enumerate() {
+            let scale = C64::new(value / norm, 0.0);
+            for s in 0..2 {
+                new_left[s]
+                    .column_mut(column)
+                    .copy_from(&u.view((s * left, column), (left, 1)));
+                new_right[s]
+                    .row_mut(column)
+                    .copy_from(&(v_t.view((column, s * right), (1, right)) * scale));
+            }
+        }
+        self.tensors[site] = new_left;
+        self.tensors[site + 1] = new_right;
+        self.center = site + 1;
+    }
+
+    /// two qubit gate in the basis |s(first) s(second)>, qubits that are not neighbours are
+    /// swapped next to each other and back
+    pub fn apply_two(&mut self, gate: &Matrix4<C64>, first: usize, second: usize) {
+        assert_ne!(first, second);
+        let swap = swap_matrix();
+        let (low, high) = (first.min(second), first.max(second));
+        for site in (low + 1..high).rev() {
+            self.apply_neighbours(&swap, site);
+        }
+        // the qubit of `high` now sits at low + 1
+        let gate = if first < second {
+            *gate
+        } else {
+            swap * gate * swap
+        };
+        self.apply_neighbours(&gate, low);
+        for site in low + 1..high {
+            self.apply_neighbours(&swap, site);
+        }
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) {
+        self.apply_two(
+            &controlled(&complex_matrix(SingleInputGate::X)),
+            control,
+            target,
+        );
+    }
+
+    /// transfer matrices of <psi| ops |psi>, `ops` has one optional operator per qubit
+    fn contract(&self, ops: &[Option<Matrix2<C64>>]) -> C64 {
+        let mut environment = DMatrix::from_element(1, 1, C64::new(1.0, 0.0));
+        for (tensor, op) in self.tensors.iter().zip(ops) {
+            let op = op.unwrap_or_else(Matrix2::identity);
+            let mut next = DMatrix::zeros(tensor[0].ncols(), tensor[0].ncols());
+            for s in 0..2 {
+                for t in 0..2 {
+                    if op[(s, t)] != C64::new(0.0, 0.0) {
+                        next += tensor[s].adjoint() * &environment * &tensor[t] * op[(s, t)];
+                    }
+                }
+            }
+            environment = next;
+        }
+        environment[(0, 0)]
+    }
+
+    /// <psi| o_1 o_2 .. |psi> for single qubit operators on different qubits
+    pub fn expectation(&self, ops: &[(usize, Matrix2<C64>)]) -> f64 {
+        let mut per_qubit = vec![None; self.no_qubits()];
+        for (qubit, op) in ops {
+            assert!(per_qubit[*qubit].is_none(), "one operator per qubit");
+            per_qubit[*qubit] = Some(*op);
+        }
+        (self.contract(&per_qubit) / self.contract(&vec![None; self.no_qubits()])).re
+    }
+
+    /// probability to measure qubit `index` as |0>
+    pub fn probability_zero(&mut self, index: usize) -> f64 {
+        self.move_center(index);
+        let [a0, a1] = &self.tensors[index];
+        let (zero, one) = (a0.norm_squared(), a1.norm_squared());
+        zero / (zero + one)
+    }
+
+    /// measure qubit `index` in the standard basis, true for |0>
+    pub fn measure(&mut self, index: usize) -> bool {
+        let zero = rand::rng().random_bool(self.probability_zero(index).clamp(0.0, 1.0));
+        let (keep, drop) = if zero { (0, 1) } else { (1, 0) };
+        let norm = self.tensors[index][keep].norm();
+        self.tensors[index][keep] /= C64::new(norm, 0.0);
+        self.tensors[index][drop].fill(C64::new(0.0, 0.0));
+        zero
+    }
+
+    /// sample all qubits without changing the state, true for |0>
+    pub fn sample(&mut self) -> Vec<bool> {
+        self.move_center(0);
+        let mut rng = rand::rng();
+        // everything right of the center is right orthonormal, so the weight of a prefix is the
+        // squared norm of its row vector
+        let mut prefix = DMatrix::from_element(1, 1, C64::new(1.0, 0.0));
+        let mut bits = vec![];
+        for tensor in &self.tensors {
+            let zero = &prefix * &tensor[0];
+            let one = &prefix * &tensor[1];
+            let (p0, p1) = (zero.norm_squared(), one.norm_squared());
+            let is_zero = rng.random_bool((p0 / (p0 + p1)).clamp(0.0, 1.0));
+            prefix = if is_zero { zero } else { one };
+            let norm = prefix.norm();
+            prefix /= C64::new(norm, 0.0);
+            bits.push(is_zero);
+        }
+        bits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::state_vector::{rotation_y, StateVector};
+
+    #[test]
+    fn test_against_state_vector() {
+        let mut rng = rand::rng();
+        let n = 6;
+        for _circuit in 0..10 {
+            let mut mps = Mps::new(n);
+            let mut vector = StateVector::new(n);
+            for _gate in 0..30 {
+                let a = rng.random_range(0..n);
+                if rng.random_bool(0.5) {
+                    let rotation = rotation_y(rng.random::<f64>() * 3.0);
+                    mps.apply(&rotation, a);
+                    vector.apply(&rotation, a);
+                } else {
+                    let b = (a + rng.random_range(1..n)) % n;
+                    mps.cnot(a, b);
+                    vector.cnot(a, b);
+                }
+            }
+            for (index, amplitude) in vector.amplitudes().iter().enumerate() {
+                let bits: Vec<bool> = (0..n).map(|i| (index >> (n - 1 - i)) & 1 == 0).collect();
+                assert!((mps.amplitude(&bits) - amplitude).norm() < 1e-9);
+            }
+            assert!(mps.truncation_error() < 1e-12);
+
+            let z = complex_matrix(SingleInputGate::Z);
+            let expected = 2.0 * vector.probability_zero(2) - 1.0;
+            assert!((mps.expectation(&[(2, z)]) - expected).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_wide_ghz() {
+        let n = 80;
+        let mut mps = Mps::new(n);
+        mps.apply_gate(SingleInputGate::Hadamard, 0);
+        for i in 1..n {
+            mps.cnot(i - 1, i);
+        }
+        assert!(mps.bond_dimensions().iter().all(|bond| *bond == 2));
+
+        let z = complex_matrix(SingleInputGate::Z);
+        assert!((mps.expectation(&[(0, z), (n - 1, z)]) - 1.0).abs() < 1e-9);
+        assert!(mps.expectation(&[(5, z)]).abs() < 1e-9);
+
+        for _shot in 0..5 {
+            let bits = mps.sample();
+            assert!(bits.iter().all(|bit| *bit == bits[0]));
+            assert!((mps.amplitude(&bits).norm_sqr() - 0.5).abs() < 1e-9);
+        }
+        let first = mps.measure(10);
+        assert_eq!(mps.measure(n - 1), first);
+    }
+
+    #[test]
+    fn test_truncation() {
+        // a bell pair does not fit into bond dimension 1, half of the weight is lost
+        let mut mps = Mps::new(2).with_max_bond(1);
+        mps.apply_gate(SingleInputGate::Hadamard, 0);
+        mps.cnot(0, 1);
+        assert!((mps.truncation_error() - 0.5).abs() < 1e-9);
+
+        // a slightly entangled pair is cut off below the cutoff
+        let mut mps = Mps::new(2).with_cutoff(0.01);
+        mps.apply(&rotation_y(0.1), 0);
+        mps.cnot(0, 1);
+        assert_eq!(mps.bond_dimensions(), vec![1]);
+        assert!(mps.truncation_error() > 0.0 && mps.truncation_error() < 0.01);
+    }
+}
@@ -0,0 +1,71 @@
+// Pauli noise channels
+//
+// The channels are simulated by applying a random pauli error (or none) to the qubit, averaged
+// over many runs this gives the mixed state of the channel.
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use rand::prelude::*;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoiseChannel {
+    /// X with probability p
+    BitFlip(f64),
+    /// Z with probability p
+    PhaseFlip(f64),
+    /// the qubit is replaced by the completely mixed state with probability p,
+    /// i.e. X, Y and Z each with probability p/4
+    Depolarizing(f64),
+}
+
+impl NoiseChannel {
+    /// the pauli error that hits the qubit this time, None if it gets through untouched
+    pub fn sample_error(&self) -> Option<SingleInputGate> {
+        let mut rng = rand::rng();
+        match self {
+            NoiseChannel::BitFlip(p) => rng.random_bool(*p).then_some(SingleInputGate::X),
+            NoiseChannel::PhaseFlip(p) => rng.random_bool(*p).then_some(SingleInputGate::Z),
+            NoiseChannel::Depolarizing(p) => {
+                if !rng.random_bool(*p) {
+                    return None;
+                }
+                match rng.random_range(0..4) {
+                    0 => None,
+                    1 => Some(SingleInputGate::X),
+                    2 => Some(SingleInputGate::Y),
+                    _ => Some(SingleInputGate::Z),
+                }
+            }
+        }
+    }
+
+    /// let the qubit at `index` pass through the channel
+    pub fn apply<B: Backend + ?Sized>(&self, state: &mut B, index: usize) {
+        if let Some(gate) = self.sample_error() {
+            state.apply_gate(gate, index);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::entangled_particle_n::EntangledParticleN;
+    use nalgebra::SVector;
+
+    #[test]
+    fn test_bit_flip() {
+        let reps = 2000;
+        let mut flips = 0;
+        for _i in 0..reps {
+            let mut prtcl = EntangledParticleN::new(SVector::<f64, 4>::new(1.0, 0.0, 0.0, 0.0));
+            NoiseChannel::BitFlip(0.2).apply(&mut prtcl, 1);
+            // measure returns false for |1>
+            if !prtcl.measure(1) {
+                flips += 1;
+            }
+            assert!(prtcl.measure(0));
+        }
+        assert!((flips as f64 / reps as f64 - 0.2).abs() < 0.04);
+    }
+}
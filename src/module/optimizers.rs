@@ -0,0 +1,350 @@
+// Classical optimizers for variational algorithms
+//
+// VQE and QAOA hand a cost function of the circuit parameters to a classical optimizer. All of
+// them only need function values:
+//
+// Nelder-Mead: a simplex of n + 1 points is reflected, expanded and contracted away from the
+//              worst point, no gradients at all. Good for few parameters and exact energies.
+// SPSA:        the gradient is estimated from two evaluations along a random direction with
+//              shrinking gains, which works even when every evaluation is a noisy shot estimate.
+// Gradient descent: central finite differences, 2n evaluations per step.
+use rand::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct OptimizationResult {
+    pub parameters: Vec<f64>,
+    pub value: f64,
+    /// number of function evaluations
+    pub evaluations: usize,
+    /// value after every iteration
+    pub history: Vec<f64>,
+}
+
+pub trait Optimizer {
+    fn minimize(
+        &self,
+        function: &mut dyn FnMut(&[f64]) -> f64,
+        initial: &[f64],
+    ) -> OptimizationResult;
+}
+
+/// counts the evaluations of the function
+struct Counted<'a> {
+    function: &'a mut dyn FnMut(&[f64]) -> f64,
+    evaluations: usize,
+}
+
+impl Counted<'_> {
+    fn call(&mut self, parameters: &[f64]) -> f64 {
+        self.evaluations += 1;
+        (self.function)(parameters)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct NelderMead {
+    max_iterations: usize,
+    tolerance: f64,
+    step: f64,
+}
+
+impl NelderMead {
+    pub fn new() -> Self {
+        Self {
+            max_iterations: 2000,
+            tolerance: 1e-10,
+            step: 0.5,
+        }
+    }
+
+    pub fn with_max_iterations(mut self, max_iterations: usize) -> Self {
+        self.max_iterations = max_iterations;
+        self
+    }
+
+    /// stop when the values on the simplex differ by less than this
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// size of the initial simplex
+    pub fn with_step(mut self, step: f64) -> Self {
+        self.step = step;
+        self
+    }
+}
+
+impl Default for NelderMead {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// a + factor * (b - a)
+fn towards(a: &[f64], b: &[f64], factor: f64) -> Vec<f64> {
+    a.iter().zip(b).map(|(a, b)| a + factor * (b - a)).collect()
+}
+
+impl Optimizer for NelderMead {
+    fn minimize(
+        &self,
+        function: &mut dyn FnMut(&[f64]) -> f64,
+        initial: &[f64],
+    ) -> OptimizationResult {
+        let mut f = Counted {
+            function,
+            evaluations: 0,
+        };
+        let n = initial.len();
+        let mut simplex: Vec<(Vec<f64>, f64)> = vec![(initial.to_vec(), f.call(initial))];
+        for i in 0..n {
+            let mut point = initial.to_vec();
+            point[i] += self.step;
+            let value = f.call(&point);
+            simplex.push((point, value));
+        }
+
+        let mut history = vec![];
+        for _iteration in 0..self.max_iterations {
+            simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+            history.push(simplex[0].1);
+            if simplex[n].1 - simplex[0].1 < self.tolerance {
+                break;
+            }
+            let centroid: Vec<f64> = (0..n)
+                .map(|i| simplex[..n].iter().map(|(p, _)| p[i]).sum::<f64>() / n as f64)
+                .collect();
+            let worst = simplex[n].clone();
+
+            let reflected = towards(&centroid, &worst.0, -1.0);
+            let reflected_value = f.call(&reflected);
+            if reflected_value < simplex[0].1 {
+                let expanded = towards(&centroid, &worst.0, -2.0);
+                let expanded_value = f.call(&expanded);
+                simplex[n] = if expanded_value < reflected_value {
+                    (expanded, expanded_value)
+                } else {
+                    (reflected, reflected_value)
+                };
+            } else if reflected_value < simplex[n - 1].1 {
+                simplex[n] = (reflected, reflected_value);
+            } else {
+                // contract towards the better of the worst and the reflected point
+                let outside = reflected_value < worst.1;
+                let target = if outside { &reflected } else { &worst.0 };
+                let contracted = towards(&centroid, target, 0.5);
+                let contracted_value = f.call(&contracted);
+                if contracted_value < reflected_value.min(worst.1) {
+                    simplex[n] = (contracted, contracted_value);
+                } else {
+                    // shrink everything towards the best point
+                    let best = simplex[0].0.clone();
+                    for vertex in simplex.iter_mut().skip(1) {
+                        vertex.0 = towards(&best, &vertex.0, 0.5);
+                        vertex.1 = f.call(&vertex.0);
+                    }
+                }
+            }
+        }
+        simplex.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let (parameters, value) = simplex.swap_remove(0);
+        OptimizationResult {
+            parameters,
+            value,
+            evaluations: f.evaluations,
+            history,
+        }
+    }
+}
+
+/// simultaneous perturbation stochastic approximation (Spall)
+#[derive(Clone, Debug)]
+pub struct Spsa {
+    iterations: usize,
+    a: f64,
+    c: f64,
+}
+
+impl Spsa {
+    pub fn new(iterations: usize) -> Self {
+        Self {
+            iterations,
+            a: 0.2,
+            c: 0.1,
+        }
+    }
+
+    /// a_k = a / (k + 1 + A)^0.602 is the step size, A is a tenth of the iterations
+    pub fn with_step(mut self, a: f64) -> Self {
+        self.a = a;
+        self
+    }
+
+    /// c_k = c / (k + 1)^0.101 is the size of the perturbation
+    pub fn with_perturbation(mut self, c: f64) -> Self {
+        self.c = c;
+        self
+    }
+}
+
+impl Optimizer for Spsa {
+    fn minimize(
+        &self,
+        function: &mut dyn FnMut(&[f64]) -> f64,
+        initial: &[f64],
+    ) -> OptimizationResult {
+        let mut f = Counted {
+            function,
+            evaluations: 0,
+        };
+        let mut rng = rand::rng();
+        let stability = 0.1 * self.iterations as f64;
+        let mut parameters = initial.to_vec();
+        let mut history = vec![];
+        for k in 0..self.iterations {
+            let a_k = self.a / (k as f64 + 1.0 + stability).powf(0.602);
+            let c_k = self.c / (k as f64 + 1.0).powf(0.101);
+            let delta: Vec<f64> = parameters
+                .iter()
+                .map(|_| if rng.random_bool(0.5) { 1.0 } else { -1.0 })
+                .collect();
+            let plus: Vec<f64> = parameters
+                .iter()
+                .zip(&delta)
+                .map(|(p, d)| p + c_k * d)
+                .collect();
+            let minus: Vec<f64> = parameters
+                .iter()
+                .zip(&delta)
+                .map(|(p, d)| p - c_k * d)
+                .collect();
+            let (value_plus, value_minus) = (f.call(&plus), f.call(&minus));
+            let slope = (value_plus - value_minus) / (2.0 * c_k);
+            for (p, d) in parameters.iter_mut().zip(&delta) {
+                // the entries of delta are +-1, so dividing by them is multiplying
+                *p -= a_k * slope * d;
+            }
+            history.push((value_plus + value_minus) / 2.0);
+        }
+        let value = f.call(&parameters);
+        OptimizationResult {
+            parameters,
+            value,
+            evaluations: f.evaluations,
+            history,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GradientDescent {
+    learning_rate: f64,
+    iterations: usize,
+    epsilon: f64,
+    tolerance: f64,
+}
+
+impl GradientDescent {
+    pub fn new(learning_rate: f64, iterations: usize) -> Self {
+        Self {
+            learning_rate,
+            iterations,
+            epsilon: 1e-5,
+            tolerance: 1e-8,
+        }
+    }
+
+    /// step of the finite differences
+    pub fn with_epsilon(mut self, epsilon: f64) -> Self {
+        self.epsilon = epsilon;
+        self
+    }
+
+    /// stop when the gradient is shorter than this
+    pub fn with_tolerance(mut self, tolerance: f64) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+}
+
+impl Optimizer for GradientDescent {
+    fn minimize(
+        &self,
+        function: &mut dyn FnMut(&[f64]) -> f64,
+        initial: &[f64],
+    ) -> OptimizationResult {
+        let mut f = Counted {
+            function,
+            evaluations: 0,
+        };
+        let mut parameters = initial.to_vec();
+        let mut history = vec![];
+        for _iteration in 0..self.iterations {
+            let gradient: Vec<f64> = (0..parameters.len())
+                .map(|i| {
+                    let mut shifted = parameters.clone();
+                    shifted[i] += self.epsilon;
+                    let plus = f.call(&shifted);
+                    shifted[i] -= 2.0 * self.epsilon;
+                    let minus = f.call(&shifted);
+                    (plus - minus) / (2.0 * self.epsilon)
+                })
+                .collect();
+            let norm = gradient.iter().map(|g| g * g).sum::<f64>().sqrt();
+            for (p, g) in parameters.iter_mut().zip(&gradient) {
+                *p -= self.learning_rate * g;
+            }
+            history.push(f.call(&parameters));
+            if norm < self.tolerance {
+                break;
+            }
+        }
+        let value = f.call(&parameters);
+        OptimizationResult {
+            parameters,
+            value,
+            evaluations: f.evaluations,
+            history,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn rosenbrock(x: &[f64]) -> f64 {
+        (1.0 - x[0]).powi(2) + 100.0 * (x[1] - x[0] * x[0]).powi(2)
+    }
+
+    #[test]
+    fn test_nelder_mead() {
+        let result = NelderMead::new().minimize(&mut rosenbrock, &[-1.2, 1.0]);
+        assert!(result.value < 1e-8);
+        assert!((result.parameters[0] - 1.0).abs() < 1e-3);
+        assert!(result.history.windows(2).all(|pair| pair[1] <= pair[0]));
+    }
+
+    #[test]
+    fn test_gradient_descent() {
+        let mut bowl = |x: &[f64]| (x[0] - 2.0).powi(2) + 3.0 * (x[1] + 1.0).powi(2);
+        let result = GradientDescent::new(0.1, 500).minimize(&mut bowl, &[0.0, 0.0]);
+        assert!((result.parameters[0] - 2.0).abs() < 1e-6);
+        assert!((result.parameters[1] + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_spsa_with_noise() {
+        let mut rng = rand::rng();
+        let mut noisy = |x: &[f64]| {
+            x.iter().map(|v| (v - 0.5).powi(2)).sum::<f64>() + 0.01 * (rng.random::<f64>() - 0.5)
+        };
+        let result = Spsa::new(600)
+            .with_step(0.5)
+            .minimize(&mut noisy, &[2.0, -1.0, 0.0]);
+        assert!(result.parameters.iter().all(|p| (p - 0.5).abs() < 0.1));
+        assert_eq!(result.evaluations, 1201);
+    }
+}
@@ -0,0 +1,494 @@
+// Oracles from classical boolean functions
+//
+// A boolean function f on n inputs becomes a quantum oracle in one of two ways:
+//
+// bit flip: |x>|t> -> |x>|t ^ f(x)>
+// phase:    |x>    -> (-1)^f(x) |x>, the bit flip oracle on an ancilla in |->
+//
+// A function given by its truth table, a closure or an expression is synthesized from its
+// algebraic normal form, f = XOR of products of inputs. The coefficients come from the Moebius
+// transform of the truth table and every product is one multi-controlled x on the target, so no
+// ancillas are needed, but a function like a | b | c | .. has 2^n - 1 products.
+//
+// An expression like "(a & !b) | c" can also be synthesized along its structure: every
+// subexpression that is not an input is computed into an ancilla, combined into the target with
+// a toffoli and computed back, a | b as a ^ b ^ (a & b). That needs one ancilla per nested
+// operation but only gates linear in the size of the expression.
+//
+// The inputs are ordered like the qubits, input 0 is the most significant bit of the index into
+// the truth table.
+use crate::module::circuit::Circuit;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Expression {
+    Constant(bool),
+    Variable(usize),
+    Not(Box<Expression>),
+    And(Box<Expression>, Box<Expression>),
+    Or(Box<Expression>, Box<Expression>),
+    Xor(Box<Expression>, Box<Expression>),
+}
+
+impl Expression {
+    /// the value for the input bits `input`, input 0 is the most significant of `no_inputs`
+    pub fn evaluate(&self, input: usize, no_inputs: usize) -> bool {
+        match self {
+            Expression::Constant(value) => *value,
+            Expression::Variable(variable) => (input >> (no_inputs - 1 - variable)) & 1 == 1,
+            Expression::Not(a) => !a.evaluate(input, no_inputs),
+            Expression::And(a, b) => a.evaluate(input, no_inputs) && b.evaluate(input, no_inputs),
+            Expression::Or(a, b) => a.evaluate(input, no_inputs) || b.evaluate(input, no_inputs),
+            Expression::Xor(a, b) => a.evaluate(input, no_inputs) ^ b.evaluate(input, no_inputs),
+        }
+    }
+
+    /// the ancillas of the oracle that follows the structure of the expression
+    pub fn no_ancillas(&self) -> usize {
+        match self {
+            Expression::Constant(_) | Expression::Variable(_) => 0,
+            Expression::Not(a) => a.no_ancillas(),
+            Expression::And(a, b) | Expression::Or(a, b) => {
+                // a is held in its ancilla while b is computed
+                let first = a.no_ancillas() + a.is_operation() as usize;
+                let second = b.no_ancillas() + b.is_operation() as usize;
+                first.max(a.is_operation() as usize + second)
+            }
+            Expression::Xor(a, b) => a.no_ancillas().max(b.no_ancillas()),
+        }
+    }
+
+    fn is_operation(&self) -> bool {
+        !matches!(self, Expression::Variable(_))
+    }
+
+    /// target ^= the expression, the ancillas start and end in |0>
+    fn bit_flip(&self, circuit: &mut Circuit, inputs: &[usize], target: usize, ancillas: &[usize]) {
+        match self {
+            Expression::Constant(false) => {}
+            Expression::Constant(true) => {
+                circuit.x(target);
+            }
+            Expression::Variable(variable) => {
+                circuit.cnot(inputs[*variable], target);
+            }
+            Expression::Not(a) => {
+                a.bit_flip(circuit, inputs, target, ancillas);
+                circuit.x(target);
+            }
+            Expression::Xor(a, b) => {
+                a.bit_flip(circuit, inputs, target, ancillas);
+                b.bit_flip(circuit, inputs, target, ancillas);
+            }
+            Expression::And(a, b) | Expression::Or(a, b) => {
+                let mut compute = Circuit::new(circuit.no_qubits());
+                let (first, rest) = a.operand(&mut compute, inputs, ancillas);
+                let (second, _) = b.operand(&mut compute, inputs, rest);
+                circuit.append(&compute);
+                circuit.toffoli(first, second, target);
+                if matches!(self, Expression::Or(..)) {
+                    circuit.cnot(first, target).cnot(second, target);
+                }
+                circuit.append(&compute.inverse(&Default::default()));
+            }
+        }
+    }
+
+    /// the qubit holding the expression: an input itself or the first ancilla, computed into it
+    fn operand<'a>(
+        &self,
+        circuit: &mut Circuit,
+        inputs: &[usize],
+        ancillas: &'a [usize],
+    ) -> (usize, &'a [usize]) {
+        match self {
+            Expression::Variable(variable) => (inputs[*variable], ancillas),
+            _ => {
+                let (ancilla, rest) = ancillas.split_first().expect("not enough ancillas");
+                self.bit_flip(circuit, inputs, *ancilla, rest);
+                (*ancilla, rest)
+            }
+        }
+    }
+}
+
+/// an expression over named variables, numbered in the order they first appear
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Formula {
+    expression: Expression,
+    variables: Vec<String>,
+}
+
+impl Formula {
+    pub fn expression(&self) -> &Expression {
+        &self.expression
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn no_inputs(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn no_ancillas(&self) -> usize {
+        self.expression.no_ancillas()
+    }
+
+    pub fn function(&self) -> BooleanFunction {
+        let n = self.no_inputs();
+        BooleanFunction::from_fn(n, |input| self.expression.evaluate(input, n))
+            .with_variables(&self.variables)
+    }
+
+    /// bit flip oracle following the structure of the expression, with `no_ancillas` ancillas
+    pub fn bit_flip_oracle(
+        &self,
+        no_qubits: usize,
+        inputs: &[usize],
+        target: usize,
+        ancillas: &[usize],
+    ) -> Circuit {
+        assert_eq!(inputs.len(), self.no_inputs());
+        assert!(ancillas.len() >= self.no_ancillas(), "not enough ancillas");
+        let mut circuit = Circuit::new(no_qubits);
+        self.expression
+            .bit_flip(&mut circuit, inputs, target, ancillas);
+        circuit
+    }
+
+    /// phase oracle following the structure of the expression, `ancillas` has one more qubit
+    /// for the |-> target
+    pub fn phase_oracle(&self, no_qubits: usize, inputs: &[usize], ancillas: &[usize]) -> Circuit {
+        let (target, rest) = ancillas.split_first().expect("not enough ancillas");
+        let bit_flip = self.bit_flip_oracle(no_qubits, inputs, *target, rest);
+        phase_oracle(&bit_flip, *target)
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    variables: Vec<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn binary(
+        &mut self,
+        operator: char,
+        operand: fn(&mut Self) -> Result<Expression, String>,
+        combine: fn(Box<Expression>, Box<Expression>) -> Expression,
+    ) -> Result<Expression, String> {
+        let mut expression = operand(self)?;
+        while self.peek() == Some(operator) {
+            self.chars.next();
+            expression = combine(Box::new(expression), Box::new(operand(self)?));
+        }
+        Ok(expression)
+    }
+
+    // | binds weakest, then ^, then &
+    fn or(&mut self) -> Result<Expression, String> {
+        self.binary('|', Self::xor, Expression::Or)
+    }
+
+    fn xor(&mut self) -> Result<Expression, String> {
+        self.binary('^', Self::and, Expression::Xor)
+    }
+
+    fn and(&mut self) -> Result<Expression, String> {
+        self.binary('&', Self::unary, Expression::And)
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        let text = self.text;
+        match self.peek() {
+            Some('!' | '~') => {
+                self.chars.next();
+                Ok(Expression::Not(Box::new(self.unary()?)))
+            }
+            Some('(') => {
+                self.chars.next();
+                let expression = self.or()?;
+                match self.chars.next() {
+                    Some(')') => Ok(expression),
+                    _ => Err(format!("missing ')' in {text}")),
+                }
+            }
+            Some('0') => {
+                self.chars.next();
+                Ok(Expression::Constant(false))
+            }
+            Some('1') => {
+                self.chars.next();
+                Ok(Expression::Constant(true))
+            }
+            Some(c) if c.is_alphabetic() || c == '_' => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                let variable = match self.variables.iter().position(|known| *known == name) {
+                    Some(variable) => variable,
+                    None => {
+                        self.variables.push(name);
+                        self.variables.len() - 1
+                    }
+                };
+                Ok(Expression::Variable(variable))
+            }
+            Some(c) => Err(format!("unexpected '{c}' in {text}")),
+            None => Err(format!("unexpected end of {text}")),
+        }
+    }
+}
+
+/// "(a & !b) | c" with ! or ~, &, ^ and | from strongest to weakest, and the constants 0 and 1
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            text,
+            chars: text.chars().peekable(),
+            variables: vec![],
+        };
+        let expression = parser.or()?;
+        if let Some(c) = parser.peek() {
+            return Err(format!("unexpected '{c}' in {text}"));
+        }
+        Ok(Formula {
+            expression,
+            variables: parser.variables,
+        })
+    }
+}
+
+/// truth table of a function of `no_inputs` bits
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BooleanFunction {
+    table: Vec<bool>,
+    variables: Vec<String>,
+}
+
+impl BooleanFunction {
+    /// `table[x]` is f(x), the inputs are called x0, x1, ..
+    pub fn from_truth_table(table: Vec<bool>) -> Self {
+        assert!(
+            table.len().is_power_of_two() && table.len() > 1,
+            "the truth table needs 2^n entries"
+        );
+        let no_inputs = table.len().trailing_zeros() as usize;
+        Self {
+            table,
+            variables: (0..no_inputs).map(|i| format!("x{i}")).collect(),
+        }
+    }
+
+    pub fn from_fn(no_inputs: usize, f: impl Fn(usize) -> bool) -> Self {
+        Self::from_truth_table((0..1 << no_inputs).map(f).collect())
+    }
+
+    pub fn with_variables(mut self, variables: &[String]) -> Self {
+        assert_eq!(variables.len(), self.no_inputs());
+        self.variables = variables.to_vec();
+        self
+    }
+
+    pub fn no_inputs(&self) -> usize {
+        self.variables.len()
+    }
+
+    pub fn variables(&self) -> &[String] {
+        &self.variables
+    }
+
+    pub fn table(&self) -> &[bool] {
+        &self.table
+    }
+
+    pub fn evaluate(&self, input: usize) -> bool {
+        self.table[input]
+    }
+
+    /// the products of the algebraic normal form as sets of inputs, [] is the constant 1
+    pub fn products(&self) -> Vec<Vec<usize>> {
+        let n = self.no_inputs();
+        let mut coefficients = self.table.clone();
+        for bit in 0..n {
+            for input in 0..coefficients.len() {
+                if (input >> bit) & 1 == 1 {
+                    coefficients[input] ^= coefficients[input ^ (1 << bit)];
+                }
+            }
+        }
+        (0..coefficients.len())
+            .filter(|input| coefficients[*input])
+            .map(|input| {
+                (0..n)
+                    .filter(|variable| (input >> (n - 1 - variable)) & 1 == 1)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// target ^= f(inputs), one multi-controlled x per product
+    pub fn bit_flip_oracle(&self, no_qubits: usize, inputs: &[usize], target: usize) -> Circuit {
+        assert_eq!(inputs.len(), self.no_inputs());
+        let mut circuit = Circuit::new(no_qubits);
+        for product in self.products() {
+            let controls: Vec<usize> = product.iter().map(|variable| inputs[*variable]).collect();
+            circuit.mcx(&controls, target);
+        }
+        circuit
+    }
+
+    /// (-1)^f(inputs) with `ancilla` in |0> before and after
+    pub fn phase_oracle(&self, no_qubits: usize, inputs: &[usize], ancilla: usize) -> Circuit {
+        phase_oracle(&self.bit_flip_oracle(no_qubits, inputs, ancilla), ancilla)
+    }
+}
+
+/// the phase oracle of a bit flip oracle onto `target`, which is |0> before and after
+pub fn phase_oracle(bit_flip: &Circuit, target: usize) -> Circuit {
+    let mut circuit = Circuit::new(bit_flip.no_qubits());
+    circuit.x(target).h(target);
+    circuit.append(bit_flip);
+    circuit.h(target).x(target);
+    circuit
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::state_vector::StateVector;
+    use std::collections::HashMap;
+
+    /// the basis state `circuit` maps the basis state `index` to
+    fn run(circuit: &Circuit, index: usize) -> usize {
+        let n = circuit.no_qubits();
+        let mut prepared = Circuit::new(n);
+        for qubit in (0..n).filter(|qubit| (index >> (n - 1 - qubit)) & 1 == 1) {
+            prepared.x(qubit);
+        }
+        prepared.append(circuit);
+        let state = prepared.state(&HashMap::new());
+        let out = state
+            .amplitudes()
+            .iter()
+            .position(|amplitude| amplitude.norm() > 0.5)
+            .unwrap();
+        assert!((state.amplitudes()[out].norm() - 1.0).abs() < 1e-9);
+        out
+    }
+
+    #[test]
+    fn test_parse() {
+        let formula: Formula = "(a & !b) | c".parse().unwrap();
+        assert_eq!(formula.variables(), ["a", "b", "c"]);
+        let function = formula.function();
+        let expected = BooleanFunction::from_fn(3, |x| (x & 0b110) == 0b100 || x & 1 == 1);
+        assert_eq!(function.table(), expected.table());
+        assert_eq!(
+            function.table(),
+            BooleanFunction::from_truth_table(vec![
+                false, true, false, true, true, true, false, true
+            ])
+            .table()
+        );
+
+        // & binds stronger than ^, ^ stronger than |
+        let formula: Formula = "x | y ^ z & ~x".parse().unwrap();
+        let function = formula.function();
+        for input in 0..8 {
+            let (x, y, z) = (input >> 2 & 1 == 1, input >> 1 & 1 == 1, input & 1 == 1);
+            assert_eq!(function.evaluate(input), x | (y ^ (z & !x)));
+        }
+        assert!("a & (b".parse::<Formula>().is_err());
+        assert!("a b".parse::<Formula>().is_err());
+        assert!("a + b".parse::<Formula>().is_err());
+    }
+
+    #[test]
+    fn test_bit_flip_oracles() {
+        let formula: Formula = "(a & !b) | (c ^ d) & !(a | 1 & d)".parse().unwrap();
+        let function = formula.function();
+        let ancillas = formula.no_ancillas();
+        // a b c d, target, ancillas
+        let n = 5 + ancillas;
+        let inputs = [0, 1, 2, 3];
+        let ancilla_qubits: Vec<usize> = (5..n).collect();
+        let structured = formula.bit_flip_oracle(n, &inputs, 4, &ancilla_qubits);
+        let normal_form = function.bit_flip_oracle(n, &inputs, 4);
+        for x in 0..16 {
+            for target in 0..2 {
+                let index = (x << (n - 4)) | (target << (n - 5));
+                let expected = index ^ ((function.evaluate(x) as usize) << (n - 5));
+                assert_eq!(run(&structured, index), expected);
+                assert_eq!(run(&normal_form, index), expected);
+            }
+        }
+
+        // a | b | c has all seven products of its inputs
+        let or = "a | b | c".parse::<Formula>().unwrap().function();
+        assert_eq!(or.products().len(), 7);
+        let majority = BooleanFunction::from_fn(3, |x: usize| x.count_ones() >= 2);
+        assert_eq!(majority.products(), [vec![1, 2], vec![0, 2], vec![0, 1]]);
+    }
+
+    #[test]
+    fn test_phase_oracle() {
+        let formula: Formula = "a & !b | !a & c".parse().unwrap();
+        let ancillas: Vec<usize> = (3..4 + formula.no_ancillas()).collect();
+        let n = 4 + formula.no_ancillas();
+        let function = formula.function();
+        let mut uniform = Circuit::new(n);
+        for qubit in 0..3 {
+            uniform.h(qubit);
+        }
+        for oracle in [
+            formula.phase_oracle(n, &[0, 1, 2], &ancillas),
+            function.phase_oracle(n, &[0, 1, 2], 3),
+        ] {
+            let mut state = uniform.clone();
+            state.append(&oracle);
+            let state = state.state(&HashMap::new());
+            for x in 0..8 {
+                let sign = if function.evaluate(x) { -1.0 } else { 1.0 };
+                let amplitude = state.amplitudes()[x << (n - 3)];
+                assert!((amplitude.re - sign / 8f64.sqrt()).abs() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn test_grover_with_synthesized_oracle() {
+        // the search of grover.rs, f(1, 0) = 1, finds the input with one iteration
+        let function = BooleanFunction::from_truth_table(vec![false, false, true, false]);
+        let mut circuit = Circuit::new(3);
+        circuit.h(0).h(1);
+        circuit.append(&function.phase_oracle(3, &[0, 1], 2));
+        // reflection about the uniform superposition
+        circuit
+            .h(0)
+            .h(1)
+            .x(0)
+            .x(1)
+            .h(1)
+            .cnot(0, 1)
+            .h(1)
+            .x(0)
+            .x(1)
+            .h(0)
+            .h(1);
+        let state: StateVector = circuit.state(&HashMap::new());
+        assert!((state.amplitudes()[0b100].norm_sqr() - 1.0).abs() < 1e-12);
+    }
+}
@@ -0,0 +1,544 @@
+// Pauli string observables
+//
+// A PauliString is a product of paulis on some qubits, Z0Z1 or X2, the identity on all others.
+// A PauliSum is a real linear combination of them, 0.5*Z0Z1 + 0.3*X2, which covers every
+// hermitian observable and in particular the hamiltonians of chemistry and spin models.
+//
+// Expectation values are exact on any backend: the qubits of a string are rotated into the z
+// basis (h for x, s^dagger then h for y) on a copy of the state and the z parity is read out.
+// For a density matrix tr(rho P) is summed directly. On hardware only samples are available,
+// `estimate` measures groups of terms that commute qubit by qubit in a shared basis, every group
+// costs `shots` samples.
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::state_vector::{complex_matrix, phase, C64};
+use nalgebra::{DMatrix, Matrix2};
+use std::fmt;
+use std::ops::{Add, Mul};
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Pauli {
+    I,
+    X,
+    Y,
+    Z,
+}
+
+impl Pauli {
+    /// the hermitian pauli matrix, y is complex unlike SingleInputGate::Y
+    pub fn matrix(&self) -> Matrix2<C64> {
+        let (zero, one, i) = (C64::new(0.0, 0.0), C64::new(1.0, 0.0), C64::new(0.0, 1.0));
+        match self {
+            Pauli::I => Matrix2::identity(),
+            Pauli::X => Matrix2::new(zero, one, one, zero),
+            Pauli::Y => Matrix2::new(zero, -i, i, zero),
+            Pauli::Z => Matrix2::new(one, zero, zero, -one),
+        }
+    }
+}
+
+/// product of paulis on distinct qubits, sorted by qubit and without identities
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Default)]
+pub struct PauliString {
+    ops: Vec<(usize, Pauli)>,
+}
+
+impl PauliString {
+    pub fn new(ops: &[(usize, Pauli)]) -> Self {
+        let mut ops: Vec<(usize, Pauli)> = ops
+            .iter()
+            .copied()
+            .filter(|(_, pauli)| *pauli != Pauli::I)
+            .collect();
+        ops.sort_by_key(|(qubit, _)| *qubit);
+        assert!(
+            ops.windows(2).all(|pair| pair[0].0 != pair[1].0),
+            "one pauli per qubit"
+        );
+        Self { ops }
+    }
+
+    pub fn identity() -> Self {
+        Self::default()
+    }
+
+    pub fn x(qubit: usize) -> Self {
+        Self::new(&[(qubit, Pauli::X)])
+    }
+
+    pub fn y(qubit: usize) -> Self {
+        Self::new(&[(qubit, Pauli::Y)])
+    }
+
+    pub fn z(qubit: usize) -> Self {
+        Self::new(&[(qubit, Pauli::Z)])
+    }
+
+    pub fn ops(&self) -> &[(usize, Pauli)] {
+        &self.ops
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    /// the pauli on `qubit`
+    pub fn get(&self, qubit: usize) -> Pauli {
+        self.ops
+            .iter()
+            .find(|(q, _)| *q == qubit)
+            .map_or(Pauli::I, |(_, pauli)| *pauli)
+    }
+
+    /// smallest register the string fits on
+    pub fn min_qubits(&self) -> usize {
+        self.ops.last().map_or(0, |(qubit, _)| qubit + 1)
+    }
+
+    /// the strings commute if they differ on an even number of qubits where both act
+    pub fn commutes_with(&self, other: &PauliString) -> bool {
+        self.ops
+            .iter()
+            .filter(|(qubit, pauli)| {
+                let theirs = other.get(*qubit);
+                theirs != Pauli::I && theirs != *pauli
+            })
+            .count()
+            .is_multiple_of(2)
+    }
+
+    /// commute on every single qubit, so they can be measured in the same basis
+    pub fn qubitwise_commutes_with(&self, other: &PauliString) -> bool {
+        self.ops.iter().all(|(qubit, pauli)| {
+            let theirs = other.get(*qubit);
+            theirs == Pauli::I || theirs == *pauli
+        })
+    }
+
+    /// rotate the qubits of the string so that it becomes a product of zs
+    fn rotate_to_z_basis<B: Backend>(&self, state: &mut B) {
+        for (qubit, pauli) in &self.ops {
+            rotate_to_z_basis(state, *qubit, *pauli);
+        }
+    }
+
+    /// <psi|P|psi>, exact
+    pub fn expectation<B: Backend + Clone>(&self, state: &B) -> f64 {
+        if self.is_identity() {
+            return 1.0;
+        }
+        let mut rotated = state.clone();
+        self.rotate_to_z_basis(&mut rotated);
+        let qubits: Vec<usize> = self.ops.iter().map(|(qubit, _)| *qubit).collect();
+        rotated.expectation_z(&qubits)
+    }
+
+    /// masks of the basis states flipped by the string and of the qubits with a z part
+    fn masks(&self, no_qubits: usize) -> (usize, usize) {
+        assert!(self.min_qubits() <= no_qubits);
+        self.ops
+            .iter()
+            .fold((0, 0), |(flip, sign), (qubit, pauli)| {
+                let bit = 1 << (no_qubits - 1 - qubit);
+                match pauli {
+                    Pauli::X => (flip | bit, sign),
+                    Pauli::Y => (flip | bit, sign | bit),
+                    Pauli::Z => (flip, sign | bit),
+                    Pauli::I => (flip, sign),
+                }
+            })
+    }
+
+    /// P|j> = factor |j ^ flip>, the factor is i^(number of ys) times -1 for every set z part
+    fn factor(&self, index: usize, sign_mask: usize) -> C64 {
+        let no_y = self.ops.iter().filter(|(_, p)| *p == Pauli::Y).count();
+        let i_power = C64::new(0.0, 1.0).powi(no_y as i32);
+        if (index & sign_mask).count_ones().is_multiple_of(2) {
+            i_power
+        } else {
+            -i_power
+        }
+    }
+
+    /// tr(rho P) of a density matrix of the whole register
+    pub fn expectation_density(&self, rho: &DMatrix<C64>) -> f64 {
+        assert!(rho.is_square() && rho.nrows().is_power_of_two());
+        let no_qubits = rho.nrows().trailing_zeros() as usize;
+        let (flip, sign) = self.masks(no_qubits);
+        (0..rho.nrows())
+            .map(|j| rho[(j ^ flip, j)] * self.factor(j, sign))
+            .sum::<C64>()
+            .re
+    }
+
+    /// the 2^n x 2^n matrix of the string on `no_qubits` qubits
+    pub fn to_matrix(&self, no_qubits: usize) -> DMatrix<C64> {
+        let (flip, sign) = self.masks(no_qubits);
+        let mut matrix = DMatrix::zeros(1 << no_qubits, 1 << no_qubits);
+        for j in 0..1 << no_qubits {
+            matrix[(j ^ flip, j)] = self.factor(j, sign);
+        }
+        matrix
+    }
+}
+
+/// rotate `qubit` so that a measurement in the z basis measures `pauli`
+fn rotate_to_z_basis<B: Backend>(state: &mut B, qubit: usize, pauli: Pauli) {
+    match pauli {
+        Pauli::I | Pauli::Z => {}
+        Pauli::X => state.apply_gate(SingleInputGate::Hadamard, qubit),
+        Pauli::Y => {
+            state.apply(&phase(-std::f64::consts::FRAC_PI_2), qubit);
+            state.apply(&complex_matrix(SingleInputGate::Hadamard), qubit);
+        }
+    }
+}
+
+impl fmt::Display for PauliString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_identity() {
+            return write!(f, "I");
+        }
+        for (qubit, pauli) in &self.ops {
+            write!(f, "{pauli:?}{qubit}")?;
+        }
+        Ok(())
+    }
+}
+
+/// "Z0Z1", "X2" or "I"
+impl FromStr for PauliString {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text = text.trim();
+        if text == "I" {
+            return Ok(Self::identity());
+        }
+        let mut ops = vec![];
+        let mut chars = text.chars().peekable();
+        while let Some(letter) = chars.next() {
+            let pauli = match letter {
+                'I' => Pauli::I,
+                'X' => Pauli::X,
+                'Y' => Pauli::Y,
+                'Z' => Pauli::Z,
+                _ => return Err(format!("unexpected '{letter}' in {text}")),
+            };
+            let mut digits = String::new();
+            while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+                digits.push(digit);
+            }
+            let qubit = digits
+                .parse()
+                .map_err(|_| format!("missing qubit after '{letter}' in {text}"))?;
+            if ops.iter().any(|(q, _)| *q == qubit) {
+                return Err(format!("qubit {qubit} appears twice in {text}"));
+            }
+            ops.push((qubit, pauli));
+        }
+        Ok(Self::new(&ops))
+    }
+}
+
+/// real combination of pauli strings
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct PauliSum {
+    terms: Vec<(f64, PauliString)>,
+}
+
+impl PauliSum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// add `coefficient` times `string`, merged with an existing term of the same string
+    pub fn add_term(&mut self, coefficient: f64, string: PauliString) {
+        match self.terms.iter_mut().find(|(_, s)| *s == string) {
+            Some((c, _)) => *c += coefficient,
+            None => self.terms.push((coefficient, string)),
+        }
+    }
+
+    pub fn with_term(mut self, coefficient: f64, string: PauliString) -> Self {
+        self.add_term(coefficient, string);
+        self
+    }
+
+    pub fn terms(&self) -> &[(f64, PauliString)] {
+        &self.terms
+    }
+
+    pub fn min_qubits(&self) -> usize {
+        self.terms
+            .iter()
+            .map(|(_, string)| string.min_qubits())
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// <psi|H|psi>, exact
+    pub fn expectation<B: Backend + Clone>(&self, state: &B) -> f64 {
+        self.terms
+            .iter()
+            .map(|(coefficient, string)| coefficient * string.expectation(state))
+            .sum()
+    }
+
+    /// tr(rho H) of a density matrix of the whole register
+    pub fn expectation_density(&self, rho: &DMatrix<C64>) -> f64 {
+        self.terms
+            .iter()
+            .map(|(coefficient, string)| coefficient * string.expectation_density(rho))
+            .sum()
+    }
+
+    pub fn to_matrix(&self, no_qubits: usize) -> DMatrix<C64> {
+        self.terms.iter().fold(
+            DMatrix::zeros(1 << no_qubits, 1 << no_qubits),
+            |matrix, (coefficient, string)| {
+                matrix + string.to_matrix(no_qubits) * C64::new(*coefficient, 0.0)
+            },
+        )
+    }
+
+    /// smallest eigenvalue by exact diagonalization, for checking small problems
+    pub fn ground_energy(&self, no_qubits: usize) -> f64 {
+        self.to_matrix(no_qubits)
+            .symmetric_eigenvalues()
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    /// indices of the non identity terms in groups that commute qubit by qubit, greedily
+    pub fn measurement_groups(&self) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = vec![];
+        for (index, (_, string)) in self.terms.iter().enumerate() {
+            if string.is_identity() {
+                continue;
+            }
+            let fits = |group: &Vec<usize>| {
+                group
+                    .iter()
+                    .all(|other| self.terms[*other].1.qubitwise_commutes_with(string))
+            };
+            match groups.iter_mut().find(|group| fits(group)) {
+                Some(group) => group.push(index),
+                None => groups.push(vec![index]),
+            }
+        }
+        groups
+    }
+
+    /// estimate <psi|H|psi> from `shots` samples per measurement group
+    pub fn estimate<B: Backend + Clone>(&self, state: &B, shots: usize) -> f64 {
+        assert!(shots > 0);
+        let identity: f64 = self
+            .terms
+            .iter()
+            .filter(|(_, string)| string.is_identity())
+            .map(|(coefficient, _)| coefficient)
+            .sum();
+        let measured: f64 = self
+            .measurement_groups()
+            .iter()
+            .map(|group| {
+                // one shared basis for the whole group
+                let mut rotated = state.clone();
+                let mut bases: Vec<(usize, Pauli)> = vec![];
+                for index in group {
+                    for (qubit, pauli) in self.terms[*index].1.ops() {
+                        if !bases.iter().any(|(q, _)| q == qubit) {
+                            bases.push((*qubit, *pauli));
+                            rotate_to_z_basis(&mut rotated, *qubit, *pauli);
+                        }
+                    }
+                }
+                let mut sums = vec![0.0; group.len()];
+                for _shot in 0..shots {
+                    let bits = rotated.sample();
+                    for (sum, index) in sums.iter_mut().zip(group) {
+                        let ones = self.terms[*index]
+                            .1
+                            .ops()
+                            .iter()
+                            .filter(|(qubit, _)| !bits[*qubit])
+                            .count();
+                        *sum += if ones.is_multiple_of(2) { 1.0 } else { -1.0 };
+                    }
+                }
+                group
+                    .iter()
+                    .zip(sums)
+                    .map(|(index, sum)| self.terms[*index].0 * sum / shots as f64)
+                    .sum::<f64>()
+            })
+            .sum();
+        identity + measured
+    }
+}
+
+impl fmt::Display for PauliSum {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (index, (coefficient, string)) in self.terms.iter().enumerate() {
+            match (index, *coefficient < 0.0) {
+                (0, false) => write!(f, "{coefficient}")?,
+                (0, true) => write!(f, "-{}", -coefficient)?,
+                (_, false) => write!(f, " + {coefficient}")?,
+                (_, true) => write!(f, " - {}", -coefficient)?,
+            }
+            if !string.is_identity() {
+                write!(f, "*{string}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// "0.5*Z0Z1 + 0.3*X2 - Y0 - 1.5", a term without a pauli string is a multiple of the identity
+impl FromStr for PauliSum {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let text: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+        // split before every sign that is not part of a number's exponent
+        let mut terms = vec![];
+        let mut start = 0;
+        for (index, c) in text.char_indices() {
+            let after_exponent = text[..index].ends_with(['e', 'E']);
+            if index > start && (c == '+' || c == '-') && !after_exponent {
+                terms.push(&text[start..index]);
+                start = index;
+            }
+        }
+        terms.push(&text[start..]);
+
+        let mut sum = PauliSum::new();
+        for term in terms {
+            let (sign, body) = match term.strip_prefix('-') {
+                Some(body) => (-1.0, body),
+                None => (1.0, term.strip_prefix('+').unwrap_or(term)),
+            };
+            let (coefficient, string) = match body.split_once('*') {
+                Some((number, string)) => (number, string.parse()?),
+                None if body.starts_with(['I', 'X', 'Y', 'Z']) => ("1", body.parse()?),
+                None => (body, PauliString::identity()),
+            };
+            let coefficient: f64 = coefficient
+                .parse()
+                .map_err(|_| format!("bad coefficient '{coefficient}' in {text}"))?;
+            sum.add_term(sign * coefficient, string);
+        }
+        Ok(sum)
+    }
+}
+
+impl Mul<PauliString> for f64 {
+    type Output = PauliSum;
+
+    fn mul(self, string: PauliString) -> PauliSum {
+        PauliSum::new().with_term(self, string)
+    }
+}
+
+impl Add for PauliSum {
+    type Output = PauliSum;
+
+    fn add(mut self, other: PauliSum) -> PauliSum {
+        for (coefficient, string) in other.terms {
+            self.add_term(coefficient, string);
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::mps::Mps;
+    use crate::module::stabilizer::StabilizerState;
+    use crate::module::state_vector::{rotation_y, StateVector};
+
+    fn bell_pair<B: Backend>() -> B {
+        let mut state = B::allocate(3);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        state.cnot(0, 1);
+        state
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let sum: PauliSum = "0.5*Z0Z1 + 0.3*X2 - Y0 - 1.5e-1".parse().unwrap();
+        assert_eq!(sum.terms().len(), 4);
+        assert_eq!(sum.terms()[2], (-1.0, PauliString::y(0)));
+        assert_eq!(sum.to_string(), "0.5*Z0Z1 + 0.3*X2 - 1*Y0 - 0.15");
+        assert_eq!(sum.to_string().parse::<PauliSum>().unwrap(), sum);
+
+        let built = 0.5 * "Z1Z0".parse::<PauliString>().unwrap() + 0.3 * PauliString::x(2);
+        assert_eq!(built.terms()[0].1, "Z0Z1".parse().unwrap());
+        assert!("Z0Z0".parse::<PauliString>().is_err());
+        assert!("0.5*Q1".parse::<PauliSum>().is_err());
+    }
+
+    #[test]
+    fn test_bell_pair_expectations() {
+        let sum: PauliSum = "Z0Z1 + X0X1 + Y0Y1 + Z0 + 2*I".parse().unwrap();
+        // 1 + 1 - 1 + 0 + 2
+        let expected = 3.0;
+        assert!((sum.expectation(&bell_pair::<StateVector>()) - expected).abs() < 1e-9);
+        assert!((sum.expectation(&bell_pair::<StabilizerState>()) - expected).abs() < 1e-9);
+        assert!((sum.expectation(&bell_pair::<Mps>()) - expected).abs() < 1e-9);
+
+        // the same from the density matrix, and the mixed state has only the identity part
+        let state: StateVector = bell_pair();
+        let rho = state.amplitudes() * state.amplitudes().adjoint();
+        assert!((sum.expectation_density(&rho) - expected).abs() < 1e-9);
+        let mixed = DMatrix::<C64>::identity(8, 8) / C64::new(8.0, 0.0);
+        assert!((sum.expectation_density(&mixed) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_matrix_and_commutation() {
+        let mut state = StateVector::new(2);
+        state.apply(&rotation_y(0.7), 0);
+        state.apply(&phase(0.4), 0);
+        state.cnot(0, 1);
+        for text in ["Y0X1", "X0Z1", "Y1"] {
+            let string: PauliString = text.parse().unwrap();
+            let matrix = string.to_matrix(2);
+            let direct = (state.amplitudes().adjoint() * &matrix * state.amplitudes())[(0, 0)];
+            assert!(
+                (string.expectation(&state) - direct.re).abs() < 1e-9,
+                "{text}"
+            );
+        }
+
+        let (xx, zz, xz): (PauliString, PauliString, PauliString) = (
+            "X0X1".parse().unwrap(),
+            "Z0Z1".parse().unwrap(),
+            "X0Z1".parse().unwrap(),
+        );
+        assert!(xx.commutes_with(&zz) && !xx.qubitwise_commutes_with(&zz));
+        assert!(!xz.commutes_with(&zz));
+        let sum: PauliSum = "Z0Z1 + Z0 + X0X1 + X1 + 3".parse().unwrap();
+        assert_eq!(sum.measurement_groups(), vec![vec![0, 1], vec![2, 3]]);
+    }
+
+    #[test]
+    fn test_estimate() {
+        let mut state = StateVector::new(2);
+        state.apply(&rotation_y(1.1), 0);
+        state.cnot(0, 1);
+        state.apply(&rotation_y(0.3), 1);
+        let sum: PauliSum = "0.5*Z0Z1 + 0.3*X1 - 0.8*X0X1 + 0.2*Y0 + 1".parse().unwrap();
+        let exact = sum.expectation(&state);
+        let estimate = sum.estimate(&state, 4000);
+        assert!((estimate - exact).abs() < 0.06, "{estimate} {exact}");
+    }
+}
@@ -0,0 +1,134 @@
+// Quantum fourier transform and phase estimation
+//
+// The QFT maps |x> to 1/sqrt(M) sum_y e^(2 pi i x y / M) |y> for M = 2^m, built from hadamards,
+// controlled phases of 2 pi / 2^k and a final reversal of the qubits.
+//
+// Phase estimation reads the eigenphase phi of an eigenstate of a unitary U into m counting
+// qubits: after hadamards, counting qubit j controls U^(2^(m - 1 - j)), which leaves
+// sum_y e^(2 pi i phi y) |y> on the counting register, and the inverse QFT turns that into
+// |phi M> if phi M is an integer, otherwise into a distribution peaked at the nearest integers.
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::state_vector::{phase, StateVector};
+use std::f64::consts::PI;
+
+fn swap(state: &mut StateVector, a: usize, b: usize) {
+    state.cnot(a, b);
+    state.cnot(b, a);
+    state.cnot(a, b);
+}
+
+/// qft on `qubits`, the first one is the most significant bit
+pub fn qft(state: &mut StateVector, qubits: &[usize]) {
+    let m = qubits.len();
+    for i in 0..m {
+        state.apply_gate(SingleInputGate::Hadamard, qubits[i]);
+        for j in i + 1..m {
+            let angle = 2.0 * PI / (1 << (j - i + 1)) as f64;
+            state.apply_controlled(&phase(angle), &[qubits[j]], qubits[i]);
+        }
+    }
+    for i in 0..m / 2 {
+        swap(state, qubits[i], qubits[m - 1 - i]);
+    }
+}
+
+/// the qft run backwards with the conjugate phases
+pub fn inverse_qft(state: &mut StateVector, qubits: &[usize]) {
+    let m = qubits.len();
+    for i in 0..m / 2 {
+        swap(state, qubits[i], qubits[m - 1 - i]);
+    }
+    for i in (0..m).rev() {
+        for j in (i + 1..m).rev() {
+            let angle = -2.0 * PI / (1 << (j - i + 1)) as f64;
+            state.apply_controlled(&phase(angle), &[qubits[j]], qubits[i]);
+        }
+        state.apply_gate(SingleInputGate::Hadamard, qubits[i]);
+    }
+}
+
+/// phase estimation with the `counting` qubits in |0..0>, `controlled_power(state, control,
+/// power)` applies U^power controlled by qubit `control`
+pub fn phase_estimation(
+    state: &mut StateVector,
+    counting: &[usize],
+    controlled_power: &mut dyn FnMut(&mut StateVector, usize, usize),
+) {
+    let m = counting.len();
+    for qubit in counting {
+        state.apply_gate(SingleInputGate::Hadamard, *qubit);
+    }
+    for (j, qubit) in counting.iter().enumerate() {
+        controlled_power(state, *qubit, 1 << (m - 1 - j));
+    }
+    inverse_qft(state, counting);
+}
+
+/// undo `phase_estimation`, `controlled_inverse_power(state, control, power)` applies
+/// U^(-power) controlled by qubit `control`
+pub fn inverse_phase_estimation(
+    state: &mut StateVector,
+    counting: &[usize],
+    controlled_inverse_power: &mut dyn FnMut(&mut StateVector, usize, usize),
+) {
+    let m = counting.len();
+    qft(state, counting);
+    for (j, qubit) in counting.iter().enumerate().rev() {
+        controlled_inverse_power(state, *qubit, 1 << (m - 1 - j));
+    }
+    for qubit in counting {
+        state.apply_gate(SingleInputGate::Hadamard, *qubit);
+    }
+}
+
+/// the distribution of the integer on `qubits`, the first one is the most significant bit
+pub fn register_probabilities(state: &StateVector, qubits: &[usize]) -> Vec<f64> {
+    let n = state.no_qubits();
+    let mut probabilities = vec![0.0; 1 << qubits.len()];
+    for (index, amplitude) in state.amplitudes().iter().enumerate() {
+        let value = qubits.iter().fold(0, |value, qubit| {
+            (value << 1) | ((index >> (n - 1 - qubit)) & 1)
+        });
+        probabilities[value] += amplitude.norm_sqr();
+    }
+    probabilities
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::state_vector::C64;
+
+    #[test]
+    fn test_qft() {
+        // qft of a basis state is a product of phases
+        let (m, x) = (4, 11usize);
+        let mut amplitudes = vec![C64::new(0.0, 0.0); 1 << m];
+        amplitudes[x] = C64::new(1.0, 0.0);
+        let mut state = StateVector::from_amplitudes(amplitudes);
+        let qubits: Vec<usize> = (0..m).collect();
+        qft(&mut state, &qubits);
+        for (y, amplitude) in state.amplitudes().iter().enumerate() {
+            let expected = C64::from_polar(0.25, 2.0 * PI * (x * y) as f64 / 16.0);
+            assert!((amplitude - expected).norm() < 1e-12);
+        }
+        inverse_qft(&mut state, &qubits);
+        assert!((state.amplitudes()[x].norm() - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_phase_estimation() {
+        // |1> is an eigenstate of phase(2 pi phi) with eigenvalue e^(2 pi i phi)
+        for (phi, expected) in [(0.375, 6), (0.8125, 13)] {
+            let mut state = StateVector::new(5);
+            state.apply_gate(SingleInputGate::X, 4);
+            phase_estimation(&mut state, &[0, 1, 2, 3], &mut |state, control, power| {
+                let angle = 2.0 * PI * phi * power as f64;
+                state.apply_controlled(&phase(angle), &[control], 4);
+            });
+            let probabilities = register_probabilities(&state, &[0, 1, 2, 3]);
+            assert!((probabilities[expected] - 1.0).abs() < 1e-12);
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// Quantum approximate optimization algorithm (Farhi, Goldstone, Gutmann)
+//
+// A QUBO asks for the bit string x that minimizes x^T Q x. With x_i = (1 - Z_i) / 2 the cost
+// becomes a pauli sum C of z and zz terms, diagonal in the standard basis. Starting from the
+// uniform superposition QAOA applies p rounds of the cost layer e^(-i gamma C) (rz rotations and
+// cnot-rz-cnot for the zz terms) and the mixer e^(-i beta sum X) (rx rotations). The 2p angles
+// are optimized classically for the lowest expected cost, then the state is sampled and the best
+// bit string kept.
+//
+// MaxCut is the QUBO of a graph: every edge that is cut gains its weight. For small graphs the
+// result is compared with the optimum found by brute force.
+use crate::module::backend::Backend;
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::graph::Graph;
+use crate::module::optimizers::Optimizer;
+use crate::module::pauli::{Pauli, PauliString, PauliSum};
+use crate::module::state_vector::{rotation_x, rotation_z, StateVector};
+use nalgebra::DMatrix;
+use std::marker::PhantomData;
+
+/// minimize x^T Q x over bit strings x
+#[derive(Clone, Debug, PartialEq)]
+pub struct Qubo {
+    matrix: DMatrix<f64>,
+}
+
+impl Qubo {
+    pub fn new(matrix: DMatrix<f64>) -> Self {
+        assert!(matrix.is_square());
+        Self { matrix }
+    }
+
+    pub fn no_variables(&self) -> usize {
+        self.matrix.nrows()
+    }
+
+    pub fn value(&self, x: &[bool]) -> f64 {
+        assert_eq!(x.len(), self.no_variables());
+        let mut value = 0.0;
+        for i in (0..x.len()).filter(|i| x[*i]) {
+            for j in (0..x.len()).filter(|j| x[*j]) {
+                value += self.matrix[(i, j)];
+            }
+        }
+        value
+    }
+
+    /// the cost as a pauli sum, x_i is 1 for qubit i in |1>
+    pub fn hamiltonian(&self) -> PauliSum {
+        let n = self.no_variables();
+        let mut hamiltonian = PauliSum::new();
+        for i in 0..n {
+            for j in 0..n {
+                let q = self.matrix[(i, j)];
+                if q == 0.0 {
+                    continue;
+                }
+                if i == j {
+                    // x_i^2 = x_i = (1 - Z_i) / 2
+                    hamiltonian.add_term(q / 2.0, PauliString::identity());
+                    hamiltonian.add_term(-q / 2.0, PauliString::z(i));
+                } else {
+                    // x_i x_j = (1 - Z_i - Z_j + Z_i Z_j) / 4
+                    hamiltonian.add_term(q / 4.0, PauliString::identity());
+                    hamiltonian.add_term(-q / 4.0, PauliString::z(i));
+                    hamiltonian.add_term(-q / 4.0, PauliString::z(j));
+                    hamiltonian
+                        .add_term(q / 4.0, PauliString::new(&[(i, Pauli::Z), (j, Pauli::Z)]));
+                }
+            }
+        }
+        hamiltonian
+    }
+
+    /// the best bit string and its value, trying all 2^n
+    pub fn brute_force(&self) -> (Vec<bool>, f64) {
+        let n = self.no_variables();
+        assert!(n < 25, "too many variables for brute force");
+        (0..1usize << n)
+            .map(|bits| {
+                let x: Vec<bool> = (0..n).map(|i| (bits >> i) & 1 == 1).collect();
+                let value = self.value(&x);
+                (x, value)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("at least one bit string")
+    }
+
+    /// minus the cut value of `graph`: an edge is cut for x_a + x_b - 2 x_a x_b = 1
+    pub fn max_cut(graph: &Graph) -> Self {
+        let mut matrix = DMatrix::zeros(graph.no_vertices(), graph.no_vertices());
+        for (a, b, weight) in graph.edges() {
+            matrix[(*a, *a)] -= weight;
+            matrix[(*b, *b)] -= weight;
+            matrix[(*a, *b)] += weight;
+            matrix[(*b, *a)] += weight;
+        }
+        Self::new(matrix)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct QaoaResult {
+    /// gamma_1..gamma_p followed by beta_1..beta_p
+    pub angles: Vec<f64>,
+    /// expected cost of the optimized state
+    pub expected_value: f64,
+    /// best bit string among the samples
+    pub best: Vec<bool>,
+    pub best_value: f64,
+    pub evaluations: usize,
+}
+
+pub struct Qaoa<B = StateVector> {
+    qubo: Qubo,
+    hamiltonian: PauliSum,
+    depth: usize,
+    shots: usize,
+    backend: PhantomData<B>,
+}
+
+impl Qaoa {
+    pub fn new(qubo: Qubo, depth: usize) -> Self {
+        assert!(depth > 0);
+        Self {
+            hamiltonian: qubo.hamiltonian(),
+            qubo,
+            depth,
+            shots: 200,
+            backend: PhantomData,
+        }
+    }
+}
+
+impl<B: Backend + Clone> Qaoa<B> {
+    /// run the circuit on the simulator `C` instead of the dense state vector
+    pub fn with_backend<C: Backend + Clone>(self) -> Qaoa<C> {
+        Qaoa {
+            qubo: self.qubo,
+            hamiltonian: self.hamiltonian,
+            depth: self.depth,
+            shots: self.shots,
+            backend: PhantomData,
+        }
+    }
+
+    /// number of samples taken from the optimized state
+    pub fn with_shots(mut self, shots: usize) -> Self {
+        self.shots = shots;
+        self
+    }
+
+    pub fn hamiltonian(&self) -> &PauliSum {
+        &self.hamiltonian
+    }
+
+    /// e^(-i gamma C), the identity part is a global phase
+    fn cost_layer(&self, state: &mut dyn Backend, gamma: f64) {
+        for (coefficient, string) in self.hamiltonian.terms() {
+            match string.ops() {
+                [] => {}
+                [(qubit, _)] => state.apply(&rotation_z(2.0 * gamma * coefficient), *qubit),
+                [(first, _), (second, _)] => {
+                    state.cnot(*first, *second);
+                    state.apply(&rotation_z(2.0 * gamma * coefficient), *second);
+                    state.cnot(*first, *second);
+                }
+                _ => unreachable!("a qubo has at most zz terms"),
+            }
+        }
+    }
+
+    /// the qaoa circuit on `state` in |00..0>
+    pub fn apply(&self, state: &mut dyn Backend, angles: &[f64]) {
+        assert_eq!(angles.len(), 2 * self.depth);
+        let (gammas, betas) = angles.split_at(self.depth);
+        for qubit in 0..self.qubo.no_variables() {
+            state.apply_gate(SingleInputGate::Hadamard, qubit);
+        }
+        for (gamma, beta) in gammas.iter().zip(betas) {
+            self.cost_layer(state, *gamma);
+            for qubit in 0..self.qubo.no_variables() {
+                state.apply(&rotation_x(2.0 * beta), qubit);
+            }
+        }
+    }
+
+    pub fn state(&self, angles: &[f64]) -> B {
+        let mut state = B::allocate(self.qubo.no_variables());
+        self.apply(&mut state, angles);
+        state
+    }
+
+    pub fn expected_value(&self, angles: &[f64]) -> f64 {
+        self.hamiltonian.expectation(&self.state(angles))
+    }
+
+    /// optimize the angles starting from small ones, then sample the best bit string
+    pub fn run(&self, optimizer: &dyn Optimizer) -> QaoaResult {
+        let initial: Vec<f64> = (0..2 * self.depth).map(|_| 0.1).collect();
+        let optimized = optimizer.minimize(&mut |angles| self.expected_value(angles), &initial);
+        let mut state = self.state(&optimized.parameters);
+        let (best, best_value) = (0..self.shots)
+            .map(|_| {
+                // a qubit in |1> (false) is a variable set to 1
+                let x: Vec<bool> = state.sample().iter().map(|zero| !zero).collect();
+                let value = self.qubo.value(&x);
+                (x, value)
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .expect("at least one shot");
+        QaoaResult {
+            angles: optimized.parameters,
+            expected_value: optimized.value,
+            best,
+            best_value,
+            evaluations: optimized.evaluations,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MaxCutReport {
+    pub cut: Vec<bool>,
+    pub cut_value: f64,
+    /// the maximum cut from brute force
+    pub max_cut: f64,
+    /// expected cut of the qaoa state over the maximum cut
+    pub approximation_ratio: f64,
+}
+
+/// run qaoa of depth `depth` on the maxcut of `graph` and compare with brute force
+pub fn max_cut(graph: &Graph, depth: usize, optimizer: &dyn Optimizer) -> MaxCutReport {
+    let qubo = Qubo::max_cut(graph);
+    let (_, optimum) = qubo.brute_force();
+    let result = Qaoa::new(qubo, depth).run(optimizer);
+    MaxCutReport {
+        cut_value: graph.cut_value(&result.best),
+        cut: result.best,
+        max_cut: -optimum,
+        approximation_ratio: result.expected_value / optimum,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::mps::Mps;
+    use crate::module::optimizers::NelderMead;
+    use crate::module::sparse_state::SparseState;
+    use crate::module::state_vector::C64;
+
+    #[test]
+    fn test_qubo_hamiltonian() {
+        let qubo = Qubo::new(DMatrix::from_row_slice(
+            3,
+            3,
+            &[1.0, -2.0, 0.5, 0.0, -1.0, 3.0, 0.5, 0.0, 2.0],
+        ));
+        let hamiltonian = qubo.hamiltonian();
+        for bits in 0..8usize {
+            // qubit 0 is the most significant bit of the basis index
+            let x: Vec<bool> = (0..3).map(|i| (bits >> (2 - i)) & 1 == 1).collect();
+            let mut amplitudes = vec![C64::new(0.0, 0.0); 8];
+            amplitudes[bits] = C64::new(1.0, 0.0);
+            let state = StateVector::from_amplitudes(amplitudes);
+            assert!((hamiltonian.expectation(&state) - qubo.value(&x)).abs() < 1e-9);
+        }
+        let (best, value) = qubo.brute_force();
+        // 1 - 2 - 1 for the first two
+        assert_eq!(best, vec![true, true, false]);
+        assert_eq!(value, -2.0);
+    }
+
+    #[test]
+    fn test_ring() {
+        // for rings p = 1 reaches 3/4 of the maximum cut
+        let report = max_cut(&Graph::ring(6), 1, &NelderMead::new());
+        assert_eq!(report.max_cut, 6.0);
+        assert!((report.approximation_ratio - 0.75).abs() < 1e-4);
+        assert_eq!(report.cut_value, 6.0);
+
+        // and deeper circuits do better
+        let report = max_cut(&Graph::ring(6), 2, &NelderMead::new());
+        assert!(report.approximation_ratio > 0.8);
+    }
+
+    #[test]
+    fn test_weighted_graph() {
+        let mut graph = Graph::new(5);
+        for (a, b, weight) in [
+            (0, 1, 2.0),
+            (0, 2, 0.5),
+            (1, 2, 1.0),
+            (1, 3, 3.0),
+            (2, 4, 1.5),
+            (3, 4, 1.0),
+            (0, 4, 0.7),
+        ] {
+            graph.add_edge(a, b, weight);
+        }
+        let report = max_cut(&graph, 3, &NelderMead::new().with_max_iterations(3000));
+        assert_eq!(report.cut_value, report.max_cut);
+        assert!(report.approximation_ratio > 0.7);
+        assert!((graph.cut_value(&report.cut) - report.cut_value).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_backends() {
+        let qaoa = Qaoa::new(Qubo::max_cut(&Graph::ring(5)), 2);
+        let angles = [0.4, -0.2, 0.7, 0.3];
+        let dense = qaoa.expected_value(&angles);
+        let qaoa = qaoa.with_backend::<SparseState>();
+        assert!((qaoa.expected_value(&angles) - dense).abs() < 1e-9);
+        let qaoa = qaoa.with_backend::<Mps>();
+        assert!((qaoa.expected_value(&angles) - dense).abs() < 1e-9);
+        let result = qaoa.run(&NelderMead::new());
+        assert_eq!(Graph::ring(5).cut_value(&result.best), 4.0);
+    }
+}
@@ -0,0 +1,325 @@
+// Transport of qubits between two named parties
+//
+// A quantum channel carries qubits one way (from -> to). On the way a qubit can be lost, hit by
+// noise or tapped by an eavesdropper sitting right behind the sender. Next to it runs a public,
+// authenticated classical channel both parties use to talk about what they sent and measured.
+use crate::module::backend::Backend;
+use crate::module::eavesdropper::Eavesdropper;
+use crate::module::entangled_particle_n::EntangledParticleN;
+use crate::module::noise::NoiseChannel;
+use crate::module::simple_example_bb_84::simple_particle::Particle;
+use crate::module::state_vector::{StateVector, C64};
+use nalgebra::Vector2;
+use rand::prelude::*;
+
+/// a pulse of the source that carries `photons` copies of the particle
+#[derive(Clone, Debug)]
+pub struct Pulse {
+    pub particle: Particle,
+    pub photons: usize,
+}
+
+/// Public classical channel between two parties. Everyone (eve included) can read the
+/// transcript, but only the two parties can send and nobody can change what was sent
+#[derive(Debug)]
+pub struct ClassicalChannel {
+    parties: [String; 2],
+    transcript: Vec<(String, Vec<usize>)>,
+    // index of the next message each party has not read yet
+    read: [usize; 2],
+}
+
+impl ClassicalChannel {
+    pub fn new(party_a: &str, party_b: &str) -> Self {
+        Self {
+            parties: [party_a.to_string(), party_b.to_string()],
+            transcript: vec![],
+            read: [0, 0],
+        }
+    }
+
+    fn party_index(&self, name: &str) -> usize {
+        self.parties
+            .iter()
+            .position(|party| party == name)
+            .unwrap_or_else(|| panic!("{name} is not a party of this channel"))
+    }
+
+    pub fn send(&mut self, from: &str, payload: Vec<usize>) {
+        // only the parties can send, that is what makes the channel authenticated
+        self.party_index(from);
+        self.transcript.push((from.to_string(), payload));
+    }
+
+    /// next message sent by the other party that `to` has not read yet
+    pub fn receive(&mut self, to: &str) -> Option<Vec<usize>> {
+        let index = self.party_index(to);
+        while self.read[index] < self.transcript.len() {
+            let (from, payload) = &self.transcript[self.read[index]];
+            self.read[index] += 1;
+            if from != to {
+                return Some(payload.clone());
+            }
+        }
+        None
+    }
+
+    pub fn send_bits(&mut self, from: &str, bits: &[bool]) {
+        self.send(from, bits.iter().map(|bit| usize::from(*bit)).collect());
+    }
+
+    pub fn receive_bits(&mut self, to: &str) -> Option<Vec<bool>> {
+        self.receive(to)
+            .map(|payload| payload.iter().map(|value| *value != 0).collect())
+    }
+
+    /// everything that was said on the channel, in order
+    pub fn transcript(&self) -> &[(String, Vec<usize>)] {
+        &self.transcript
+    }
+}
+
+pub struct QuantumChannel {
+    pub from: String,
+    pub to: String,
+    loss: f64,
+    noise: Option<NoiseChannel>,
+    tap: Option<Box<dyn Eavesdropper>>,
+    pub classical: ClassicalChannel,
+}
+
+impl QuantumChannel {
+    /// lossless, noiseless channel without eavesdropper
+    pub fn new(from: &str, to: &str) -> Self {
+        Self {
+            from: from.to_string(),
+            to: to.to_string(),
+            loss: 0.0,
+            noise: None,
+            tap: None,
+            classical: ClassicalChannel::new(from, to),
+        }
+    }
+
+    /// every qubit is lost with probability `loss`
+    pub fn with_loss(mut self, loss: f64) -> Self {
+        assert!((0.0..=1.0).contains(&loss));
+        self.loss = loss;
+        self
+    }
+
+    pub fn with_noise(mut self, noise: NoiseChannel) -> Self {
+        self.noise = Some(noise);
+        self
+    }
+
+    pub fn with_depolarizing(self, p: f64) -> Self {
+        self.with_noise(NoiseChannel::Depolarizing(p))
+    }
+
+    pub fn with_tap(mut self, eve: Box<dyn Eavesdropper>) -> Self {
+        self.tap = Some(eve);
+        self
+    }
+
+    /// the eavesdropper on the line, to ask what she learned
+    pub fn tap(&self) -> Option<&dyn Eavesdropper> {
+        self.tap.as_deref()
+    }
+
+    fn lose(&self) -> bool {
+        self.loss > 0.0 && rand::rng().random_bool(self.loss)
+    }
+
+    /// send a pulse, returns what arrives (None if nothing does)
+    pub fn send_pulse(&mut self, pulse: Pulse) -> Option<Particle> {
+        // nothing to intercept or detect, eve only moves on to the next position
+        if pulse.photons == 0 {
+            if let Some(eve) = self.tap.as_mut() {
+                eve.empty_pulse();
+            }
+            return None;
+        }
+        let mut particle = pulse.particle;
+        if let Some(eve) = self.tap.as_mut() {
+            let state = particle
+                .real_state_vector()
+                .expect("particle was never prepared");
+            particle = Particle::from_state(eve.intercept(state, pulse.photons)?);
+        }
+
+        if self.lose() {
+            return None;
+        }
+        if let Some(gate) = self.noise.and_then(|noise| noise.sample_error()) {
+            particle.apply_gate(gate);
+        }
+        Some(particle)
+    }
+
+    pub fn send_particle(&mut self, particle: Particle) -> Option<Particle> {
+        self.send_pulse(Pulse {
+            particle,
+            photons: 1,
+        })
+    }
+
+    pub fn send_pulses(&mut self, pulses: Vec<Pulse>) -> Vec<Option<Particle>> {
+        pulses
+            .into_iter()
+            .map(|pulse| self.send_pulse(pulse))
+            .collect()
+    }
+
+    pub fn send_stream(&mut self, particles: Vec<Particle>) -> Vec<Option<Particle>> {
+        particles
+            .into_iter()
+            .map(|particle| self.send_particle(particle))
+            .collect()
+    }
+
+    /// send the qubit at `index` of a (possibly entangled) state. Returns false if it got lost.
+    /// Eve can only tap qubits that are not entangled with the others anymore
+    pub fn send_qubit<B: Tappable>(&mut self, state: &mut B, index: usize) -> bool {
+        if let Some(eve) = self.tap.as_mut() {
+            let particle = state
+                .particle_state(index)
+                .expect("eve can only tap particles that are not entangled");
+            match eve.intercept(particle, 1) {
+                // the rotation that takes what alice sent to what eve forwards
+                Some(forwarded) => {
+                    let perpendicular = |v: Vector2<f64>| Vector2::new(-v[1], v[0]);
+                    let rotation = forwarded * particle.transpose()
+                        + perpendicular(forwarded) * perpendicular(particle).transpose();
+                    state.apply(&rotation.map(|value| C64::new(value, 0.0)), index);
+                }
+                None => return false,
+            }
+        }
+
+        if self.lose() {
+            return false;
+        }
+        if let Some(noise) = self.noise {
+            noise.apply(state, index);
+        }
+        true
+    }
+}
+
+/// backends eve can tap: she measures and resends the real state of a single qubit
+pub trait Tappable: Backend {
+    /// real state of the qubit at `index` up to a global phase, None if it is entangled with the
+    /// others or not real
+    fn particle_state(&self, index: usize) -> Option<Vector2<f64>>;
+}
+
+impl<const N: usize> Tappable for EntangledParticleN<N> {
+    fn particle_state(&self, index: usize) -> Option<Vector2<f64>> {
+        EntangledParticleN::particle_state(self, index)
+    }
+}
+
+impl Tappable for StateVector {
+    fn particle_state(&self, index: usize) -> Option<Vector2<f64>> {
+        let state = self.qubit_state(index)?;
+        let largest = if state[0].norm() > state[1].norm() {
+            state[0]
+        } else {
+            state[1]
+        };
+        let state = state * C64::from_polar(1.0, -largest.arg());
+        (state.iter().all(|value| value.im.abs() < 1e-6)).then(|| state.map(|value| value.re))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::eavesdropper::{InterceptResend, PhotonSource};
+    use crate::module::gates::basic::SingleInputGate;
+    use nalgebra::SVector;
+
+    #[test]
+    fn test_classical_channel() {
+        let mut channel = ClassicalChannel::new("Alice", "Bob");
+        channel.send_bits("Alice", &[true, false]);
+        channel.send("Bob", vec![2, 0]);
+
+        // nobody reads their own messages
+        assert_eq!(channel.receive_bits("Bob"), Some(vec![true, false]));
+        assert_eq!(channel.receive("Bob"), None);
+        assert_eq!(channel.receive("Alice"), Some(vec![2, 0]));
+        assert_eq!(channel.transcript().len(), 2);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_classical_channel_authenticated() {
+        let mut channel = ClassicalChannel::new("Alice", "Bob");
+        channel.send("Eve", vec![1]);
+    }
+
+    #[test]
+    fn test_loss_and_tap() {
+        let mut channel = QuantumChannel::new("Alice", "Bob").with_loss(0.3);
+        let particles = vec![Particle::from_state(nalgebra::Vector2::new(1.0, 0.0)); 2000];
+        let arrived = channel
+            .send_stream(particles)
+            .iter()
+            .filter(|p| p.is_some())
+            .count();
+        assert!((arrived as f64 / 2000.0 - 0.7).abs() < 0.04);
+
+        // eve measuring in the standard basis does not change |0> in a product state
+        let mut channel =
+            QuantumChannel::new("Alice", "Bob").with_tap(Box::new(InterceptResend::new(vec![0.0])));
+        let mut state = EntangledParticleN::new(SVector::<f64, 4>::new(1.0, 0.0, 0.0, 0.0));
+        assert!(channel.send_qubit(&mut state, 1));
+        assert_eq!(state.get_params()[0], 1.0);
+        assert!(channel.tap().unwrap().guess(0, 0.0).unwrap().bit);
+    }
+
+    #[test]
+    fn test_tap_state_vector() {
+        // eve measuring |+> in the standard basis forwards |0> or |1>, alice's qubit is untouched
+        let mut channel =
+            QuantumChannel::new("Alice", "Bob").with_tap(Box::new(InterceptResend::new(vec![0.0])));
+        let mut zeros = 0;
+        for index in 0..400 {
+            let mut state = StateVector::new(2);
+            state.apply_gate(SingleInputGate::Hadamard, 1);
+            assert!(channel.send_qubit(&mut state, 1));
+            let bit = channel.tap().unwrap().guess(index, 0.0).unwrap().bit;
+            assert!((state.probability_zero(1) - f64::from(u8::from(bit))).abs() < 1e-9);
+            assert!((state.probability_zero(0) - 1.0).abs() < 1e-9);
+            zeros += usize::from(bit);
+        }
+        assert!((zeros as f64 / 400.0 - 0.5).abs() < 0.1);
+    }
+
+    #[test]
+    fn test_empty_pulses_never_arrive() {
+        let source = PhotonSource::new(0.5);
+        let mut channel =
+            QuantumChannel::new("Alice", "Bob").with_tap(Box::new(InterceptResend::new(vec![0.0])));
+        let pulses: Vec<Pulse> = (0..1000)
+            .map(|_| Pulse {
+                particle: Particle::from_state(nalgebra::Vector2::new(1.0, 0.0)),
+                photons: source.sample_photon_number(),
+            })
+            .collect();
+        let photons: Vec<usize> = pulses.iter().map(|pulse| pulse.photons).collect();
+        let arrived = channel.send_pulses(pulses);
+        for (index, (photons, particle)) in photons.iter().zip(&arrived).enumerate() {
+            assert_eq!(*photons > 0, particle.is_some());
+            // eve only learns something about pulses that carried a photon
+            assert_eq!(
+                *photons > 0,
+                channel.tap().unwrap().guess(index, 0.0).is_some()
+            );
+        }
+        assert!(photons.iter().filter(|photons| **photons == 0).count() > 500);
+    }
+}
@@ -0,0 +1,293 @@
+// Discrete and continuous time quantum walks
+//
+// A coined walk on a line or a cycle carries a qubit, the coin, at every position: |0> moves
+// left and |1> right. A step applies the coin gate, by default a hadamard, and then moves both
+// parts. The parts interfere, so after t steps the walker has spread over ~0.54 t positions with
+// peaks near the fronts, instead of the sqrt(t) bell curve of a classical random walk. A line
+// reflects the walker at its ends, a cycle wraps around.
+//
+// On an arbitrary graph the coin space of a vertex of degree d is spanned by its d edges. The
+// grover coin 2/d J - I reflects about their uniform superposition and the flip-flop shift moves
+// the amplitude on the edge u -> v to the edge v -> u.
+//
+// A continuous time walk needs no coin: the adjacency matrix A is a hamiltonian and the walker
+// evolves as e^(-iAt)|start>.
+//
+// All walks return the probability of every position, after each step or at the given times.
+// The classical random walk to compare with moves to a neighbour with probability proportional
+// to the weight of the edge.
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::graph::Graph;
+use crate::module::state_vector::{complex_matrix, evolution_operator, C64};
+use nalgebra::{DVector, Matrix2, Vector2};
+
+/// coined walk on a line or a cycle
+#[derive(Clone, Debug)]
+pub struct CoinedWalk {
+    positions: usize,
+    cyclic: bool,
+    coin: Matrix2<C64>,
+    start: usize,
+    initial_coin: Vector2<C64>,
+}
+
+impl CoinedWalk {
+    /// starts in the middle
+    pub fn line(positions: usize) -> Self {
+        Self::new(positions, false, positions / 2)
+    }
+
+    /// starts at 0
+    pub fn cycle(positions: usize) -> Self {
+        Self::new(positions, true, 0)
+    }
+
+    fn new(positions: usize, cyclic: bool, start: usize) -> Self {
+        assert!(positions > 1);
+        Self {
+            positions,
+            cyclic,
+            coin: complex_matrix(SingleInputGate::Hadamard),
+            start,
+            // (|0> + i|1>) / sqrt(2) makes the hadamard walk symmetric
+            initial_coin: Vector2::new(C64::new(1.0, 0.0), C64::new(0.0, 1.0))
+                / C64::new(2f64.sqrt(), 0.0),
+        }
+    }
+
+    pub fn with_coin(mut self, coin: Matrix2<C64>) -> Self {
+        self.coin = coin;
+        self
+    }
+
+    pub fn with_start(mut self, start: usize) -> Self {
+        assert!(start < self.positions);
+        self.start = start;
+        self
+    }
+
+    pub fn with_initial_coin(mut self, initial_coin: Vector2<C64>) -> Self {
+        self.initial_coin = initial_coin.normalize();
+        self
+    }
+
+    /// the distribution of the positions before the first step and after every step
+    pub fn run(&self, steps: usize) -> Vec<Vec<f64>> {
+        let n = self.positions;
+        let mut amplitudes = vec![Vector2::zeros(); n];
+        amplitudes[self.start] = self.initial_coin;
+        let probabilities = |amplitudes: &[Vector2<C64>]| -> Vec<f64> {
+            amplitudes.iter().map(|coin| coin.norm_squared()).collect()
+        };
+        let mut distributions = vec![probabilities(&amplitudes)];
+        for _ in 0..steps {
+            let mut moved = vec![Vector2::zeros(); n];
+            for (x, coin) in amplitudes.iter().enumerate() {
+                let coin = self.coin * coin;
+                match (x, self.cyclic) {
+                    (0, false) => moved[0][1] += coin[0],
+                    (_, _) => moved[(x + n - 1) % n][0] += coin[0],
+                }
+                match (x == n - 1, self.cyclic) {
+                    (true, false) => moved[x][0] += coin[1],
+                    (_, _) => moved[(x + 1) % n][1] += coin[1],
+                }
+            }
+            amplitudes = moved;
+            distributions.push(probabilities(&amplitudes));
+        }
+        distributions
+    }
+}
+
+/// grover coin walk with the flip-flop shift on any graph, the weights are ignored
+#[derive(Clone, Debug)]
+pub struct GraphWalk {
+    graph: Graph,
+    start: usize,
+}
+
+impl GraphWalk {
+    /// starts in the uniform superposition of the edges leaving `start`
+    pub fn new(graph: Graph, start: usize) -> Self {
+        assert!(start < graph.no_vertices());
+        assert!(
+            (0..graph.no_vertices()).all(|vertex| !graph.neighbours(vertex).is_empty()),
+            "every vertex needs an edge"
+        );
+        Self { graph, start }
+    }
+
+    /// the distribution of the vertices before the first step and after every step
+    pub fn run(&self, steps: usize) -> Vec<Vec<f64>> {
+        // both directions of every edge, the reverse of arc i is arc i ^ 1. Pairing them by the
+        // edge keeps parallel edges apart
+        let arcs: Vec<(usize, usize)> = self
+            .graph
+            .edges()
+            .iter()
+            .flat_map(|(a, b, _)| [(*a, *b), (*b, *a)])
+            .collect();
+        let leaving: Vec<Vec<usize>> = (0..self.graph.no_vertices())
+            .map(|vertex| (0..arcs.len()).filter(|i| arcs[*i].0 == vertex).collect())
+            .collect();
+
+        let mut amplitudes = DVector::<C64>::zeros(arcs.len());
+        let degree = leaving[self.start].len() as f64;
+        for arc in &leaving[self.start] {
+            amplitudes[*arc] = C64::new(1.0 / degree.sqrt(), 0.0);
+        }
+        let probabilities = |amplitudes: &DVector<C64>| -> Vec<f64> {
+            leaving
+                .iter()
+                .map(|arcs| arcs.iter().map(|arc| amplitudes[*arc].norm_sqr()).sum())
+                .collect()
+        };
+        let mut distributions = vec![probabilities(&amplitudes)];
+        for _ in 0..steps {
+            for arcs in &leaving {
+                let mean = arcs.iter().map(|arc| amplitudes[*arc]).sum::<C64>()
+                    / C64::new(arcs.len() as f64, 0.0);
+                for arc in arcs {
+                    amplitudes[*arc] = 2.0 * mean - amplitudes[*arc];
+                }
+            }
+            amplitudes = DVector::from_fn(arcs.len(), |i, _| amplitudes[i ^ 1]);
+            distributions.push(probabilities(&amplitudes));
+        }
+        distributions
+    }
+}
+
+/// the distribution of e^(-iAt)|start> at each of the `times`
+pub fn continuous_walk(graph: &Graph, start: usize, times: &[f64]) -> Vec<Vec<f64>> {
+    let adjacency = graph.adjacency_matrix().map(|weight| C64::new(weight, 0.0));
+    times
+        .iter()
+        .map(|time| {
+            evolution_operator(&adjacency, *time)
+                .column(start)
+                .iter()
+                .map(|amplitude| amplitude.norm_sqr())
+                .collect()
+        })
+        .collect()
+}
+
+/// the classical random walk from `start`, before the first step and after every step
+pub fn classical_walk(graph: &Graph, start: usize, steps: usize) -> Vec<Vec<f64>> {
+    let adjacency = graph.adjacency_matrix();
+    let mut distribution = vec![0.0; graph.no_vertices()];
+    distribution[start] = 1.0;
+    let mut distributions = vec![distribution.clone()];
+    for _ in 0..steps {
+        let mut moved = vec![0.0; graph.no_vertices()];
+        for (from, p) in distribution.iter().enumerate() {
+            let total: f64 = adjacency.row(from).sum();
+            for (to, weight) in adjacency.row(from).iter().enumerate() {
+                moved[to] += p * weight / total;
+            }
+        }
+        distribution = moved;
+        distributions.push(distribution.clone());
+    }
+    distributions
+}
+
+/// spread of a distribution over the positions 0, 1, ..
+pub fn standard_deviation(distribution: &[f64]) -> f64 {
+    let mean: f64 = distribution
+        .iter()
+        .enumerate()
+        .map(|(x, p)| x as f64 * p)
+        .sum();
+    distribution
+        .iter()
+        .enumerate()
+        .map(|(x, p)| (x as f64 - mean).powi(2) * p)
+        .sum::<f64>()
+        .sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_hadamard_walk_spreads_faster() {
+        let steps = 100;
+        let quantum = CoinedWalk::line(2 * steps + 1).run(steps);
+        let classical = classical_walk(&Graph::line(2 * steps + 1), steps, steps);
+        let (quantum, classical) = (&quantum[steps], &classical[steps]);
+        assert!((quantum.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        for x in 0..steps {
+            assert!((quantum[x] - quantum[2 * steps - x]).abs() < 1e-12);
+        }
+        // sigma ~ 0.54 t against sqrt(t)
+        assert!((standard_deviation(classical) - 10.0).abs() < 1e-9);
+        assert!((standard_deviation(quantum) / steps as f64 - 0.54).abs() < 0.02);
+
+        // starting in |0> the walk drifts left
+        let left = CoinedWalk::line(2 * steps + 1)
+            .with_initial_coin(Vector2::new(C64::new(1.0, 0.0), C64::new(0.0, 0.0)))
+            .run(steps);
+        let left_part: f64 = left[steps][..steps].iter().sum();
+        let right_part: f64 = left[steps][steps + 1..].iter().sum();
+        assert!(left_part > 0.7 && right_part < 0.3);
+    }
+
+    #[test]
+    fn test_cycle_and_reflection() {
+        for walk in [CoinedWalk::cycle(7), CoinedWalk::line(6).with_start(1)] {
+            for distribution in walk.run(50) {
+                assert!((distribution.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_graph_walk() {
+        // on a cycle the grover coin is x, so the two halves move away from the start
+        let distributions = GraphWalk::new(Graph::ring(10), 0).run(4);
+        assert!((distributions[3][3] - 0.5).abs() < 1e-12);
+        assert!((distributions[3][7] - 0.5).abs() < 1e-12);
+
+        // the two parallel edges of a ring of two carry the walker back and forth
+        let distributions = GraphWalk::new(Graph::ring(2), 0).run(3);
+        for (step, distribution) in distributions.iter().enumerate() {
+            assert!((distribution[step % 2] - 1.0).abs() < 1e-12);
+        }
+
+        let mut graph = Graph::new(5);
+        for (a, b) in [(0, 1), (0, 2), (0, 3), (1, 2), (2, 3), (3, 4), (1, 4)] {
+            graph.add_edge(a, b, 1.0);
+        }
+        for distribution in GraphWalk::new(graph.clone(), 4).run(30) {
+            assert!((distribution.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+        // the classical walk settles on degree / (2 * edges)
+        let classical = classical_walk(&graph, 4, 200);
+        assert!((classical[200][0] - 3.0 / 14.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_continuous_walk_on_complete_graph() {
+        // <start|e^(-iAt)|start> = (e^(-i(n-1)t) + (n-1) e^(it)) / n
+        let n = 6;
+        let mut graph = Graph::new(n);
+        for a in 0..n {
+            for b in a + 1..n {
+                graph.add_edge(a, b, 1.0);
+            }
+        }
+        let times = [0.0, 0.3, 1.0, 2.5];
+        for (time, distribution) in times.iter().zip(continuous_walk(&graph, 2, &times)) {
+            let amplitude = (C64::from_polar(1.0, -(n as f64 - 1.0) * time)
+                + (n as f64 - 1.0) * C64::from_polar(1.0, *time))
+                / n as f64;
+            assert!((distribution[2] - amplitude.norm_sqr()).abs() < 1e-9);
+            assert!((distribution.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+// B92 protocol: alice only uses two non-orthogonal states, north along z for a 0 and north along
+// x for a 1. Bob measures in a random basis. Whenever he gets south he knows alice cannot have sent
+// north in the basis he used, so it must have been the other state. Only these conclusive results
+// are kept, bob announces which ones they are.
+
+/// a quarter of the qubits gives a conclusive result: bob has to pick the other basis (1/2) and
+/// then get south (1/2)
+pub const SIFTING_RATIO: f64 = 0.25;
+
+/// qber up to which a secret key can be distilled from single photons, about 4.8% (Tamaki and
+/// Lütkenhaus 2004). The bound comes out of a numerical optimization, there is no closed form
+/// for the key fraction like for the other protocols
+pub const QBER_THRESHOLD: f64 = 0.048;
+
+/// basis of the state alice sends for every bit
+pub fn encode(message: &[bool]) -> Vec<usize> {
+    message.iter().map(|bit| usize::from(*bit)).collect()
+}
+
+/// bob announces 1 for every conclusive result (south) and 0 otherwise
+pub fn announce(results: &[bool]) -> Vec<usize> {
+    results.iter().map(|result| usize::from(!result)).collect()
+}
+
+/// the key bit for a position or None if it is dropped.
+/// Bob keeps his conclusive results: south along z means alice sent the x state (1) and
+/// south along x means she sent the z state (0). Alice keeps what bob announced as conclusive
+pub fn sift(receiver: bool, basis: usize, result: bool, announcement: usize) -> Option<bool> {
+    if receiver {
+        (!result).then_some(basis == 0)
+    } else {
+        (announcement == 1).then_some(basis == 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::module::quantum_channel::QuantumChannel;
+    use crate::module::simple_example_bb_84::simple_example_bb_84::{
+        exchange_key, exchange_key_over, Protocol,
+    };
+
+    #[test]
+    fn test_b92() {
+        let stats = exchange_key(Protocol::B92, 4000, false);
+        assert_eq!(stats.qber, 0.0);
+        assert!((stats.sifted as f64 / stats.sent as f64 - 0.25).abs() < 0.03);
+
+        // intercept resend: eve gets more conclusive results through (3/8) but a third of them
+        // are wrong
+        let stats = exchange_key(Protocol::B92, 4000, true);
+        assert!((stats.sifted as f64 / stats.sent as f64 - 0.375).abs() < 0.04);
+        assert!((stats.qber - 1.0 / 3.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_b92_announcements_hold_no_key() {
+        let mut channel = QuantumChannel::new("Alice", "Bob");
+        let (alice, bob) = exchange_key_over(Protocol::B92, 400, &mut channel);
+        assert_eq!(alice.sifted_key(), bob.sifted_key());
+        let key = alice.sifted_key();
+        assert!(key.contains(&true) && key.contains(&false));
+
+        // every message is the same at all key positions, so none of them tells the bits apart
+        let positions: Vec<usize> = alice
+            .sifted_positions()
+            .iter()
+            .map(|(index, _)| *index)
+            .collect();
+        for (_, payload) in channel.classical.transcript() {
+            assert!(positions
+                .iter()
+                .all(|index| payload[*index] == payload[positions[0]]));
+        }
+    }
+}
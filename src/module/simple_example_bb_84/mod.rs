@@ -1,4 +1,6 @@
+pub mod b92;
 pub mod simple_example_bb_84;
 pub mod simple_particle;
+pub mod six_state;
 
 use crate::module::utils;
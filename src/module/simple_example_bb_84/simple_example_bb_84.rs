@@ -1,14 +1,200 @@
 // BB84 protocol for secure communication using quatum mechanical properties
-use super::simple_particle::{self as pa, Particle};
+use super::simple_particle::{self as pa, Apparatus, Particle};
+use super::{b92, six_state};
+use crate::module::eavesdropper::{binary_entropy, EavesdropReport, Eavesdropper, PhotonSource};
+use crate::module::quantum_channel::{Pulse, QuantumChannel};
 use rand::prelude::*;
 
+/// The key distribution schemes that run on top of the same parties and particle streams
+#[derive(Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Protocol {
+    /// two bases, four states
+    #[default]
+    BB84,
+    /// two non-orthogonal states, see `b92`
+    B92,
+    /// three mutually unbiased bases, see `six_state`
+    SixState,
+}
+
+impl Protocol {
+    /// number of bases the parties pick from
+    pub fn no_bases(&self) -> usize {
+        match self {
+            Protocol::BB84 | Protocol::B92 => 2,
+            Protocol::SixState => 3,
+        }
+    }
+
+    /// orient the apparatus along the basis with index `basis`
+    pub fn orient(&self, apparatus: &mut Apparatus, basis: usize) {
+        match self {
+            Protocol::BB84 | Protocol::B92 => {
+                let angle = if basis == 0 { 0.0 } else { 90.0 };
+                apparatus.set_angle(angle);
+            }
+            Protocol::SixState => six_state::orient(apparatus, basis),
+        }
+    }
+
+    /// fraction of the sent qubits that ends up in the sifted key if noone listens in
+    pub fn sifting_ratio(&self) -> f64 {
+        match self {
+            Protocol::BB84 => 0.5,
+            Protocol::B92 => b92::SIFTING_RATIO,
+            Protocol::SixState => 1.0 / 3.0,
+        }
+    }
+
+    /// asymptotic fraction of the sifted key that can be turned into a secret key after error
+    /// correction and privacy amplification at a given qber. None if we have no closed form, B92
+    /// only has the threshold
+    pub fn key_fraction(&self, qber: f64) -> Option<f64> {
+        match self {
+            // shor-preskill
+            Protocol::BB84 => Some(1.0 - 2.0 * binary_entropy(qber)),
+            Protocol::B92 => None,
+            Protocol::SixState => Some(six_state::key_fraction(qber)),
+        }
+    }
+
+    /// the qber above which no secret key can be distilled
+    pub fn qber_threshold(&self) -> Option<f64> {
+        if *self == Protocol::B92 {
+            return Some(b92::QBER_THRESHOLD);
+        }
+        self.key_fraction(0.0)?;
+        // the key fraction falls monotonically up to the threshold, bisect for the zero
+        let (mut low, mut high) = (0.0, 0.5);
+        for _i in 0..60 {
+            let mid = (low + high) / 2.0;
+            if self.key_fraction(mid)? > 0.0 {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        Some(low)
+    }
+}
+
+/// angle (in state space) of the basis the apparatus uses for a BB84 basis.
+/// The apparatus angle of 90 degree corresponds to the diagonal basis at -45 degree
+pub fn basis_angle(basis: usize) -> f64 {
+    if basis == 0 {
+        0.0
+    } else {
+        -45.0
+    }
+}
+
+/// compare the sifted keys of alice and bob against what eve can guess once the bases are public
+pub fn eavesdrop_report(alice: &Party, bob: &Party, eve: &dyn Eavesdropper) -> EavesdropReport {
+    assert_eq!(
+        bob.protocol,
+        Protocol::BB84,
+        "eve only knows how to attack BB84"
+    );
+    EavesdropReport::new(
+        &alice.msg_agreements,
+        &bob.msg_agreements,
+        &bob.sifted_positions(),
+        eve,
+    )
+}
+
+/// Outcome of a key exchange, used to compare the protocols
+#[derive(Debug, Clone)]
+pub struct KeyExchangeStats {
+    pub protocol: Protocol,
+    pub sent: usize,
+    pub sifted: usize,
+    pub qber: f64,
+}
+
+impl KeyExchangeStats {
+    pub fn new(protocol: Protocol, sent: usize, alice: &Party, bob: &Party) -> Self {
+        let errors = alice
+            .sifted_key()
+            .iter()
+            .zip(bob.sifted_key())
+            .filter(|(a, b)| a != b)
+            .count();
+        let sifted = bob.sifted_key().len();
+        KeyExchangeStats {
+            protocol,
+            sent,
+            sifted,
+            qber: errors as f64 / sifted.max(1) as f64,
+        }
+    }
+
+    /// secret key bits per sent qubit, None if the protocol has no key fraction formula
+    pub fn key_rate(&self) -> Option<f64> {
+        let fraction = self.protocol.key_fraction(self.qber)?;
+        Some(self.sifted as f64 / self.sent as f64 * fraction.max(0.0))
+    }
+}
+
+/// Run a whole exchange of `len` qubits between alice and bob. If `eve` is set, a third party of
+/// the same protocol reads every qubit and resends what she measured
+pub fn exchange_key(protocol: Protocol, len: usize, eve: bool) -> KeyExchangeStats {
+    let mut alice = Party::with_protocol("Alice", protocol);
+    let mut bob = Party::with_protocol("Bob", protocol);
+    alice.set_message_and_bases((0..len).map(|_| rand::rng().random_bool(0.5)).collect());
+
+    let mut qbits = alice.generate_particle_stream();
+    if eve {
+        let mut eve = Party::with_protocol("Eve", protocol);
+        eve.read_qbits(qbits);
+        qbits = eve.generate_particle_stream();
+    }
+    bob.read_qbits(qbits);
+
+    bob.compare_bases(alice.share_basis());
+    alice.compare_bases(bob.share_basis());
+    KeyExchangeStats::new(protocol, len, &alice, &bob)
+}
+
+/// Run a whole exchange over `channel`. The qubits travel over the quantum channel, detections
+/// and bases are announced on its classical side channel. Returns alice and bob for inspection
+pub fn exchange_key_over(
+    protocol: Protocol,
+    len: usize,
+    channel: &mut QuantumChannel,
+) -> (Party, Party) {
+    let mut alice = Party::with_protocol(&channel.from, protocol);
+    let mut bob = Party::with_protocol(&channel.to, protocol);
+    alice.set_message_and_bases((0..len).map(|_| rand::rng().random_bool(0.5)).collect());
+
+    let qbits = channel.send_stream(alice.generate_particle_stream());
+    bob.read_pulses(qbits);
+
+    channel
+        .classical
+        .send_bits(&bob.name, &bob.share_detections());
+    let detections = channel.classical.receive_bits(&alice.name).unwrap();
+    alice.apply_detections(detections);
+
+    channel.classical.send(&alice.name, alice.share_basis());
+    channel.classical.send(&bob.name, bob.share_basis());
+    bob.compare_bases(channel.classical.receive(&bob.name).unwrap());
+    alice.compare_bases(channel.classical.receive(&alice.name).unwrap());
+    (alice, bob)
+}
+
 #[derive(Default)]
 pub struct Party {
     name: String,
+    protocol: Protocol,
+    // result of the measurement (receiver) or the state that is sent (sender)
     message: Option<Vec<bool>>,
-    basis_vec: Option<Vec<bool>>,
-    basis_agreements: Vec<bool>,
+    basis_vec: Option<Vec<usize>>,
+    basis_agreements: Vec<usize>,
     msg_agreements: Vec<bool>,
+    detections: Option<Vec<bool>>,
+    sifted_indices: Vec<usize>,
+    receiver: bool,
 }
 
 impl Party {
@@ -18,6 +204,15 @@ impl Party {
             ..Default::default()
         }
     }
+
+    pub fn with_protocol(name: &str, protocol: Protocol) -> Self {
+        Party {
+            name: name.to_string(),
+            protocol,
+            ..Default::default()
+        }
+    }
+
     pub fn compare_msg_bits(&self, other_bits: Vec<bool>) -> bool {
         let own_bits = self.msg_agreements[0..self.msg_agreements.len() / 2].to_owned();
         println!("Publicly comparing half of the remaining message (after removing the part where basis didnt agree)");
@@ -27,42 +222,107 @@ impl Party {
         self.msg_agreements[0..self.msg_agreements.len() / 2] == other_bits
     }
 
-    pub fn compare_bases(&mut self, other_basis: Vec<bool>) {
+    /// keep the bits where the bases agree. For B92 the other party announces which of bobs
+    /// results were conclusive instead of its bases
+    pub fn compare_bases(&mut self, other_basis: Vec<usize>) {
         let basis_vec = self.basis_vec.as_ref().expect("no basis");
+        let message = self.message.as_ref().expect("no msg");
         for (i, item) in other_basis.iter().enumerate() {
-            if &basis_vec[i] == item {
+            // bits that never made it to bob cannot be part of the key
+            if let Some(detections) = &self.detections {
+                if !detections[i] {
+                    continue;
+                }
+            }
+            let key_bit = match self.protocol {
+                Protocol::B92 => b92::sift(self.receiver, basis_vec[i], message[i], *item),
+                Protocol::BB84 | Protocol::SixState => {
+                    (&basis_vec[i] == item).then_some(message[i])
+                }
+            };
+            if let Some(bit) = key_bit {
                 self.basis_agreements.push(other_basis[i]);
-                self.msg_agreements.push(self.message.as_ref().unwrap()[i]);
+                self.msg_agreements.push(bit);
+                self.sifted_indices.push(i);
             }
         }
         println!(
             "{} compares received basis with own and finds {} agreements. Around {} should be expected",
             self.name,
-            self.msg_agreements.len(),basis_vec.len() / 2
+            self.msg_agreements.len(),
+            (basis_vec.len() as f64 * self.protocol.sifting_ratio()).round()
         );
     }
 
     pub fn share_n_bits(&self) -> Vec<bool> {
         self.msg_agreements[0..self.msg_agreements.len() / 2].to_vec()
     }
-    pub fn share_basis(&self) -> Vec<bool> {
-        self.basis_vec.clone().unwrap()
+    /// what the party announces publicly: its bases, for B92 bob's conclusive results and
+    /// nothing of value from alice, whose "bases" are her key bits
+    pub fn share_basis(&self) -> Vec<usize> {
+        let basis_vec = self.basis_vec.clone().unwrap();
+        match (self.protocol, self.receiver) {
+            (Protocol::B92, true) => b92::announce(self.message.as_ref().expect("no msg")),
+            (Protocol::B92, false) => vec![0; basis_vec.len()],
+            _ => basis_vec,
+        }
+    }
+
+    /// bob announces publicly which pulses he detected
+    pub fn share_detections(&self) -> Vec<bool> {
+        self.detections
+            .clone()
+            .unwrap_or(vec![true; self.message.as_ref().expect("no msg").len()])
+    }
+
+    pub fn apply_detections(&mut self, detections: Vec<bool>) {
+        self.detections = Some(detections);
+    }
+
+    /// the key bits kept after comparing bases
+    pub fn sifted_key(&self) -> &[bool] {
+        &self.msg_agreements
+    }
+
+    /// stream position and basis angle of every bit in the sifted key
+    pub fn sifted_positions(&self) -> Vec<(usize, f64)> {
+        let basis_vec = self.basis_vec.as_ref().expect("no basis");
+        self.sifted_indices
+            .iter()
+            .map(|i| (*i, basis_angle(basis_vec[*i])))
+            .collect()
     }
 
     pub fn read_qbits(&mut self, qbits: Vec<pa::Particle>) {
+        self.read_pulses(qbits.into_iter().map(Some).collect());
+        self.detections = None;
+    }
+
+    /// read pulses where some might have been lost on the way (None)
+    pub fn read_pulses(&mut self, pulses: Vec<Option<Particle>>) {
         println!("{} reads qbits", self.name);
         let mut system = pa::System::default();
-        self.create_basis_vec(qbits.len());
+        self.create_basis_vec(pulses.len());
+        self.receiver = true;
         let basis_vec = self.basis_vec.as_ref().unwrap();
         let mut message: Vec<bool> = vec![];
-        for i in 0..qbits.len() {
-            let angle = if basis_vec[i] { 0.0 } else { 90.0 };
-            system.apparatus.set_angle(angle);
-            system.particle = qbits[i].clone();
-            let res = system.measure();
-            message.push(res);
+        let mut detections: Vec<bool> = vec![];
+        for (i, pulse) in pulses.into_iter().enumerate() {
+            match pulse {
+                Some(particle) => {
+                    self.protocol.orient(&mut system.apparatus, basis_vec[i]);
+                    system.particle = particle;
+                    message.push(system.measure());
+                    detections.push(true);
+                }
+                None => {
+                    message.push(false);
+                    detections.push(false);
+                }
+            }
         }
         self.message = Some(message);
+        self.detections = Some(detections);
     }
 
     pub fn set_message_and_bases(&mut self, message: Vec<bool>) {
@@ -70,6 +330,12 @@ impl Party {
         if len % 4 != 0 {
             panic!("message lenght needs to be divisible by 4")
         }
+        if self.protocol == Protocol::B92 {
+            // the bit picks one of the two states, both are sent as north
+            self.basis_vec = Some(b92::encode(&message));
+            self.message = Some(vec![true; len]);
+            return;
+        }
         self.message = Some(message);
         self.create_basis_vec(len);
     }
@@ -85,18 +351,28 @@ impl Party {
 
         particle_stream
     }
-    fn generate_particle(&self, bit: bool, basis: bool) -> Particle {
+
+    /// like generate_particle_stream but every particle is sent as a pulse of a weak laser source
+    pub fn generate_pulse_stream(&mut self, source: &PhotonSource) -> Vec<Pulse> {
+        self.generate_particle_stream()
+            .into_iter()
+            .map(|particle| Pulse {
+                particle,
+                photons: source.sample_photon_number(),
+            })
+            .collect()
+    }
+    fn generate_particle(&self, bit: bool, basis: usize) -> Particle {
         // select basis and orient apparatus accordingly
-        let angle = if basis { 0.0 } else { 90.0 };
         let mut system = pa::System::default();
-        system.apparatus.set_angle(angle);
+        self.protocol.orient(&mut system.apparatus, basis);
         let mut set_state = system.measure();
         let mut particle = system.particle.clone();
 
         // measure particle until the bit we want to represent is set
         while set_state != bit {
             system = pa::System::default();
-            system.apparatus.set_angle(angle);
+            self.protocol.orient(&mut system.apparatus, basis);
             set_state = system.measure();
             particle = system.particle.clone();
         }
@@ -107,7 +383,7 @@ impl Party {
         let mut basis_vec = vec![];
 
         for _bit in 0..len {
-            basis_vec.push(rand::rng().random_bool(0.5));
+            basis_vec.push(rand::rng().random_range(0..self.protocol.no_bases()));
         }
         self.basis_vec = Some(basis_vec);
     }
@@ -117,6 +393,16 @@ impl Party {
 mod tests {
 
     use super::*;
+    use crate::module::eavesdropper::{
+        Breidbart, InterceptResend, PartialInterception, PhaseCovariantCloner,
+        PhotonNumberSplitting,
+    };
+
+    fn run_with_eve(eve: Box<dyn Eavesdropper>, len: usize) -> EavesdropReport {
+        let mut channel = QuantumChannel::new("Alice", "Bob").with_tap(eve);
+        let (alice, bob) = exchange_key_over(Protocol::BB84, len, &mut channel);
+        eavesdrop_report(&alice, &bob, channel.tap().unwrap())
+    }
 
     #[test]
     fn test_bb84_with_eve() {
@@ -182,4 +468,72 @@ mod tests {
         // if noone intercepted there should be agreement
         assert!(bob.compare_msg_bits(alice.share_n_bits()));
     }
+
+    #[test]
+    fn test_bb84_eavesdropper_strategies() {
+        let bases = vec![basis_angle(0), basis_angle(1)];
+        let len = 2000;
+
+        // intercept resend: 25% errors, eve knows half the key
+        let report = run_with_eve(Box::new(InterceptResend::new(bases.clone())), len);
+        assert!((report.qber - 0.25).abs() < 0.06);
+        assert!((report.eve_information - 0.5).abs() < 0.1);
+
+        // attacking half the qubits halves the errors
+        let eve = PartialInterception::new(InterceptResend::new(bases.clone()), 0.5);
+        let report = run_with_eve(Box::new(eve), len);
+        assert!((report.qber - 0.125).abs() < 0.05);
+        assert!((report.eve_information - 0.25).abs() < 0.1);
+
+        // breidbart: same errors, eve right with cos^2(pi/8) ~ 0.85 in both bases
+        let report = run_with_eve(Box::new(Breidbart::between(bases[0], bases[1])), len);
+        assert!((report.qber - 0.25).abs() < 0.06);
+        assert!((report.eve_agreement - 0.854).abs() < 0.06);
+
+        // optimal cloner: fewer errors (~14.6%) for the same agreement
+        let report = run_with_eve(Box::new(PhaseCovariantCloner::optimal()), len);
+        assert!((report.qber - 0.146).abs() < 0.05);
+        assert!((report.eve_agreement - 0.854).abs() < 0.06);
+        assert!(report.eve_information > 0.25);
+    }
+
+    #[test]
+    fn test_bb84_photon_number_splitting() {
+        // weak laser pulses with mean photon number 0.5. Eve splits off a photon of every multi
+        // photon pulse and blocks most single photons. This introduces no errors at all
+        let source = PhotonSource::new(0.5);
+        let mut channel =
+            QuantumChannel::new("Alice", "Bob").with_tap(Box::new(PhotonNumberSplitting::new(0.9)));
+        let mut alice = Party::new("Alice");
+        let mut bob = Party::new("Bob");
+        alice.set_message_and_bases((0..4000).map(|_| rand::rng().random_bool(0.5)).collect());
+
+        let pulses = channel.send_pulses(alice.generate_pulse_stream(&source));
+        bob.read_pulses(pulses);
+        alice.apply_detections(bob.share_detections());
+
+        bob.compare_bases(alice.share_basis());
+        alice.compare_bases(bob.share_basis());
+
+        let report = eavesdrop_report(&alice, &bob, channel.tap().unwrap());
+        assert_eq!(report.qber, 0.0);
+        // P(n>=2) / (P(n>=2) + 0.1 P(n=1)) ~ 0.09 / (0.09 + 0.03) of the key is known to eve
+        assert!(report.eve_information > 0.6);
+        assert_eq!(alice.sifted_key(), bob.sifted_key());
+    }
+
+    #[test]
+    fn test_bb84_noisy_lossy_channel() {
+        // depolarizing noise p flips a bit with probability p/2 in any basis
+        let mut channel = QuantumChannel::new("Alice", "Bob")
+            .with_loss(0.5)
+            .with_depolarizing(0.1);
+        let (alice, bob) = exchange_key_over(Protocol::BB84, 4000, &mut channel);
+        let stats = KeyExchangeStats::new(Protocol::BB84, 4000, &alice, &bob);
+        assert!((stats.qber - 0.05).abs() < 0.03);
+        // half the qubits are lost, half of the rest survives sifting
+        assert!((stats.sifted as f64 / 4000.0 - 0.25).abs() < 0.03);
+        // eve can follow the whole conversation
+        assert_eq!(channel.classical.transcript().len(), 3);
+    }
 }
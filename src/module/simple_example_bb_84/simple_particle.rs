@@ -1,5 +1,6 @@
 use super::utils::round_to_n_decimal_places;
-use nalgebra::{DVector, Matrix2, Matrix4, SVector, Vector2, Vector4};
+use crate::module::gates::basic::SingleInputGate;
+use nalgebra::{Complex, DVector, Matrix2, Matrix4, SVector, Vector2, Vector4};
 use rand::prelude::*;
 use std::{f64::consts::PI, vec};
 
@@ -43,27 +44,72 @@ impl System {
     pub fn probability_north(&self) -> f64 {
         // first get the vector basis * state
         let probability_north =
-            self.apparatus.basis.transpose() * self.particle.state_vector.unwrap();
+            self.apparatus.basis.adjoint() * self.particle.state_vector.unwrap();
         //take square of first element to get probability for north
-        probability_north[0].powi(2)
+        probability_north[0].norm_sqr()
     }
 }
 
 #[derive(Default, Clone, Debug)]
 pub struct Particle {
-    state_vector: Option<Vector2<f64>>,
+    state_vector: Option<Vector2<Complex<f64>>>,
     orientation: Option<bool>,
 }
 
+impl Particle {
+    /// particle that has been prepared in a known state
+    pub fn from_state(state_vector: Vector2<f64>) -> Self {
+        Self::from_complex_state(state_vector.map(|a| Complex::new(a, 0.0)))
+    }
+
+    pub fn from_complex_state(state_vector: Vector2<Complex<f64>>) -> Self {
+        Particle {
+            state_vector: Some(state_vector),
+            orientation: None,
+        }
+    }
+
+    pub fn state_vector(&self) -> Option<Vector2<Complex<f64>>> {
+        self.state_vector
+    }
+
+    /// apply a gate to a particle that has been prepared
+    pub fn apply_gate(&mut self, gate: SingleInputGate) {
+        let matrix = gate.get_matrix::<2>(0).map(|a| Complex::new(a, 0.0));
+        self.state_vector = self.state_vector.map(|state| matrix * state);
+    }
+
+    /// state vector for particles that were prepared in the x-z plane (real amplitudes up to a
+    /// global phase)
+    pub fn real_state_vector(&self) -> Option<Vector2<f64>> {
+        self.state_vector.map(|state| {
+            // remove the global phase of the larger amplitude
+            let reference = if state[0].norm() >= state[1].norm() {
+                state[0]
+            } else {
+                state[1]
+            };
+            let phase = reference.conj() / reference.norm();
+            let real = state.map(|a| a * phase);
+            assert!(
+                round_to_n_decimal_places(real[0].im, 6) == 0.0
+                    && round_to_n_decimal_places(real[1].im, 6) == 0.0,
+                "particle is not in a real state"
+            );
+            real.map(|a| a.re)
+        })
+    }
+}
+
 pub struct Apparatus {
-    basis: Matrix2<f64>,
+    basis: Matrix2<Complex<f64>>,
     angle: f64,
 }
 
 impl Default for Apparatus {
     fn default() -> Apparatus {
         Apparatus {
-            basis: Matrix2::new(1.0, 0.0, 0.0, 1.0),
+            basis: Matrix2::identity(),
             angle: 0.0,
         }
     }
@@ -85,6 +131,24 @@ impl Apparatus {
             radians.sin(),
             -1.0 * radians.sin(),
             radians.cos(),
+        )
+        .map(|a| Complex::new(a, 0.0));
+    }
+
+    /// point the apparatus in any direction on the bloch sphere. `teta` is the angle from the z
+    /// axis and `phi` the angle around it, both in degrees. set_angle(teta) is the same as
+    /// set_direction(teta, 180.0), set_direction(90.0, 90.0) points along the y axis
+    pub fn set_direction(&mut self, teta: f64, phi: f64) {
+        self.angle = teta;
+        let half = (teta / 2.0).to_radians();
+        let phase = Complex::from_polar(1.0, phi.to_radians());
+
+        // north and south are the columns of the basis
+        self.basis = Matrix2::new(
+            Complex::new(half.cos(), 0.0),
+            -phase.conj() * half.sin(),
+            phase * half.sin(),
+            Complex::new(half.cos(), 0.0),
         );
     }
 }
@@ -194,4 +258,32 @@ mod tests {
 
         println!("after 100 tests we have {trues} times N and {falses} times S");
     }
+
+    #[test]
+    fn test_apparatus_direction() {
+        // set_direction along the x-z plane agrees with set_angle
+        let mut app = Apparatus::default();
+        app.set_angle(90.0);
+        let mut app_direction = Apparatus::default();
+        app_direction.set_direction(90.0, 180.0);
+        assert!((app.basis - app_direction.basis).norm() < 1e-9);
+
+        // a particle prepared along y gives random results along z and x, but always the same
+        // result along y
+        let mut system = System::default();
+        system.apparatus.set_direction(90.0, 90.0);
+        let obs_y = system.measure();
+        for _i in 0..10 {
+            assert_eq!(system.measure(), obs_y);
+        }
+        let expected = if obs_y { 1.0 } else { 0.0 };
+        assert!((system.probability_north() - expected).abs() < 1e-9);
+
+        let prepared = system.particle.clone();
+        for teta in [0.0, 90.0] {
+            system.apparatus.set_angle(teta);
+            system.particle = prepared.clone();
+            assert!((system.probability_north() - 0.5).abs() < 1e-9);
+        }
+    }
 }
@@ -0,0 +1,68 @@
+// Six state protocol: like BB84 but with the y axis as third basis. All three bases are mutually
+// unbiased, so eve picks the wrong basis more often and causes more errors. The price is that
+// only a third of the bits survive sifting.
+use super::simple_particle::Apparatus;
+
+/// orient the apparatus along z (0), x (1) or y (2)
+pub fn orient(apparatus: &mut Apparatus, basis: usize) {
+    match basis {
+        0 => apparatus.set_angle(0.0),
+        1 => apparatus.set_angle(90.0),
+        2 => apparatus.set_direction(90.0, 90.0),
+        _ => panic!("the six state protocol only has three bases"),
+    }
+}
+
+/// asymptotic secret key fraction with one way post processing (Lo 2001),
+/// 1 - S of the bell diagonal state with eigenvalues (1 - 3q/2, q/2, q/2, q/2)
+pub fn key_fraction(qber: f64) -> f64 {
+    let plogp = |p: f64| if p > 0.0 { p * p.log2() } else { 0.0 };
+    1.0 + plogp(1.0 - 1.5 * qber) + 3.0 * plogp(qber / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::module::simple_example_bb_84::simple_example_bb_84::{exchange_key, Protocol};
+
+    #[test]
+    fn test_six_state() {
+        let stats = exchange_key(Protocol::SixState, 3000, false);
+        assert_eq!(stats.qber, 0.0);
+        assert!((stats.sifted as f64 / stats.sent as f64 - 1.0 / 3.0).abs() < 0.04);
+
+        // eve picks the right basis only a third of the time
+        let stats = exchange_key(Protocol::SixState, 3000, true);
+        assert!((stats.qber - 1.0 / 3.0).abs() < 0.05);
+    }
+
+    #[test]
+    fn test_compare_protocols() {
+        // six state tolerates more noise than BB84 (12.6% vs 11%) and its intercept resend
+        // errors are higher, but it keeps fewer bits. B92 tolerates the least noise
+        let bb84_threshold = Protocol::BB84.qber_threshold().unwrap();
+        let six_state_threshold = Protocol::SixState.qber_threshold().unwrap();
+        let b92_threshold = Protocol::B92.qber_threshold().unwrap();
+        assert!((bb84_threshold - 0.110).abs() < 0.001);
+        assert!((six_state_threshold - 0.126).abs() < 0.001);
+        assert!(b92_threshold < bb84_threshold);
+
+        let mut rates = vec![];
+        for protocol in [Protocol::BB84, Protocol::B92, Protocol::SixState] {
+            let clean = exchange_key(protocol, 2000, false);
+            let attacked = exchange_key(protocol, 2000, true);
+            println!(
+                "{:?}: key rate {:?}, qber with eve {:.3}, threshold {:?}",
+                protocol,
+                clean.key_rate(),
+                attacked.qber,
+                protocol.qber_threshold()
+            );
+            // any of the protocols notices an intercept resend attack
+            assert!(attacked.qber > 0.15);
+            assert!(attacked.qber > protocol.qber_threshold().unwrap());
+            rates.push(clean.sifted as f64 / clean.sent as f64);
+        }
+        assert!(rates[0] > rates[2] && rates[2] > rates[1]);
+    }
+}
@@ -0,0 +1,347 @@
+// Sparse state vector
+//
+// Circuits made mostly of classical reversible gates (x, cnot, toffoli, the oracles of grover and
+// deutsch-josza, arithmetic) only move amplitudes between basis states, so most of the 2^n
+// amplitudes stay zero. Here only the nonzero amplitudes are kept in a hash map keyed by the
+// basis index, which makes such circuits cheap on many more qubits than a dense vector allows.
+// Once more than `density_threshold` of all amplitudes are nonzero the map costs more than it
+// saves and the state switches to a dense StateVector for good.
+//
+// As for the dense vector qubit 0 is the most significant bit and measure returns true for |0>.
+use crate::module::gates::basic::SingleInputGate;
+use crate::module::state_vector::{complex_matrix, StateVector, C64};
+use nalgebra::Matrix2;
+use rand::prelude::*;
+use std::collections::HashMap;
+
+/// amplitudes smaller than this are dropped from the map
+const ZERO: f64 = 1e-14;
+
+/// above this many qubits the state never switches to dense
+const MAX_DENSE_QUBITS: usize = 30;
+
+#[derive(Clone, Debug)]
+enum Storage {
+    Sparse(HashMap<usize, C64>),
+    Dense(StateVector),
+}
+
+#[derive(Clone, Debug)]
+pub struct SparseState {
+    no_qubits: usize,
+    storage: Storage,
+    density_threshold: f64,
+}
+
+impl SparseState {
+    /// all qubits in |0>, switches to dense when a quarter of the amplitudes are nonzero
+    pub fn new(no_qubits: usize) -> Self {
+        assert!(no_qubits < usize::BITS as usize);
+        Self {
+            no_qubits,
+            storage: Storage::Sparse(HashMap::from([(0, C64::new(1.0, 0.0))])),
+            density_threshold: 0.25,
+        }
+    }
+
+    /// fraction of nonzero amplitudes above which the dense vector is used
+    pub fn with_density_threshold(mut self, density_threshold: f64) -> Self {
+        self.density_threshold = density_threshold;
+        self.check_density();
+        self
+    }
+
+    pub fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    pub fn is_dense(&self) -> bool {
+        matches!(self.storage, Storage::Dense(_))
+    }
+
+    /// number of stored amplitudes
+    pub fn no_amplitudes(&self) -> usize {
+        match &self.storage {
+            Storage::Sparse(amplitudes) => amplitudes.len(),
+            Storage::Dense(state) => state.amplitudes().len(),
+        }
+    }
+
+    /// fraction of the 2^n amplitudes that are nonzero
+    pub fn density(&self) -> f64 {
+        match &self.storage {
+            Storage::Sparse(amplitudes) => {
+                amplitudes.len() as f64 / 2f64.powi(self.no_qubits as i32)
+            }
+            Storage::Dense(state) => {
+                let nonzero = state
+                    .amplitudes()
+                    .iter()
+                    .filter(|amplitude| amplitude.norm() > ZERO)
+                    .count();
+                nonzero as f64 / state.amplitudes().len() as f64
+            }
+        }
+    }
+
+    /// amplitude of the basis state with index `index`
+    pub fn amplitude(&self, index: usize) -> C64 {
+        match &self.storage {
+            Storage::Sparse(amplitudes) => amplitudes
+                .get(&index)
+                .copied()
+                .unwrap_or(C64::new(0.0, 0.0)),
+            Storage::Dense(state) => state.amplitudes()[index],
+        }
+    }
+
+    /// the nonzero amplitudes sorted by basis index
+    pub fn nonzero_amplitudes(&self) -> Vec<(usize, C64)> {
+        let mut nonzero: Vec<(usize, C64)> = match &self.storage {
+            Storage::Sparse(amplitudes) => amplitudes
+                .iter()
+                .map(|(index, amplitude)| (*index, *amplitude))
+                .collect(),
+            Storage::Dense(state) => state
+                .amplitudes()
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|(_, amplitude)| amplitude.norm() > ZERO)
+                .collect(),
+        };
+        nonzero.sort_by_key(|(index, _)| *index);
+        nonzero
+    }
+
+    /// the same state as a dense vector
+    pub fn to_state_vector(&self) -> StateVector {
+        match &self.storage {
+            Storage::Sparse(amplitudes) => {
+                let mut dense = vec![C64::new(0.0, 0.0); 1 << self.no_qubits];
+                for (index, amplitude) in amplitudes {
+                    dense[*index] = *amplitude;
+                }
+                StateVector::from_amplitudes(dense)
+            }
+            Storage::Dense(state) => state.clone(),
+        }
+    }
+
+    fn mask(&self, index: usize) -> usize {
+        assert!(index < self.no_qubits);
+        1 << (self.no_qubits - 1 - index)
+    }
+
+    fn check_density(&mut self) {
+        if let Storage::Sparse(amplitudes) = &self.storage {
+            let dense_size = 2f64.powi(self.no_qubits as i32);
+            if self.no_qubits <= MAX_DENSE_QUBITS
+                && amplitudes.len() as f64 > self.density_threshold * dense_size
+            {
+                self.storage = Storage::Dense(self.to_state_vector());
+            }
+        }
+    }
+
+    /// apply `matrix` to qubit `target` for the basis states where all `controls` are |1>
+    pub fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        let target_mask = self.mask(target);
+        let control_mask = controls
+            .iter()
+            .fold(0, |mask, control| mask | self.mask(*control));
+        assert_eq!(control_mask & target_mask, 0, "target can not be a control");
+
+        let amplitudes = match &mut self.storage {
+            Storage::Dense(state) => {
+                state.apply_controlled(matrix, controls, target);
+                return;
+            }
+            Storage::Sparse(amplitudes) => amplitudes,
+        };
+        // every pair of basis states the gate mixes, by the index with the target bit cleared
+        let mut pairs: Vec<usize> = amplitudes
+            .keys()
+            .filter(|index| *index & control_mask == control_mask)
+            .map(|index| index & !target_mask)
+            .collect();
+        pairs.sort_unstable();
+        pairs.dedup();
+        let zero = C64::new(0.0, 0.0);
+        for i in pairs {
+            let a0 = amplitudes.remove(&i).unwrap_or(zero);
+            let a1 = amplitudes.remove(&(i | target_mask)).unwrap_or(zero);
+            for (index, amplitude) in [
+                (i, matrix[(0, 0)] * a0 + matrix[(0, 1)] * a1),
+                (i | target_mask, matrix[(1, 0)] * a0 + matrix[(1, 1)] * a1),
+            ] {
+                if amplitude.norm() > ZERO {
+                    amplitudes.insert(index, amplitude);
+                }
+            }
+        }
+        self.check_density();
+    }
+
+    pub fn apply(&mut self, matrix: &Matrix2<C64>, target: usize) {
+        self.apply_controlled(matrix, &[], target);
+    }
+
+    pub fn apply_gate(&mut self, gate: SingleInputGate, target: usize) {
+        self.apply(&complex_matrix(gate), target);
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) {
+        self.apply_controlled(&complex_matrix(SingleInputGate::X), &[control], target);
+    }
+
+    /// x on `target` if both controls are |1>
+    pub fn toffoli(&mut self, first: usize, second: usize, target: usize) {
+        self.apply_controlled(
+            &complex_matrix(SingleInputGate::X),
+            &[first, second],
+            target,
+        );
+    }
+
+    /// probability to measure qubit `index` as |0>
+    pub fn probability_zero(&self, index: usize) -> f64 {
+        let mask = self.mask(index);
+        match &self.storage {
+            Storage::Sparse(amplitudes) => amplitudes
+                .iter()
+                .filter(|(i, _)| *i & mask == 0)
+                .map(|(_, amplitude)| amplitude.norm_sqr())
+                .sum(),
+            Storage::Dense(state) => state.probability_zero(index),
+        }
+    }
+
+    /// project qubit `index` onto |0> (true) or |1> (false) and renormalize
+    pub fn collapse(&mut self, index: usize, zero: bool) {
+        let mask = self.mask(index);
+        match &mut self.storage {
+            Storage::Sparse(amplitudes) => {
+                amplitudes.retain(|i, _| (*i & mask == 0) == zero);
+                let norm = amplitudes
+                    .values()
+                    .map(|amplitude| amplitude.norm_sqr())
+                    .sum::<f64>()
+                    .sqrt();
+                assert!(norm > 0.0, "outcome has probability 0");
+                for amplitude in amplitudes.values_mut() {
+                    *amplitude /= C64::new(norm, 0.0);
+                }
+            }
+            Storage::Dense(state) => state.collapse(index, zero),
+        }
+    }
+
+    /// measure qubit `index` in the standard basis, true for |0>
+    pub fn measure(&mut self, index: usize) -> bool {
+        let zero = rand::rng().random_bool(self.probability_zero(index).clamp(0.0, 1.0));
+        self.collapse(index, zero);
+        zero
+    }
+
+    /// measure all qubits without changing the state, true for |0>
+    pub fn sample(&self) -> Vec<bool> {
+        let mut remaining = rand::rng().random::<f64>();
+        let nonzero = self.nonzero_amplitudes();
+        let mut chosen = nonzero.last().expect("the state is not empty").0;
+        for (index, amplitude) in &nonzero {
+            remaining -= amplitude.norm_sqr();
+            if remaining < 0.0 {
+                chosen = *index;
+                break;
+            }
+        }
+        (0..self.no_qubits)
+            .map(|qubit| chosen & self.mask(qubit) == 0)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::state_vector::rotation_y;
+
+    #[test]
+    fn test_wide_classical_circuit() {
+        // a ghz state on 60 qubits has two amplitudes, far too many qubits for a dense vector
+        let n = 60;
+        let mut state = SparseState::new(n);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        for i in 1..n {
+            state.cnot(i - 1, i);
+        }
+        state.toffoli(0, 1, n - 1);
+        assert!(!state.is_dense());
+        assert_eq!(state.no_amplitudes(), 2);
+        // the toffoli flipped the last qubit of |11..1>
+        assert!((state.amplitude((1 << n) - 2).norm_sqr() - 0.5).abs() < 1e-9);
+
+        let bits = state.sample();
+        assert!(bits[1..n - 1].iter().all(|bit| *bit == bits[0]));
+        let first = state.measure(0);
+        assert_eq!(state.measure(n - 2), first);
+        assert_eq!(state.no_amplitudes(), 1);
+    }
+
+    #[test]
+    fn test_against_state_vector() {
+        let mut rng = rand::rng();
+        let n = 5;
+        for _circuit in 0..20 {
+            let mut sparse = SparseState::new(n).with_density_threshold(0.5);
+            let mut dense = StateVector::new(n);
+            for _gate in 0..20 {
+                let a = rng.random_range(0..n);
+                let b = (a + rng.random_range(1..n)) % n;
+                match rng.random_range(0..4) {
+                    0 => {
+                        let rotation = rotation_y(rng.random::<f64>() * 3.0);
+                        sparse.apply(&rotation, a);
+                        dense.apply(&rotation, a);
+                    }
+                    1 => {
+                        sparse.apply_gate(SingleInputGate::Hadamard, a);
+                        dense.apply_gate(SingleInputGate::Hadamard, a);
+                    }
+                    2 => {
+                        sparse.cnot(a, b);
+                        dense.cnot(a, b);
+                    }
+                    _ => {
+                        let x = complex_matrix(SingleInputGate::X);
+                        let c = (b + 1..b + n).map(|c| c % n).find(|c| *c != a).unwrap();
+                        sparse.toffoli(a, c, b);
+                        dense.apply_controlled(&x, &[a, c], b);
+                    }
+                }
+            }
+            assert!(sparse.to_state_vector().fidelity(&dense) > 1.0 - 1e-9);
+            for qubit in 0..n {
+                let probability = dense.probability_zero(qubit);
+                assert!((sparse.probability_zero(qubit) - probability).abs() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dense_fallback() {
+        let mut state = SparseState::new(4);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        state.apply_gate(SingleInputGate::Hadamard, 1);
+        assert!(!state.is_dense());
+        // 8 out of 16 amplitudes is above a quarter
+        state.apply_gate(SingleInputGate::Hadamard, 2);
+        assert!(state.is_dense());
+        assert!((state.density() - 0.5).abs() < 1e-9);
+        assert!((state.probability_zero(3) - 1.0).abs() < 1e-9);
+        let zero = state.measure(0);
+        assert_eq!(state.amplitude(0).norm() > 0.0, zero);
+    }
+}
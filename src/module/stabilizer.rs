@@ -0,0 +1,301 @@
+// Stabilizer simulator after Aaronson and Gottesman (CHP)
+//
+// A state that is reached from |00..0> with clifford gates (H, S, CNOT and the paulis) is fixed by
+// n pauli products, its stabilizers. The tableau keeps those n rows plus n destabilizer rows, each
+// as x and z bits per qubit and a sign bit r. Gates update the bits of one or two columns, so
+// circuits on thousands of qubits are cheap. Rows are packed into u64 words.
+//
+// Rows 0..n are the destabilizers, n..2n the stabilizers and row 2n is scratch space for
+// deterministic measurements. As for the other simulators measure returns true for |0>.
+use crate::module::gates::basic::SingleInputGate;
+use rand::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StabilizerState {
+    no_qubits: usize,
+    words: usize,
+    x: Vec<u64>,
+    z: Vec<u64>,
+    r: Vec<bool>,
+}
+
+impl StabilizerState {
+    /// all qubits in |0>
+    pub fn new(no_qubits: usize) -> Self {
+        let words = no_qubits.div_ceil(64);
+        let rows = 2 * no_qubits + 1;
+        let mut state = Self {
+            no_qubits,
+            words,
+            x: vec![0; rows * words],
+            z: vec![0; rows * words],
+            r: vec![false; rows],
+        };
+        for qubit in 0..no_qubits {
+            state.set_x(qubit, qubit, true);
+            state.set_z(no_qubits + qubit, qubit, true);
+        }
+        state
+    }
+
+    pub fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    fn position(&self, row: usize, qubit: usize) -> (usize, u64) {
+        (row * self.words + qubit / 64, 1 << (qubit % 64))
+    }
+
+    fn x(&self, row: usize, qubit: usize) -> bool {
+        let (word, bit) = self.position(row, qubit);
+        self.x[word] & bit != 0
+    }
+
+    fn z(&self, row: usize, qubit: usize) -> bool {
+        let (word, bit) = self.position(row, qubit);
+        self.z[word] & bit != 0
+    }
+
+    fn set_x(&mut self, row: usize, qubit: usize, value: bool) {
+        let (word, bit) = self.position(row, qubit);
+        if value {
+            self.x[word] |= bit;
+        } else {
+            self.x[word] &= !bit;
+        }
+    }
+
+    fn set_z(&mut self, row: usize, qubit: usize, value: bool) {
+        let (word, bit) = self.position(row, qubit);
+        if value {
+            self.z[word] |= bit;
+        } else {
+            self.z[word] &= !bit;
+        }
+    }
+
+    pub fn h(&mut self, qubit: usize) {
+        for row in 0..2 * self.no_qubits {
+            let (x, z) = (self.x(row, qubit), self.z(row, qubit));
+            self.r[row] ^= x && z;
+            self.set_x(row, qubit, z);
+            self.set_z(row, qubit, x);
+        }
+    }
+
+    pub fn s(&mut self, qubit: usize) {
+        for row in 0..2 * self.no_qubits {
+            let (x, z) = (self.x(row, qubit), self.z(row, qubit));
+            self.r[row] ^= x && z;
+            self.set_z(row, qubit, x ^ z);
+        }
+    }
+
+    pub fn s_dagger(&mut self, qubit: usize) {
+        self.s(qubit);
+        self.z_gate(qubit);
+    }
+
+    pub fn cnot(&mut self, control: usize, target: usize) {
+        assert_ne!(control, target);
+        for row in 0..2 * self.no_qubits {
+            let (xc, zc) = (self.x(row, control), self.z(row, control));
+            let (xt, zt) = (self.x(row, target), self.z(row, target));
+            self.r[row] ^= xc && zt && (xt == zc);
+            self.set_x(row, target, xt ^ xc);
+            self.set_z(row, control, zc ^ zt);
+        }
+    }
+
+    /// a pauli only flips the signs of the rows it anticommutes with
+    fn pauli(&mut self, qubit: usize, x: bool, z: bool) {
+        for row in 0..2 * self.no_qubits {
+            self.r[row] ^= (x && self.z(row, qubit)) ^ (z && self.x(row, qubit));
+        }
+    }
+
+    pub fn x_gate(&mut self, qubit: usize) {
+        self.pauli(qubit, true, false);
+    }
+
+    pub fn y_gate(&mut self, qubit: usize) {
+        self.pauli(qubit, true, true);
+    }
+
+    pub fn z_gate(&mut self, qubit: usize) {
+        self.pauli(qubit, false, true);
+    }
+
+    /// the gates of gates::basic, the real y differs from the pauli y only by a global phase
+    pub fn apply_gate(&mut self, gate: SingleInputGate, qubit: usize) {
+        match gate {
+            SingleInputGate::X => self.x_gate(qubit),
+            SingleInputGate::Y => self.y_gate(qubit),
+            SingleInputGate::Z => self.z_gate(qubit),
+            SingleInputGate::Hadamard => self.h(qubit),
+        }
+    }
+
+    /// multiply row `source` into row `target`, keeping track of the sign
+    fn rowsum(&mut self, target: usize, source: usize) {
+        // i^phase picked up from the single qubit products, in units of i
+        let mut phase: i64 = 2 * i64::from(self.r[target]) + 2 * i64::from(self.r[source]);
+        for word in 0..self.words {
+            let (x1, z1) = (
+                self.x[source * self.words + word],
+                self.z[source * self.words + word],
+            );
+            let (x2, z2) = (
+                self.x[target * self.words + word],
+                self.z[target * self.words + word],
+            );
+            let (y, x_only, z_only) = (x1 & z1, x1 & !z1, !x1 & z1);
+            let plus = (y & z2 & !x2) | (x_only & z2 & x2) | (z_only & x2 & !z2);
+            let minus = (y & x2 & !z2) | (x_only & z2 & !x2) | (z_only & x2 & z2);
+            phase += i64::from(plus.count_ones()) - i64::from(minus.count_ones());
+            self.x[target * self.words + word] ^= x1;
+            self.z[target * self.words + word] ^= z1;
+        }
+        self.r[target] = phase.rem_euclid(4) == 2;
+    }
+
+    /// stabilizer row that anticommutes with z on `qubit`, if there is one the outcome is random
+    fn random_row(&self, qubit: usize) -> Option<usize> {
+        (self.no_qubits..2 * self.no_qubits).find(|row| self.x(*row, qubit))
+    }
+
+    /// the outcome of measuring `qubit` if it is certain, None if it is random. Does not change
+    /// the state
+    pub fn peek(&mut self, qubit: usize) -> Option<bool> {
+        if self.random_row(qubit).is_some() {
+            return None;
+        }
+        Some(self.deterministic_outcome(qubit))
+    }
+
+    fn deterministic_outcome(&mut self, qubit: usize) -> bool {
+        let scratch = 2 * self.no_qubits;
+        for word in 0..self.words {
+            self.x[scratch * self.words + word] = 0;
+            self.z[scratch * self.words + word] = 0;
+        }
+        self.r[scratch] = false;
+        for row in 0..self.no_qubits {
+            if self.x(row, qubit) {
+                self.rowsum(scratch, row + self.no_qubits);
+            }
+        }
+        !self.r[scratch]
+    }
+
+    /// measure `qubit` in the standard basis, true for |0>
+    pub fn measure(&mut self, qubit: usize) -> bool {
+        let Some(p) = self.random_row(qubit) else {
+            return self.deterministic_outcome(qubit);
+        };
+        for row in 0..2 * self.no_qubits {
+            if row != p && self.x(row, qubit) {
+                self.rowsum(row, p);
+            }
+        }
+        // the old stabilizer becomes a destabilizer, z on the qubit with the outcome as sign
+        let destabilizer = p - self.no_qubits;
+        for word in 0..self.words {
+            self.x[destabilizer * self.words + word] = self.x[p * self.words + word];
+            self.z[destabilizer * self.words + word] = self.z[p * self.words + word];
+            self.x[p * self.words + word] = 0;
+            self.z[p * self.words + word] = 0;
+        }
+        self.r[destabilizer] = self.r[p];
+        self.set_z(p, qubit, true);
+        let zero = rand::rng().random_bool(0.5);
+        self.r[p] = !zero;
+        zero
+    }
+
+    /// measure and flip back to |0>
+    pub fn reset(&mut self, qubit: usize) {
+        if !self.measure(qubit) {
+            self.x_gate(qubit);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::state_vector::{phase, StateVector};
+
+    #[test]
+    fn test_large_ghz() {
+        let n = 1500;
+        let mut state = StabilizerState::new(n);
+        state.h(0);
+        for i in 1..n {
+            state.cnot(i - 1, i);
+        }
+        assert_eq!(state.peek(700), None);
+        let first = state.measure(0);
+        for i in 1..n {
+            assert_eq!(state.peek(i), Some(first));
+        }
+        assert_eq!(state.measure(n - 1), first);
+    }
+
+    #[test]
+    fn test_gates() {
+        let mut state = StabilizerState::new(2);
+        // s s is z, h z h is x
+        state.h(0);
+        state.s(0);
+        state.s(0);
+        state.h(0);
+        assert!(!state.measure(0));
+        state.y_gate(1);
+        assert!(!state.measure(1));
+        state.reset(1);
+        assert!(state.measure(1));
+    }
+
+    #[test]
+    fn test_random_circuits_against_state_vector() {
+        let mut rng = rand::rng();
+        for _circuit in 0..30 {
+            let n = 4;
+            let mut tableau = StabilizerState::new(n);
+            let mut vector = StateVector::new(n);
+            for _gate in 0..25 {
+                let a = rng.random_range(0..n);
+                match rng.random_range(0..4) {
+                    0 => {
+                        tableau.h(a);
+                        vector.apply_gate(SingleInputGate::Hadamard, a);
+                    }
+                    1 => {
+                        tableau.s(a);
+                        vector.apply(&phase(std::f64::consts::FRAC_PI_2), a);
+                    }
+                    2 => {
+                        tableau.y_gate(a);
+                        vector.apply_gate(SingleInputGate::Y, a);
+                    }
+                    _ => {
+                        let b = (a + rng.random_range(1..n)) % n;
+                        tableau.cnot(a, b);
+                        vector.cnot(a, b);
+                    }
+                }
+            }
+            for qubit in 0..n {
+                let probability = vector.probability_zero(qubit);
+                match tableau.peek(qubit) {
+                    None => assert!((probability - 0.5).abs() < 1e-9),
+                    Some(zero) => assert!((probability - f64::from(u8::from(zero))).abs() < 1e-9),
+                }
+                let outcome = tableau.measure(qubit);
+                vector.collapse(qubit, outcome);
+            }
+        }
+    }
+}
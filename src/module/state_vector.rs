@@ -0,0 +1,442 @@
+// Complex state vector of n qubits
+//
+// Unlike EntangledParticleN the number of qubits is chosen at runtime and amplitudes are complex,
+// gates are applied to the amplitude pairs directly instead of building the full matrix. As
+// everywhere else particle 0 is the most significant bit and a measurement returns true for |0>.
+//
+// With the `parallel` feature gates, probabilities and the renormalization after a measurement
+// are split over threads with rayon once the register has at least `parallel_threshold` qubits.
+// Below that the threads cost more than they save. Both paths give the same amplitudes.
+use crate::module::gates::basic::SingleInputGate;
+use nalgebra::{Complex, DMatrix, DVector, Matrix2, Vector2};
+use rand::prelude::*;
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+pub type C64 = Complex<f64>;
+
+/// default number of qubits from which the parallel kernels are used
+#[cfg(feature = "parallel")]
+pub const PARALLEL_THRESHOLD: usize = 14;
+
+/// 2x2 complex matrix of a real gate
+pub fn complex_matrix(gate: SingleInputGate) -> Matrix2<C64> {
+    gate.get_matrix::<2>(0).map(|value| C64::new(value, 0.0))
+}
+
+/// rotation around the y axis of the bloch sphere, real
+pub fn rotation_y(angle: f64) -> Matrix2<C64> {
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    Matrix2::new(cos, -sin, sin, cos).map(|value| C64::new(value, 0.0))
+}
+
+/// rotation around the x axis of the bloch sphere
+pub fn rotation_x(angle: f64) -> Matrix2<C64> {
+    let (sin, cos) = (angle / 2.0).sin_cos();
+    Matrix2::new(
+        C64::new(cos, 0.0),
+        C64::new(0.0, -sin),
+        C64::new(0.0, -sin),
+        C64::new(cos, 0.0),
+    )
+}
+
+/// rotation around the z axis of the bloch sphere, diag(e^(-i angle / 2), e^(i angle / 2))
+pub fn rotation_z(angle: f64) -> Matrix2<C64> {
+    Matrix2::new(
+        C64::from_polar(1.0, -angle / 2.0),
+        C64::new(0.0, 0.0),
+        C64::new(0.0, 0.0),
+        C64::from_polar(1.0, angle / 2.0),
+    )
+}
+
+/// diag(1, e^(i angle)), the s gate is phase(pi / 2)
+pub fn phase(angle: f64) -> Matrix2<C64> {
+    Matrix2::new(
+        C64::new(1.0, 0.0),
+        C64::new(0.0, 0.0),
+        C64::new(0.0, 0.0),
+        C64::from_polar(1.0, angle),
+    )
+}
+
+/// e^(-i hamiltonian time) for a hermitian matrix, from its eigendecomposition
+pub fn evolution_operator(hamiltonian: &DMatrix<C64>, time: f64) -> DMatrix<C64> {
+    let eigen = hamiltonian.clone().symmetric_eigen();
+    let phases = eigen
+        .eigenvalues
+        .map(|energy| C64::from_polar(1.0, -energy * time));
+    &eigen.eigenvectors * DMatrix::from_diagonal(&phases) * eigen.eigenvectors.adjoint()
+}
+
+#[derive(Clone, Debug)]
+pub struct StateVector {
+    no_qubits: usize,
+    amplitudes: DVector<C64>,
+    #[cfg(feature = "parallel")]
+    parallel_threshold: usize,
+}
+
+// the threshold only decides how the kernels run, not which state this is
+impl PartialEq for StateVector {
+    fn eq(&self, other: &Self) -> bool {
+        self.no_qubits == other.no_qubits && self.amplitudes == other.amplitudes
+    }
+}
+
+impl StateVector {
+    /// all qubits in |0>
+    pub fn new(no_qubits: usize) -> Self {
+        let mut amplitudes = DVector::zeros(1 << no_qubits);
+        amplitudes[0] = C64::new(1.0, 0.0);
+        Self {
+            no_qubits,
+            amplitudes,
+            #[cfg(feature = "parallel")]
+            parallel_threshold: PARALLEL_THRESHOLD,
+        }
+    }
+
+    pub fn from_amplitudes(amplitudes: Vec<C64>) -> Self {
+        assert!(amplitudes.len().is_power_of_two());
+        let res = Self {
+            no_qubits: amplitudes.len().trailing_zeros() as usize,
+            amplitudes: DVector::from_vec(amplitudes),
+            #[cfg(feature = "parallel")]
+            parallel_threshold: PARALLEL_THRESHOLD,
+        };
+        assert!(
+            (res.amplitudes.norm() - 1.0).abs() < 1e-6,
+            "state is not normalized"
+        );
+        res
+    }
+
+    /// product state of single qubit states
+    pub fn product(states: &[Vector2<C64>]) -> Self {
+        let mut amplitudes = DVector::from_element(1, C64::new(1.0, 0.0));
+        for state in states {
+            amplitudes = amplitudes.kronecker(state);
+        }
+        Self::from_amplitudes(amplitudes.as_slice().to_vec())
+    }
+
+    /// use the parallel kernels from `qubits` qubits on, usize::MAX keeps everything serial
+    #[cfg(feature = "parallel")]
+    pub fn with_parallel_threshold(mut self, qubits: usize) -> Self {
+        self.parallel_threshold = qubits;
+        self
+    }
+
+    #[cfg(feature = "parallel")]
+    fn is_parallel(&self) -> bool {
+        self.no_qubits >= self.parallel_threshold
+    }
+
+    pub fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    pub fn amplitudes(&self) -> &DVector<C64> {
+        &self.amplitudes
+    }
+
+    fn mask(&self, index: usize) -> usize {
+        assert!(index < self.no_qubits);
+        1 << (self.no_qubits - 1 - index)
+    }
+
+    /// apply `matrix` to qubit `target` for the basis states where all `controls` are |1>
+    pub fn apply_controlled(&mut self, matrix: &Matrix2<C64>, controls: &[usize], target: usize) {
+        let target_mask = self.mask(target);
+        let control_mask = controls
+            .iter()
+            .fold(0, |mask, control| mask | self.mask(*control));
+        assert_eq!(control_mask & target_mask, 0, "target can not be a control");
+
+        #[cfg(feature = "parallel")]
+        if self.is_parallel() {
+            parallel_apply(
+                self.amplitudes.as_mut_slice(),
+                matrix,
+                control_mask,
+                target_mask,
+            );
+            return;
+        }
+        for i in 0..self.amplitudes.len() {
+            if i & target_mask != 0 || i & control_mask != control_mask {
+                continue;
+            }
+            let (a0, a1) = (self.amplitudes[i], self.amplitudes[i | target_mask]);
+            self.amplitudes[i] = matrix[(0, 0)] * a0 + matrix[(0, 1)] * a1;
+            self.amplitudes[i | target_mask] = matrix[(1, 0)] * a0 + matrix[(1, 1)] * a1;
+        }
+    }
+
+    pub fn apply(&mut self, matrix: &Matrix2<C64>, target: usize) {
+        self.apply_controlled(matrix, &[], target);
+    }
+
+    pub fn apply_gate(&mut self, gate: SingleInputGate, target: usize) {
+        self.apply(&complex_matrix(gate), target);
+    }
+
+    /// cnot between any two qubits, they do not need to be adjacent
+    pub fn cnot(&mut self, control: usize, target: usize) {
+        self.apply_controlled(&complex_matrix(SingleInputGate::X), &[control], target);
+    }
+
+    /// apply the 2^k x 2^k `matrix` to the k `targets`, the first is the most significant bit,
+    /// for the basis states where all `controls` are |1>
+    pub fn apply_controlled_unitary(
+        &mut self,
+        matrix: &DMatrix<C64>,
+        controls: &[usize],
+        targets: &[usize],
+    ) {
+        let k = targets.len();
+        assert!(matrix.is_square() && matrix.nrows() == 1 << k);
+        let target_masks: Vec<usize> = targets.iter().map(|target| self.mask(*target)).collect();
+        let target_mask = target_masks.iter().fold(0, |all, mask| all | mask);
+        assert_eq!(target_mask.count_ones() as usize, k, "targets repeat");
+        let control_mask = controls
+            .iter()
+            .fold(0, |mask, control| mask | self.mask(*control));
+        assert_eq!(control_mask & target_mask, 0, "target can not be a control");
+
+        // the offsets of the 2^k basis states of the targets from one with all targets |0>
+        let offsets: Vec<usize> = (0..1usize << k)
+            .map(|j| {
+                (0..k)
+                    .filter(|bit| (j >> (k - 1 - bit)) & 1 == 1)
+                    .fold(0, |offset, bit| offset | target_masks[bit])
+            })
+            .collect();
+        let mut block = DVector::zeros(1 << k);
+        for i in 0..self.amplitudes.len() {
+            if i & target_mask != 0 || i & control_mask != control_mask {
+                continue;
+            }
+            for (j, offset) in offsets.iter().enumerate() {
+                block[j] = self.amplitudes[i | offset];
+            }
+            let result = matrix * &block;
+            for (j, offset) in offsets.iter().enumerate() {
+                self.amplitudes[i | offset] = result[j];
+            }
+        }
+    }
+
+    pub fn apply_unitary(&mut self, matrix: &DMatrix<C64>, targets: &[usize]) {
+        self.apply_controlled_unitary(matrix, &[], targets);
+    }
+
+    /// multiply the amplitude of every basis state by `diagonal(index)`, for oracles and
+    /// reflections
+    pub fn apply_diagonal(&mut self, diagonal: impl Fn(usize) -> C64) {
+        for (i, amplitude) in self.amplitudes.iter_mut().enumerate() {
+            *amplitude *= diagonal(i);
+        }
+    }
+
+    /// probability to measure qubit `index` as |0>
+    pub fn probability_zero(&self, index: usize) -> f64 {
+        let mask = self.mask(index);
+        #[cfg(feature = "parallel")]
+        if self.is_parallel() {
+            return self
+                .amplitudes
+                .as_slice()
+                .par_iter()
+                .enumerate()
+                .filter(|(i, _)| i & mask == 0)
+                .map(|(_, amplitude)| amplitude.norm_sqr())
+                .sum();
+        }
+        self.amplitudes
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i & mask == 0)
+            .map(|(_, amplitude)| amplitude.norm_sqr())
+            .sum()
+    }
+
+    /// project qubit `index` onto |0> (true) or |1> (false) and renormalize
+    pub fn collapse(&mut self, index: usize, zero: bool) {
+        let mask = self.mask(index);
+        #[cfg(feature = "parallel")]
+        if self.is_parallel() {
+            let amplitudes = self.amplitudes.as_mut_slice();
+            amplitudes
+                .par_iter_mut()
+                .enumerate()
+                .filter(|(i, _)| (i & mask == 0) != zero)
+                .for_each(|(_, amplitude)| *amplitude = C64::new(0.0, 0.0));
+            let norm = amplitudes
+                .par_iter()
+                .map(|amplitude| amplitude.norm_sqr())
+                .sum::<f64>()
+                .sqrt();
+            assert!(norm > 0.0, "outcome has probability 0");
+            amplitudes
+                .par_iter_mut()
+                .for_each(|amplitude| *amplitude /= C64::new(norm, 0.0));
+            return;
+        }
+        for (i, amplitude) in self.amplitudes.iter_mut().enumerate() {
+            if (i & mask == 0) != zero {
+                *amplitude = C64::new(0.0, 0.0);
+            }
+        }
+        let norm = self.amplitudes.norm();
+        assert!(norm > 0.0, "outcome has probability 0");
+        self.amplitudes /= C64::new(norm, 0.0);
+    }
+
+    /// measure qubit `index` in the standard basis, true for |0>
+    pub fn measure(&mut self, index: usize) -> bool {
+        let zero = rand::rng().random_bool(self.probability_zero(index).clamp(0.0, 1.0));
+        self.collapse(index, zero);
+        zero
+    }
+
+    /// state of qubit `index`, None if it is entangled with the others
+    pub fn qubit_state(&self, index: usize) -> Option<Vector2<C64>> {
+        let mask = self.mask(index);
+        let pairs: Vec<Vector2<C64>> = (0..self.amplitudes.len())
+            .filter(|i| i & mask == 0)
+            .map(|i| Vector2::new(self.amplitudes[i], self.amplitudes[i | mask]))
+            .collect();
+        let largest = pairs.iter().max_by(|a, b| a.norm().total_cmp(&b.norm()))?;
+        let state = largest.normalize();
+        for pair in &pairs {
+            let rest = pair - state * state.dotc(pair);
+            if rest.norm() > 1e-6 {
+                return None;
+            }
+        }
+        Some(state)
+    }
+
+    /// |<self|other>|^2
+    pub fn fidelity(&self, other: &StateVector) -> f64 {
+        self.amplitudes.dotc(&other.amplitudes).norm_sqr()
+    }
+}
+
+/// the loop of apply_controlled on threads. Blocks of 2 * target_mask amplitudes hold whole pairs
+/// (i, i | target_mask), for low targets there are many blocks to spread over the threads, for
+/// high targets few large ones whose halves are zipped in parallel
+#[cfg(feature = "parallel")]
+fn parallel_apply(
+    amplitudes: &mut [C64],
+    matrix: &Matrix2<C64>,
+    control_mask: usize,
+    target_mask: usize,
+) {
+    let update = |i: usize, a0: &mut C64, a1: &mut C64| {
+        if i & control_mask == control_mask {
+            let (old0, old1) = (*a0, *a1);
+            *a0 = matrix[(0, 0)] * old0 + matrix[(0, 1)] * old1;
+            *a1 = matrix[(1, 0)] * old0 + matrix[(1, 1)] * old1;
+        }
+    };
+    let block = 2 * target_mask;
+    let no_blocks = amplitudes.len() / block;
+    if no_blocks >= target_mask {
+        amplitudes
+            .par_chunks_mut(block)
+            .enumerate()
+            .for_each(|(b, chunk)| {
+                let (low, high) = chunk.split_at_mut(target_mask);
+                for (j, (a0, a1)) in low.iter_mut().zip(high).enumerate() {
+                    update(b * block + j, a0, a1);
+                }
+            });
+    } else {
+        for (b, chunk) in amplitudes.chunks_mut(block).enumerate() {
+            let (low, high) = chunk.split_at_mut(target_mask);
+            low.par_iter_mut()
+                .zip(high.par_iter_mut())
+                .enumerate()
+                .for_each(|(j, (a0, a1))| update(b * block + j, a0, a1));
+        }
+    }
+}
+
+/// |<a|b>|^2 of two single qubit states
+pub fn qubit_fidelity(a: &Vector2<C64>, b: &Vector2<C64>) -> f64 {
+    a.dotc(b).norm_sqr()
+}
+
+/// random pure state, uniform on the bloch sphere
+pub fn random_qubit() -> Vector2<C64> {
+    let mut rng = rand::rng();
+    let teta = (1.0 - 2.0 * rng.random::<f64>()).acos();
+    let phi = 2.0 * std::f64::consts::PI * rng.random::<f64>();
+    Vector2::new(
+        C64::new((teta / 2.0).cos(), 0.0),
+        C64::from_polar((teta / 2.0).sin(), phi),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_bell_state() {
+        // cnot over a qubit in between
+        let mut state = StateVector::new(3);
+        state.apply_gate(SingleInputGate::Hadamard, 0);
+        state.cnot(0, 2);
+        assert!((state.probability_zero(2) - 0.5).abs() < 1e-9);
+        assert_eq!(state.qubit_state(0), None);
+        assert!(state.qubit_state(1).is_some());
+
+        let first = state.measure(0);
+        assert_eq!(state.measure(2), first);
+        assert!(state.qubit_state(2).is_some());
+    }
+
+    #[test]
+    fn test_product_state() {
+        let qubit = random_qubit();
+        let zero = Vector2::new(C64::new(1.0, 0.0), C64::new(0.0, 0.0));
+        let state = StateVector::product(&[zero, qubit]);
+        assert!(qubit_fidelity(&state.qubit_state(1).unwrap(), &qubit) > 1.0 - 1e-9);
+        assert!(state.fidelity(&state) > 1.0 - 1e-9);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        let n = 12;
+        let mut rng = rand::rng();
+        let mut parallel = StateVector::new(n).with_parallel_threshold(0);
+        let mut serial = StateVector::new(n).with_parallel_threshold(usize::MAX);
+        for _gate in 0..200 {
+            let a = rng.random_range(0..n);
+            let b = (a + rng.random_range(1..n)) % n;
+            if rng.random_bool(0.5) {
+                let rotation = rotation_y(rng.random::<f64>() * 3.0) * phase(rng.random::<f64>());
+                parallel.apply(&rotation, a);
+                serial.apply(&rotation, a);
+            } else {
+                parallel.cnot(a, b);
+                serial.cnot(a, b);
+            }
+        }
+        assert_eq!(parallel, serial);
+        for qubit in 0..n {
+            let difference = parallel.probability_zero(qubit) - serial.probability_zero(qubit);
+            assert!(difference.abs() < 1e-12);
+        }
+        parallel.collapse(3, true);
+        serial.collapse(3, true);
+        assert!((parallel.amplitudes() - serial.amplitudes()).norm() < 1e-12);
+    }
+}
@@ -0,0 +1,318 @@
+// Rotated surface code memory experiment
+//
+// d x d data qubits sit on a grid, the stabilizers on the plaquettes between them: weight four
+// ones inside, alternating x and z, and weight two ones on the boundary, x type on the top and
+// bottom and z type on the left and right. Every stabilizer has its own ancilla.
+//
+// The memory experiment keeps logical |0> for a number of rounds of syndrome extraction under
+// circuit level noise, then measures all data qubits. Only bit flips can change the logical z
+// observable (z on the top row), so the z type detectors are decoded with the union-find decoder:
+// a detector fires when a z stabilizer changes between rounds, data qubit errors connect the
+// detectors of one round, measurement errors the same detector in neighbouring rounds.
+//
+// The decoding graph is the phenomenological one: it has no diagonal edges for errors between
+// the cnots of a round and no hook edges for ancilla errors spreading to two data qubits. The
+// decoder still sees those faults, as longer chains, so the circuit level threshold it reaches
+// is below the one of a decoder with the full circuit level graph.
+use crate::module::error_correction::Stabilizer;
+use crate::module::stabilizer::StabilizerState;
+use crate::module::union_find_decoder::DecodingGraph;
+use rand::prelude::*;
+
+#[derive(Clone, Debug)]
+pub struct RotatedSurfaceCode {
+    distance: usize,
+    stabilizers: Vec<Stabilizer>,
+}
+
+impl RotatedSurfaceCode {
+    pub fn new(distance: usize) -> Self {
+        assert!(distance >= 3 && distance % 2 == 1, "distance has to be odd");
+        let d = distance;
+        let mut stabilizers = vec![];
+        for i in 0..=d {
+            for j in 0..=d {
+                let x_type = (i + j) % 2 == 0;
+                let on_top_or_bottom = i == 0 || i == d;
+                let on_left_or_right = j == 0 || j == d;
+                let keep = match (on_top_or_bottom, on_left_or_right) {
+                    (false, false) => true,
+                    (true, false) => x_type,
+                    (false, true) => !x_type,
+                    // corners
+                    (true, true) => false,
+                };
+                if !keep {
+                    continue;
+                }
+                let corner = |di: usize, dj: usize| {
+                    let (row, column) = ((i + di).checked_sub(1)?, (j + dj).checked_sub(1)?);
+                    (row < d && column < d).then_some(row * d + column)
+                };
+                let (nw, ne, sw, se) = (corner(0, 0), corner(0, 1), corner(1, 0), corner(1, 1));
+                // the order of the cnots keeps errors on the ancilla from spreading along the
+                // logical operators
+                if x_type {
+                    stabilizers.push(Stabilizer::X(
+                        [nw, ne, sw, se].into_iter().flatten().collect(),
+                    ));
+                } else {
+                    stabilizers.push(Stabilizer::Z(
+                        [nw, sw, ne, se].into_iter().flatten().collect(),
+                    ));
+                }
+            }
+        }
+        assert_eq!(stabilizers.len(), d * d - 1);
+        Self {
+            distance,
+            stabilizers,
+        }
+    }
+
+    pub fn distance(&self) -> usize {
+        self.distance
+    }
+
+    pub fn no_data_qubits(&self) -> usize {
+        self.distance * self.distance
+    }
+
+    pub fn stabilizers(&self) -> &[Stabilizer] {
+        &self.stabilizers
+    }
+
+    /// data qubits of the logical z operator, the top row
+    pub fn logical_z(&self) -> Vec<usize> {
+        (0..self.distance).collect()
+    }
+
+    fn z_stabilizers(&self) -> Vec<&Vec<usize>> {
+        self.stabilizers
+            .iter()
+            .filter_map(|stabilizer| match stabilizer {
+                Stabilizer::Z(qubits) => Some(qubits),
+                Stabilizer::X(_) => None,
+            })
+            .collect()
+    }
+
+    /// detectors for every z stabilizer in `rounds` rounds and the final data measurement, with
+    /// the edges of the phenomenological noise model only: data qubit errors within a round
+    /// and measurement errors between rounds
+    pub fn decoding_graph(&self, rounds: usize) -> DecodingGraph {
+        let z_stabilizers = self.z_stabilizers();
+        let layer = z_stabilizers.len();
+        let logical = self.logical_z();
+        let mut graph = DecodingGraph::new(layer * (rounds + 1));
+        for t in 0..=rounds {
+            for qubit in 0..self.no_data_qubits() {
+                let detectors: Vec<usize> = (0..layer)
+                    .filter(|k| z_stabilizers[*k].contains(&qubit))
+                    .map(|k| t * layer + k)
+                    .collect();
+                let flips = logical.contains(&qubit);
+                match detectors[..] {
+                    [a] => graph.add_edge(a, graph.boundary(), flips),
+                    [a, b] => graph.add_edge(a, b, flips),
+                    _ => unreachable!("every data qubit is in one or two z stabilizers"),
+                }
+            }
+            if t < rounds {
+                for k in 0..layer {
+                    graph.add_edge(t * layer + k, (t + 1) * layer + k, false);
+                }
+            }
+        }
+        graph
+    }
+}
+
+/// a random pauli with probability p
+fn depolarize(state: &mut StabilizerState, qubit: usize, p: f64, rng: &mut impl Rng) {
+    if p > 0.0 && rng.random_bool(p) {
+        match rng.random_range(0..3) {
+            0 => state.x_gate(qubit),
+            1 => state.y_gate(qubit),
+            _ => state.z_gate(qubit),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct MemoryReport {
+    pub shots: usize,
+    pub failures: usize,
+    pub rounds: usize,
+}
+
+impl MemoryReport {
+    /// fraction of shots that ended with the wrong logical state
+    pub fn logical_failure_rate(&self) -> f64 {
+        self.failures as f64 / self.shots as f64
+    }
+
+    /// failure rate of a single round: after r rounds the failure rate is (1 - (1 - 2e)^r) / 2
+    pub fn failure_rate_per_round(&self) -> f64 {
+        let total = self.logical_failure_rate().min(0.5);
+        (1.0 - (1.0 - 2.0 * total).powf(1.0 / self.rounds as f64)) / 2.0
+    }
+}
+
+pub struct MemoryExperiment {
+    code: RotatedSurfaceCode,
+    rounds: usize,
+    /// probability of a pauli error after every gate, of a flipped measurement or reset and
+    /// of an error on every data qubit per round
+    noise: f64,
+    graph: DecodingGraph,
+}
+
+impl MemoryExperiment {
+    pub fn new(distance: usize, rounds: usize, noise: f64) -> Self {
+        assert!(rounds > 0);
+        let code = RotatedSurfaceCode::new(distance);
+        let graph = code.decoding_graph(rounds);
+        Self {
+            code,
+            rounds,
+            noise,
+            graph,
+        }
+    }
+
+    /// one run of the memory, returns true if the logical qubit was lost
+    pub fn run_shot(&self) -> bool {
+        let mut rng = rand::rng();
+        let p = self.noise;
+        let no_data = self.code.no_data_qubits();
+        let mut state = StabilizerState::new(no_data + self.code.stabilizers().len());
+        let mut defects = vec![];
+        let mut previous = vec![false; self.code.z_stabilizers().len()];
+
+        for _round in 0..self.rounds {
+            for qubit in 0..no_data {
+                depolarize(&mut state, qubit, p, &mut rng);
+            }
+            let mut z_outcomes = vec![];
+            for (index, stabilizer) in self.code.stabilizers().iter().enumerate() {
+                let ancilla = no_data + index;
+                let minus = match stabilizer {
+                    Stabilizer::Z(qubits) => {
+                        for qubit in qubits {
+                            state.cnot(*qubit, ancilla);
+                            depolarize(&mut state, *qubit, p, &mut rng);
+                            depolarize(&mut state, ancilla, p, &mut rng);
+                        }
+                        !state.measure(ancilla)
+                    }
+                    Stabilizer::X(qubits) => {
+                        state.h(ancilla);
+                        depolarize(&mut state, ancilla, p, &mut rng);
+                        for qubit in qubits {
+                            state.cnot(ancilla, *qubit);
+                            depolarize(&mut state, *qubit, p, &mut rng);
+                            depolarize(&mut state, ancilla, p, &mut rng);
+                        }
+                        state.h(ancilla);
+                        depolarize(&mut state, ancilla, p, &mut rng);
+                        !state.measure(ancilla)
+                    }
+                };
+                // reset the ancilla, which can fail as well
+                if minus ^ (p > 0.0 && rng.random_bool(p)) {
+                    state.x_gate(ancilla);
+                }
+                let recorded = minus ^ (p > 0.0 && rng.random_bool(p));
+                if let Stabilizer::Z(_) = stabilizer {
+                    z_outcomes.push(recorded);
+                }
+            }
+            for (k, outcome) in z_outcomes.into_iter().enumerate() {
+                defects.push(outcome ^ previous[k]);
+                previous[k] = outcome;
+            }
+        }
+
+        // the final data measurement gives the last layer of z stabilizers and the observable
+        let data: Vec<bool> = (0..no_data)
+            .map(|qubit| !state.measure(qubit) ^ (p > 0.0 && rng.random_bool(p)))
+            .collect();
+        for (k, qubits) in self.code.z_stabilizers().iter().enumerate() {
+            let parity = qubits.iter().fold(false, |parity, q| parity ^ data[*q]);
+            defects.push(parity ^ previous[k]);
+        }
+        let observable = self
+            .code
+            .logical_z()
+            .iter()
+            .fold(false, |parity, q| parity ^ data[*q]);
+
+        let correction = self.graph.decode(&defects);
+        observable ^ self.graph.observable_flip(&correction)
+    }
+
+    pub fn run(&self, shots: usize) -> MemoryReport {
+        MemoryReport {
+            shots,
+            failures: (0..shots).filter(|_| self.run_shot()).count(),
+            rounds: self.rounds,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        for distance in [3, 5] {
+            let code = RotatedSurfaceCode::new(distance);
+            let overlap =
+                |a: &Vec<usize>, b: &Vec<usize>| a.iter().filter(|q| b.contains(q)).count();
+            for a in code.stabilizers() {
+                for b in code.stabilizers() {
+                    if let (Stabilizer::X(x), Stabilizer::Z(z)) = (a, b) {
+                        assert_eq!(overlap(x, z) % 2, 0);
+                    }
+                }
+                // the logical z commutes with all x stabilizers
+                if let Stabilizer::X(x) = a {
+                    assert_eq!(overlap(x, &code.logical_z()) % 2, 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_single_errors_are_decoded() {
+        let graph = RotatedSurfaceCode::new(5).decoding_graph(3);
+        for (index, edge) in graph.edges().iter().enumerate() {
+            let mut defects = vec![false; graph.no_detectors()];
+            for node in [edge.nodes.0, edge.nodes.1] {
+                if node != graph.boundary() {
+                    defects[node] = true;
+                }
+            }
+            let correction = graph.decode(&defects);
+            assert_eq!(
+                graph.observable_flip(&correction),
+                edge.flips_observable,
+                "edge {index}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_memory_experiment() {
+        let report = MemoryExperiment::new(3, 3, 0.0).run(20);
+        assert_eq!(report.failures, 0);
+
+        let small = MemoryExperiment::new(3, 3, 0.003).run(1000);
+        let large = MemoryExperiment::new(5, 3, 0.003).run(1000);
+        assert!(small.failure_rate_per_round() < 0.05);
+        assert!(large.failures < small.failures);
+    }
+}
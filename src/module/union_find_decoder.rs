@@ -0,0 +1,244 @@
+// Union-find decoder (Delfosse, Nickerson)
+//
+// The decoding graph has a node per detector and one extra boundary node. Every edge is an error
+// that flips the detectors at its ends, it remembers whether it also flips the logical
+// observable. Starting from the detectors that fired, clusters with an odd number of them grow by
+// half an edge in every direction until they are even or reach the boundary. Inside the grown
+// clusters a spanning tree is peeled from the leaves, which gives a correction that explains all
+// detection events.
+use std::collections::VecDeque;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Edge {
+    pub nodes: (usize, usize),
+    pub flips_observable: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct DecodingGraph {
+    no_nodes: usize,
+    edges: Vec<Edge>,
+    // edge indices at every node, the boundary node included
+    incident: Vec<Vec<usize>>,
+}
+
+impl DecodingGraph {
+    /// `no_detectors` detector nodes and the boundary node with index `no_detectors`
+    pub fn new(no_detectors: usize) -> Self {
+        Self {
+            no_nodes: no_detectors + 1,
+            edges: vec![],
+            incident: vec![vec![]; no_detectors + 1],
+        }
+    }
+
+    pub fn boundary(&self) -> usize {
+        self.no_nodes - 1
+    }
+
+    pub fn no_detectors(&self) -> usize {
+        self.no_nodes - 1
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn add_edge(&mut self, a: usize, b: usize, flips_observable: bool) {
+        assert!(a < self.no_nodes && b < self.no_nodes && a != b);
+        self.incident[a].push(self.edges.len());
+        self.incident[b].push(self.edges.len());
+        self.edges.push(Edge {
+            nodes: (a, b),
+            flips_observable,
+        });
+    }
+
+    fn other(&self, edge: usize, node: usize) -> usize {
+        let (a, b) = self.edges[edge].nodes;
+        if a == node {
+            b
+        } else {
+            a
+        }
+    }
+
+    /// edges of a correction for the detectors that fired
+    pub fn decode(&self, defects: &[bool]) -> Vec<usize> {
+        assert_eq!(defects.len(), self.no_detectors());
+        let mut clusters = Clusters::new(self.no_nodes, self.boundary(), defects);
+        let mut support = vec![0u8; self.edges.len()];
+
+        // grow odd clusters until all are even or touch the boundary
+        loop {
+            let odd: Vec<usize> = (0..self.no_nodes)
+                .filter(|node| clusters.is_odd(*node))
+                .collect();
+            if odd.is_empty() {
+                break;
+            }
+            let mut fused = vec![];
+            for node in odd {
+                for edge in &self.incident[node] {
+                    if support[*edge] < 2 {
+                        support[*edge] += 1;
+                        if support[*edge] == 2 {
+                            fused.push(*edge);
+                        }
+                    }
+                }
+            }
+            for edge in fused {
+                let (a, b) = self.edges[edge].nodes;
+                clusters.union(a, b);
+            }
+        }
+
+        self.peel(&support, defects)
+    }
+
+    /// spanning forest of the grown edges, peeled from the leaves
+    fn peel(&self, support: &[u8], defects: &[bool]) -> Vec<usize> {
+        let mut parity: Vec<bool> = defects.to_vec();
+        parity.push(false);
+        let mut visited = vec![false; self.no_nodes];
+        let mut correction = vec![];
+
+        // the boundary goes first so that clusters touching it are rooted there
+        let roots = std::iter::once(self.boundary()).chain(0..self.no_detectors());
+        for root in roots {
+            if visited[root] {
+                continue;
+            }
+            visited[root] = true;
+            let mut order = vec![];
+            let mut queue = VecDeque::from([root]);
+            while let Some(node) = queue.pop_front() {
+                for edge in &self.incident[node] {
+                    let next = self.other(*edge, node);
+                    if support[*edge] == 2 && !visited[next] {
+                        visited[next] = true;
+                        order.push((next, node, *edge));
+                        queue.push_back(next);
+                    }
+                }
+            }
+            for (node, parent, edge) in order.into_iter().rev() {
+                if parity[node] {
+                    parity[node] = false;
+                    parity[parent] ^= true;
+                    correction.push(edge);
+                }
+            }
+        }
+        correction
+    }
+
+    /// whether the correction flips the logical observable
+    pub fn observable_flip(&self, correction: &[usize]) -> bool {
+        correction.iter().fold(false, |flip, edge| {
+            flip ^ self.edges[*edge].flips_observable
+        })
+    }
+}
+
+/// disjoint sets of nodes with the parity of their defects
+struct Clusters {
+    parent: Vec<usize>,
+    odd: Vec<bool>,
+    boundary: Vec<bool>,
+}
+
+impl Clusters {
+    fn new(no_nodes: usize, boundary: usize, defects: &[bool]) -> Self {
+        let mut odd = defects.to_vec();
+        odd.push(false);
+        let mut touches_boundary = vec![false; no_nodes];
+        touches_boundary[boundary] = true;
+        Self {
+            parent: (0..no_nodes).collect(),
+            odd,
+            boundary: touches_boundary,
+        }
+    }
+
+    fn find(&mut self, node: usize) -> usize {
+        let mut root = node;
+        while self.parent[root] != root {
+            root = self.parent[root];
+        }
+        let mut node = node;
+        while self.parent[node] != root {
+            let next = self.parent[node];
+            self.parent[node] = root;
+            node = next;
+        }
+        root
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        self.parent[b] = a;
+        self.odd[a] ^= self.odd[b];
+        self.boundary[a] |= self.boundary[b];
+    }
+
+    /// part of a cluster that still has to grow
+    fn is_odd(&mut self, node: usize) -> bool {
+        let root = self.find(node);
+        self.odd[root] && !self.boundary[root]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    /// repetition code: detectors between neighbouring bits, the ends are boundaries
+    fn line(no_detectors: usize) -> DecodingGraph {
+        let mut graph = DecodingGraph::new(no_detectors);
+        let boundary = graph.boundary();
+        graph.add_edge(boundary, 0, true);
+        for i in 1..no_detectors {
+            graph.add_edge(i - 1, i, false);
+        }
+        graph.add_edge(no_detectors - 1, boundary, false);
+        graph
+    }
+
+    #[test]
+    fn test_repetition_code() {
+        let graph = line(6);
+        // bits 2 and 3 flipped: detectors 1 and 3 fire
+        let mut defects = vec![false; 6];
+        defects[1] = true;
+        defects[3] = true;
+        let correction = graph.decode(&defects);
+        assert_eq!(correction.len(), 2);
+        assert!(!graph.observable_flip(&correction));
+
+        // the first bit flipped: matched to the near boundary, which flips the observable
+        let mut defects = vec![false; 6];
+        defects[0] = true;
+        let correction = graph.decode(&defects);
+        assert_eq!(correction, vec![0]);
+        assert!(graph.observable_flip(&correction));
+
+        // every correction explains the detection events
+        for pattern in 0..64usize {
+            let defects: Vec<bool> = (0..6).map(|i| (pattern >> i) & 1 == 1).collect();
+            let mut parity = defects.clone();
+            parity.push(false);
+            for edge in graph.decode(&defects) {
+                let (a, b) = graph.edges()[edge].nodes;
+                parity[a] ^= true;
+                parity[b] ^= true;
+            }
+            assert!(parity[..6].iter().all(|fired| !fired));
+        }
+    }
+}
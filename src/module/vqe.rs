@@ -0,0 +1,202 @@
+// Variational quantum eigensolver
+//
+// A parameterized circuit, the ansatz, prepares a trial state |psi(theta)> and the energy
+// <psi(theta)|H|psi(theta)> of a pauli sum hamiltonian is handed to a classical optimizer. By the
+// variational principle every energy is an upper bound of the ground energy, so minimizing over
+// theta approaches the ground state as far as the ansatz can reach it.
+//
+// The energy is exact by default, with `with_shots` it is estimated from samples as on hardware,
+// which calls for an optimizer that copes with noise such as SPSA.
+//
+// The trial states run on the dense state vector unless `with_backend` picks another simulator.
+use crate::module::backend::Backend;
+use crate::module::optimizers::{OptimizationResult, Optimizer};
+use crate::module::pauli::PauliSum;
+use crate::module::state_vector::{rotation_y, rotation_z, StateVector};
+use std::marker::PhantomData;
+
+pub trait Ansatz {
+    fn no_qubits(&self) -> usize;
+
+    fn no_parameters(&self) -> usize;
+
+    /// apply the circuit for `parameters` to `state`, which starts in |00..0>
+    fn apply(&self, state: &mut dyn Backend, parameters: &[f64]);
+}
+
+/// layers of y and z rotations on every qubit, each followed by a line of cnots, and a final
+/// rotation layer. Cheap on hardware as it only entangles neighbours
+#[derive(Clone, Debug)]
+pub struct HardwareEfficient {
+    no_qubits: usize,
+    layers: usize,
+}
+
+impl HardwareEfficient {
+    pub fn new(no_qubits: usize, layers: usize) -> Self {
+        Self { no_qubits, layers }
+    }
+}
+
+impl Ansatz for HardwareEfficient {
+    fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    fn no_parameters(&self) -> usize {
+        2 * self.no_qubits * (self.layers + 1)
+    }
+
+    fn apply(&self, state: &mut dyn Backend, parameters: &[f64]) {
+        assert_eq!(parameters.len(), self.no_parameters());
+        let mut angles = parameters.iter();
+        for layer in 0..=self.layers {
+            for qubit in 0..self.no_qubits {
+                state.apply(&rotation_y(*angles.next().unwrap()), qubit);
+                state.apply(&rotation_z(*angles.next().unwrap()), qubit);
+            }
+            if layer < self.layers {
+                for qubit in 1..self.no_qubits {
+                    state.cnot(qubit - 1, qubit);
+                }
+            }
+        }
+    }
+}
+
+/// an ansatz from any circuit
+pub struct CustomAnsatz<F> {
+    no_qubits: usize,
+    no_parameters: usize,
+    circuit: F,
+}
+
+impl<F: Fn(&mut dyn Backend, &[f64])> CustomAnsatz<F> {
+    pub fn new(no_qubits: usize, no_parameters: usize, circuit: F) -> Self {
+        Self {
+            no_qubits,
+            no_parameters,
+            circuit,
+        }
+    }
+}
+
+impl<F: Fn(&mut dyn Backend, &[f64])> Ansatz for CustomAnsatz<F> {
+    fn no_qubits(&self) -> usize {
+        self.no_qubits
+    }
+
+    fn no_parameters(&self) -> usize {
+        self.no_parameters
+    }
+
+    fn apply(&self, state: &mut dyn Backend, parameters: &[f64]) {
+        assert_eq!(parameters.len(), self.no_parameters);
+        (self.circuit)(state, parameters);
+    }
+}
+
+pub struct Vqe<A, B = StateVector> {
+    hamiltonian: PauliSum,
+    ansatz: A,
+    shots: Option<usize>,
+    backend: PhantomData<B>,
+}
+
+impl<A: Ansatz> Vqe<A> {
+    pub fn new(hamiltonian: PauliSum, ansatz: A) -> Self {
+        assert!(
+            hamiltonian.min_qubits() <= ansatz.no_qubits(),
+            "the hamiltonian acts on more qubits than the ansatz"
+        );
+        Self {
+            hamiltonian,
+            ansatz,
+            shots: None,
+            backend: PhantomData,
+        }
+    }
+}
+
+impl<A: Ansatz, B: Backend + Clone> Vqe<A, B> {
+    /// prepare the trial states on the simulator `C`
+    pub fn with_backend<C: Backend + Clone>(self) -> Vqe<A, C> {
+        Vqe {
+            hamiltonian: self.hamiltonian,
+            ansatz: self.ansatz,
+            shots: self.shots,
+            backend: PhantomData,
+        }
+    }
+
+    /// estimate every energy from `shots` samples per measurement group instead of exactly
+    pub fn with_shots(mut self, shots: usize) -> Self {
+        self.shots = Some(shots);
+        self
+    }
+
+    pub fn hamiltonian(&self) -> &PauliSum {
+        &self.hamiltonian
+    }
+
+    pub fn ansatz(&self) -> &A {
+        &self.ansatz
+    }
+
+    /// the trial state for `parameters`
+    pub fn state(&self, parameters: &[f64]) -> B {
+        let mut state = B::allocate(self.ansatz.no_qubits());
+        self.ansatz.apply(&mut state, parameters);
+        state
+    }
+
+    pub fn energy(&self, parameters: &[f64]) -> f64 {
+        let state = self.state(parameters);
+        match self.shots {
+            None => self.hamiltonian.expectation(&state),
+            Some(shots) => self.hamiltonian.estimate(&state, shots),
+        }
+    }
+
+    /// minimize the energy starting from `initial`
+    pub fn run(&self, optimizer: &dyn Optimizer, initial: &[f64]) -> OptimizationResult {
+        assert_eq!(initial.len(), self.ansatz.no_parameters());
+        optimizer.minimize(&mut |parameters| self.energy(parameters), initial)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::module::mps::Mps;
+    use crate::module::optimizers::NelderMead;
+    use crate::module::sparse_state::SparseState;
+
+    #[test]
+    fn test_transverse_field_ising() {
+        let hamiltonian: PauliSum = "-Z0Z1 - Z1Z2 - 0.8*X0 - 0.8*X1 - 0.8*X2".parse().unwrap();
+        let exact = hamiltonian.ground_energy(3);
+
+        let vqe = Vqe::new(hamiltonian, HardwareEfficient::new(3, 2));
+        let initial = vec![0.1; vqe.ansatz().no_parameters()];
+        let result = vqe.run(&NelderMead::new().with_max_iterations(6000), &initial);
+        // above the ground energy, but close
+        assert!(result.value >= exact - 1e-9);
+        assert!(result.value - exact < 1e-2, "{} {exact}", result.value);
+    }
+
+    #[test]
+    fn test_backends() {
+        let hamiltonian: PauliSum = "-Z0Z1 - Z1Z2 - 0.8*X0 - 0.8*X1 - 0.8*X2".parse().unwrap();
+        let vqe = Vqe::new(hamiltonian, HardwareEfficient::new(3, 2));
+        let parameters: Vec<f64> = (0..vqe.ansatz().no_parameters())
+            .map(|i| 0.3 * i as f64)
+            .collect();
+        let dense = vqe.energy(&parameters);
+        let vqe = vqe.with_backend::<SparseState>();
+        assert!((vqe.energy(&parameters) - dense).abs() < 1e-9);
+        let vqe = vqe.with_backend::<Mps>();
+        assert!((vqe.energy(&parameters) - dense).abs() < 1e-9);
+    }
+}